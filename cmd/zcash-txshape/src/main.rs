@@ -1,12 +1,18 @@
 //! Binary entrypoint for zcash-txshape.
 
+use anyhow::Context;
 use clap::Parser;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use tracing::info;
+use zcash_txshape::alert;
 use zcash_txshape::collector;
 use zcash_txshape::config::Config;
+use zcash_txshape::model::divergence::BucketSort;
+use zcash_txshape::model::score::BaselineFile;
 use zcash_txshape::report;
 use zcash_txshape::storage;
+use zcash_txshape::synth;
 
 #[derive(clap::Parser)]
 #[command(name = "zcash-txshape", about = "Transaction Shape Analyzer for Zcash")]
@@ -25,16 +31,159 @@ enum Command {
         #[arg(long)]
         range: String,
     },
+    /// Flag and store windows where one shape tuple floods consecutive blocks (thresholds in [flood]).
+    DetectFloods {
+        /// Block range to scan (must be collected already).
+        #[arg(long)]
+        range: String,
+        /// Output format: text (default) or json.
+        #[arg(long, default_value = "text")]
+        output: String,
+    },
+    /// Score the latest window against a trailing baseline and threshold rules ([alerts]),
+    /// deliver new alerts to the configured sinks, and exit with status 1 when any alert fires.
+    Alerts {
+        /// Output format: text (default) or json.
+        #[arg(long, default_value = "text")]
+        output: String,
+        /// Exclude blocks inside flood windows stored by detect-floods.
+        #[arg(long)]
+        exclude_floods: bool,
+        /// Count coinbase transactions as user transactions.
+        #[arg(long)]
+        include_coinbase: bool,
+    },
+    /// Score wallet transaction shapes against stored chain traffic (surprisal, share, rank).
+    Score {
+        /// File with a JSON TxShape (or array of them) or one raw tx hex per line; "-" for stdin.
+        #[arg(long)]
+        input: PathBuf,
+        /// Reference block range (default: the last --days of stored blocks).
+        #[arg(long)]
+        range: Option<String>,
+        /// Reference window in days, ending at the latest stored block.
+        #[arg(long, default_value = "7")]
+        days: u32,
+        /// Output format: text (default) or json.
+        #[arg(long, default_value = "text")]
+        output: String,
+        /// Exclude blocks inside flood windows stored by detect-floods.
+        #[arg(long)]
+        exclude_floods: bool,
+        /// Count coinbase transactions as user transactions.
+        #[arg(long)]
+        include_coinbase: bool,
+    },
+    /// Export the shape distribution of a stored range as a baseline file for `ci`.
+    ExportBaseline {
+        /// Block range (default: the last --days of stored blocks).
+        #[arg(long)]
+        range: Option<String>,
+        /// Window in days, ending at the latest stored block.
+        #[arg(long, default_value = "30")]
+        days: u32,
+        /// Output file.
+        #[arg(long)]
+        out: PathBuf,
+        /// Exclude blocks inside flood windows stored by detect-floods.
+        #[arg(long)]
+        exclude_floods: bool,
+        /// Count coinbase transactions as user transactions.
+        #[arg(long)]
+        include_coinbase: bool,
+    },
+    /// Check wallet fixture shapes against a baseline file; exits with status 1 when any shape
+    /// is less common than the thresholds. Needs no config or database.
+    Ci {
+        /// Fixture file: JSON TxShape (or array) or one raw tx hex per line.
+        #[arg(long)]
+        fixtures: PathBuf,
+        /// Baseline file written by export-baseline.
+        #[arg(long)]
+        baseline: PathBuf,
+        /// Minimum share of baseline transactions with the identical shape.
+        #[arg(long, default_value = "0.001")]
+        min_share: f64,
+        /// Maximum rank of the shape in the baseline (1 = most common).
+        #[arg(long)]
+        max_rank: Option<usize>,
+        /// Result format: json (default) or junit.
+        #[arg(long, default_value = "json")]
+        format: String,
+        /// Write results to this file instead of stdout.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Simulate padding policies ([[padding]] in config) on stored shape tuples.
+    SimulatePadding {
+        /// Block range (default: the last --days of stored blocks).
+        #[arg(long)]
+        range: Option<String>,
+        /// Window in days, ending at the latest stored block.
+        #[arg(long, default_value = "7")]
+        days: u32,
+        /// Only simulate these policies (repeatable; default: all configured).
+        #[arg(long = "policy")]
+        policies: Vec<String>,
+        /// Output format: text (default) or json.
+        #[arg(long, default_value = "text")]
+        output: String,
+        /// Exclude blocks inside flood windows stored by detect-floods.
+        #[arg(long)]
+        exclude_floods: bool,
+        /// Count coinbase transactions as user transactions.
+        #[arg(long)]
+        include_coinbase: bool,
+    },
+    /// Generate synthetic blocks from a scenario file, into the database or via a mock node.
+    Synth {
+        #[command(subcommand)]
+        mode: SynthMode,
+    },
     /// Produce reports from stored statistics.
     Report {
         /// Output format: text (default) or json.
         #[arg(long, default_value = "text")]
         output: String,
+        /// Exclude blocks inside flood windows stored by detect-floods.
+        #[arg(long)]
+        exclude_floods: bool,
+        /// Count coinbase transactions as user transactions.
+        #[arg(long)]
+        include_coinbase: bool,
         #[command(subcommand)]
         kind: ReportKind,
     },
 }
 
+#[derive(clap::Subcommand)]
+enum SynthMode {
+    /// Write generated per-block stats into the database.
+    Write {
+        /// Scenario TOML (profiles, bursts, rollouts).
+        #[arg(long)]
+        scenario: PathBuf,
+        /// Database path (default: storage.db_path from config).
+        #[arg(long)]
+        db: Option<PathBuf>,
+        /// Write the generated ground truth as JSON to this file.
+        #[arg(long)]
+        truth: Option<PathBuf>,
+    },
+    /// Serve generated blocks over zcashd-style JSON-RPC for `collect`.
+    Serve {
+        /// Scenario TOML (profiles, bursts, rollouts).
+        #[arg(long)]
+        scenario: PathBuf,
+        /// Listen address.
+        #[arg(long, default_value = "127.0.0.1:18232")]
+        listen: String,
+        /// Write the generated ground truth as JSON to this file.
+        #[arg(long)]
+        truth: Option<PathBuf>,
+    },
+}
+
 #[derive(clap::Subcommand)]
 enum ReportKind {
     /// Daily summary for the last N days.
@@ -50,12 +199,80 @@ enum ReportKind {
         range_a: String,
         #[arg(long)]
         range_b: String,
+        /// Number of per-bucket changes shown per histogram (text output).
+        #[arg(long, default_value = "5")]
+        top: usize,
+        /// Bucket order: abs-share-delta, share-delta, ratio, count-delta or bucket.
+        #[arg(long, default_value = "abs-share-delta")]
+        sort: String,
+    },
+    /// Compare any number of labeled block ranges (e.g. --range pre-nu5=0..1000 --range post-nu5=1000..2000).
+    Compare {
+        /// Labeled range LABEL=START..END (or START..END, labeled by itself); repeat for each range.
+        #[arg(long = "range", required = true)]
+        ranges: Vec<String>,
+    },
+    /// Detect regime shifts in per-window metrics (CUSUM and Bayesian online change points).
+    Changepoints {
+        /// Block range to scan (e.g. 1600000..1700000).
+        #[arg(long)]
+        range: String,
+        /// Window size in blocks (default: one day).
+        #[arg(long, default_value = "144")]
+        window: u32,
+        /// Minimum confidence (0..1) for a reported change point.
+        #[arg(long, default_value = "0.95")]
+        min_confidence: f64,
+    },
+    /// Block-level distributions (tx count, fullness, shielded share, interval) and their
+    /// correlation with per-block shape diversity.
+    Blocks {
+        /// Block range (e.g. 1700000..1710000).
+        #[arg(long)]
+        range: String,
+    },
+    /// Pairwise mutual information and conditional entropy between shape-tuple dimensions.
+    MutualInfo {
+        /// Block range (e.g. 1700000..1710000).
+        #[arg(long)]
+        range: String,
+    },
+    /// Version groups and consensus branch ids, with older version groups mined after an
+    /// upgrade introduced a newer one (activation heights of collector.network).
+    Branches {
+        /// Block range (e.g. 2700000..2800000).
+        #[arg(long)]
+        range: String,
     },
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> anyhow::Result<ExitCode> {
     let cli = Cli::parse();
+    // ci only reads files, so it runs without a config or database.
+    if let Command::Ci {
+        fixtures,
+        baseline,
+        min_share,
+        max_rank,
+        format,
+        out,
+    } = &cli.command
+    {
+        let failures = run_ci(
+            fixtures,
+            baseline,
+            *min_share,
+            *max_rank,
+            format,
+            out.as_deref(),
+        )?;
+        return Ok(if failures > 0 {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
     let config_path = cli
         .config
         .or_else(|| {
@@ -73,21 +290,203 @@ async fn main() -> anyhow::Result<()> {
         )
         .init();
 
+    // Commands that gate CI jobs or cron set this instead of exiting mid-run.
+    let mut exit = ExitCode::SUCCESS;
     match cli.command {
         Command::Collect { range } => {
             let (low, high) = parse_range(&range)?;
             let db = storage::open_db(&config.storage.db_path)?;
             collector::run_collect(&config, &db, low, high).await?;
         }
-        Command::Report { output, kind } => {
+        Command::DetectFloods { range, output } => {
+            let (low, high) = parse_range(&range)?;
+            let db = storage::open_db(&config.storage.db_path)?;
+            let opts = report::ReportOptions {
+                json: output.eq_ignore_ascii_case("json"),
+                ..Default::default()
+            };
+            storage::flag_floods(&db, low, high, &config.flood)?;
+            report::flood_windows(&db, low, high, &config.flood, opts)?;
+        }
+        Command::Alerts {
+            output,
+            exclude_floods,
+            include_coinbase,
+        } => {
+            let db = storage::open_db(&config.storage.db_path)?;
+            let opts = report::ReportOptions {
+                json: output.eq_ignore_ascii_case("json"),
+                exclude_floods,
+                include_coinbase,
+            };
+            let result = report::anomaly_alerts(&db, &config.alerts, opts)?;
+            if !config.alerts.sinks.is_empty() {
+                let now = chrono::Utc::now().timestamp();
+                let sent = alert::deliver(&db, &config.alerts, &result, now).await?;
+                info!(delivered = sent.len(), "alerts delivered");
+            }
+            if !result.alerts.is_empty() {
+                exit = ExitCode::FAILURE;
+            }
+        }
+        Command::Score {
+            input,
+            range,
+            days,
+            output,
+            exclude_floods,
+            include_coinbase,
+        } => {
+            let text = if input.as_os_str() == "-" {
+                std::io::read_to_string(std::io::stdin())?
+            } else {
+                std::fs::read_to_string(&input)?
+            };
+            let shapes = collector::rawtx::shapes_from_input(&text)?;
             let db = storage::open_db(&config.storage.db_path)?;
-            let json = output.eq_ignore_ascii_case("json");
+            let (low, high) = match range {
+                Some(r) => parse_range(&r)?,
+                None => report::recent_range(&db, days.saturating_mul(24 * 6))?
+                    .ok_or_else(|| anyhow::anyhow!("no block data in database"))?,
+            };
+            let opts = report::ReportOptions {
+                json: output.eq_ignore_ascii_case("json"),
+                exclude_floods,
+                include_coinbase,
+            };
+            report::score_shapes(&db, &shapes, low, high, opts)?;
+        }
+        Command::ExportBaseline {
+            range,
+            days,
+            out,
+            exclude_floods,
+            include_coinbase,
+        } => {
+            let db = storage::open_db(&config.storage.db_path)?;
+            let (low, high) = match range {
+                Some(r) => parse_range(&r)?,
+                None => report::recent_range(&db, days.saturating_mul(24 * 6))?
+                    .ok_or_else(|| anyhow::anyhow!("no block data in database"))?,
+            };
+            let opts = report::ReportOptions {
+                exclude_floods,
+                include_coinbase,
+                ..Default::default()
+            };
+            let file = report::export_baseline(&db, low, high, opts, &out)?;
+            info!(
+                low,
+                high,
+                txs = file.distribution.total,
+                shapes = file.distribution.shapes.len(),
+                path = %out.display(),
+                "baseline exported"
+            );
+        }
+        Command::SimulatePadding {
+            range,
+            days,
+            policies,
+            output,
+            exclude_floods,
+            include_coinbase,
+        } => {
+            let selected: Vec<_> = if policies.is_empty() {
+                config.padding.clone()
+            } else {
+                policies
+                    .iter()
+                    .map(|name| {
+                        config
+                            .padding
+                            .iter()
+                            .find(|p| &p.name == name)
+                            .cloned()
+                            .ok_or_else(|| anyhow::anyhow!("unknown padding policy {:?}", name))
+                    })
+                    .collect::<anyhow::Result<_>>()?
+            };
+            let db = storage::open_db(&config.storage.db_path)?;
+            let (low, high) = match range {
+                Some(r) => parse_range(&r)?,
+                None => report::recent_range(&db, days.saturating_mul(24 * 6))?
+                    .ok_or_else(|| anyhow::anyhow!("no block data in database"))?,
+            };
+            let opts = report::ReportOptions {
+                json: output.eq_ignore_ascii_case("json"),
+                exclude_floods,
+                include_coinbase,
+            };
+            report::simulate_padding(&db, low, high, &selected, opts)?;
+        }
+        Command::Synth { mode } => match mode {
+            SynthMode::Write {
+                scenario,
+                db,
+                truth,
+            } => {
+                let scenario = synth::Scenario::load(&scenario)?;
+                let (blocks, ground_truth) = synth::generate(&scenario)?;
+                let db = storage::open_db(db.as_deref().unwrap_or(&config.storage.db_path))?;
+                synth::write_to_db(&db, &blocks)?;
+                write_truth(truth.as_deref(), &ground_truth)?;
+                info!(
+                    low = scenario.low,
+                    high = scenario.high,
+                    n_txs = ground_truth.n_txs,
+                    "synthetic blocks written"
+                );
+            }
+            SynthMode::Serve {
+                scenario,
+                listen,
+                truth,
+            } => {
+                let scenario = synth::Scenario::load(&scenario)?;
+                let (blocks, ground_truth) = synth::generate(&scenario)?;
+                write_truth(truth.as_deref(), &ground_truth)?;
+                let listener = tokio::net::TcpListener::bind(&listen).await?;
+                info!(
+                    addr = %listener.local_addr()?,
+                    low = scenario.low,
+                    high = scenario.high,
+                    "serving synthetic blocks"
+                );
+                let blocks = std::sync::Arc::new(synth::mock_node::block_map(blocks));
+                synth::mock_node::serve(listener, blocks).await?;
+            }
+        },
+        Command::Ci { .. } => unreachable!("handled before config load"),
+        Command::Report {
+            output,
+            exclude_floods,
+            include_coinbase,
+            kind,
+        } => {
+            let db = storage::open_db(&config.storage.db_path)?;
+            let opts = report::ReportOptions {
+                json: output.eq_ignore_ascii_case("json"),
+                exclude_floods,
+                include_coinbase,
+            };
             match kind {
-                ReportKind::Daily { days } => report::daily_summary(&db, days, json)?,
-                ReportKind::Weekly => report::weekly_summary(&db, json)?,
-                ReportKind::Diff { range_a, range_b } => {
+                ReportKind::Daily { days } => report::daily_summary(&db, days, opts)?,
+                ReportKind::Weekly => report::weekly_summary(&db, opts)?,
+                ReportKind::Diff {
+                    range_a,
+                    range_b,
+                    top,
+                    sort,
+                } => {
                     let (a_lo, a_hi) = parse_range(&range_a)?;
                     let (b_lo, b_hi) = parse_range(&range_b)?;
+                    let sort = BucketSort::from_label(&sort).with_context(|| {
+                        format!(
+                            "unknown bucket sort {:?} (expected abs-share-delta, share-delta, ratio, count-delta or bucket)",
+                            sort
+                        )
+                    })?;
                     // Auto-collect missing ranges so diff works without a prior collect.
                     let blocks_a = storage::block_heights_in_range(&db, a_lo, a_hi)?;
                     let blocks_b = storage::block_heights_in_range(&db, b_lo, b_hi)?;
@@ -99,14 +498,109 @@ async fn main() -> anyhow::Result<()> {
                         info!(range = %range_b, "collecting range B (no block data in database)");
                         collector::run_collect(&config, &db, b_lo, b_hi).await?;
                     }
-                    report::range_diff(&db, a_lo, a_hi, b_lo, b_hi, json)?;
+                    let view = report::BucketView { top, sort };
+                    report::range_diff(&db, a_lo, a_hi, b_lo, b_hi, view, opts)?;
+                }
+                ReportKind::Compare { ranges } => {
+                    let ranges = ranges
+                        .iter()
+                        .map(|r| parse_labeled_range(r))
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    if ranges.len() < 2 {
+                        anyhow::bail!("compare needs at least two --range arguments");
+                    }
+                    // Auto-collect missing ranges, as for diff.
+                    for r in &ranges {
+                        if storage::block_heights_in_range(&db, r.low, r.high)?.is_empty() {
+                            info!(label = %r.label, "collecting range (no block data in database)");
+                            collector::run_collect(&config, &db, r.low, r.high).await?;
+                        }
+                    }
+                    report::range_compare(&db, &ranges, opts)?;
+                }
+                ReportKind::Changepoints {
+                    range,
+                    window,
+                    min_confidence,
+                } => {
+                    let (low, high) = parse_range(&range)?;
+                    if window == 0 {
+                        anyhow::bail!("window must be positive");
+                    }
+                    report::change_points(&db, low, high, window, min_confidence, opts)?;
+                }
+                ReportKind::Blocks { range } => {
+                    let (low, high) = parse_range(&range)?;
+                    report::block_composition(&db, low, high, opts)?;
+                }
+                ReportKind::MutualInfo { range } => {
+                    let (low, high) = parse_range(&range)?;
+                    report::mutual_information(&db, low, high, opts)?;
+                }
+                ReportKind::Branches { range } => {
+                    let (low, high) = parse_range(&range)?;
+                    report::version_lag(&db, low, high, config.collector.network, opts)?;
                 }
             }
         }
     }
+    Ok(exit)
+}
+
+fn write_truth(path: Option<&Path>, truth: &synth::GroundTruth) -> anyhow::Result<()> {
+    if let Some(path) = path {
+        std::fs::write(path, serde_json::to_string_pretty(truth)? + "\n")?;
+    }
     Ok(())
 }
 
+/// Run the CI check and write results; returns the number of failing shapes.
+fn run_ci(
+    fixtures: &Path,
+    baseline: &Path,
+    min_share: f64,
+    max_rank: Option<usize>,
+    format: &str,
+    out: Option<&Path>,
+) -> anyhow::Result<usize> {
+    let read = |p: &Path| {
+        std::fs::read_to_string(p).with_context(|| format!("failed to read {}", p.display()))
+    };
+    let shapes = collector::rawtx::shapes_from_input(&read(fixtures)?)?;
+    let baseline = BaselineFile::from_json(&read(baseline)?)?;
+    let thresholds = report::CiThresholds {
+        min_share,
+        max_rank,
+    };
+    let result = report::ci_check(&shapes, &baseline, thresholds);
+    let text = match format.to_ascii_lowercase().as_str() {
+        "json" => serde_json::to_string_pretty(&result)? + "\n",
+        "junit" => report::junit_xml(&result),
+        other => anyhow::bail!("unknown ci format {:?} (expected json or junit)", other),
+    };
+    match out {
+        Some(path) => std::fs::write(path, text)?,
+        None => print!("{}", text),
+    }
+    Ok(result.failures)
+}
+
+/// Parse "LABEL=START..END"; without a label the range text itself is the label.
+fn parse_labeled_range(s: &str) -> anyhow::Result<report::LabeledRange> {
+    let s = s.trim();
+    let (label, range) = match s.split_once('=') {
+        Some((label, range)) if !label.trim().is_empty() => (label.trim(), range),
+        Some(_) => anyhow::bail!("range label must be non-empty"),
+        None => (s, s),
+    };
+    let (low, high) = parse_range(range)?;
+    Ok(report::LabeledRange {
+        label: label.to_string(),
+        low,
+        high,
+    })
+}
+
 fn parse_range(s: &str) -> anyhow::Result<(u32, u32)> {
     let s = s.trim();
     let (a, b) = s
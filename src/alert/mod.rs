@@ -0,0 +1,175 @@
+//! Alert rules and delivery: fixed bands over the latest window and push delivery to sinks
+//! with per-alert dedup and cool-down.
+
+mod sink;
+
+use crate::config::{AlertRule, AlertsConfig};
+use crate::model::baseline::{window_metric, Alert};
+use crate::model::ShapeStats;
+use crate::storage;
+use rusqlite::Connection;
+use serde::Serialize;
+use tracing::warn;
+
+/// Alerts raised for one scored window [window_low, window_high).
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowAlerts {
+    pub window_low: u32,
+    pub window_high: u32,
+    pub alerts: Vec<Alert>,
+}
+
+/// Alert as delivered to sinks.
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertEvent {
+    /// Dedup key: "kind:subject".
+    pub key: String,
+    /// Unix time of delivery.
+    pub sent_at: i64,
+    pub window_low: u32,
+    pub window_high: u32,
+    #[serde(flatten)]
+    pub alert: Alert,
+}
+
+/// Check `rules` against `stats`; one alert per rule whose metric is outside its band.
+pub fn evaluate_rules(stats: &ShapeStats, rules: &[AlertRule]) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+    for rule in rules {
+        let Some(value) = window_metric(stats, &rule.metric) else {
+            continue;
+        };
+        let breach = match (rule.min, rule.max) {
+            (Some(lo), _) if value < lo => Some((lo, value - lo)),
+            (_, Some(hi)) if value > hi => Some((hi, value - hi)),
+            _ => None,
+        };
+        if let Some((bound, score)) = breach {
+            alerts.push(Alert {
+                kind: "rule".to_string(),
+                subject: rule.name.clone(),
+                value,
+                baseline: 0.0,
+                score,
+                threshold: bound,
+            });
+        }
+    }
+    alerts
+}
+
+/// Deliver `window`'s alerts to the configured sinks at unix time `now`, skipping alerts
+/// already delivered to a sink for the same window (dedup) or within `cooldown_secs` of their
+/// last delivery there. Delivery is recorded per sink, so only the sinks that failed retry on
+/// the next run; any failure is returned as an error after the successful sinks are recorded.
+/// Returns the events delivered to at least one sink.
+pub async fn deliver(
+    conn: &Connection,
+    cfg: &AlertsConfig,
+    window: &WindowAlerts,
+    now: i64,
+) -> anyhow::Result<Vec<AlertEvent>> {
+    let mut delivered: Vec<AlertEvent> = Vec::new();
+    let mut failed = 0usize;
+    for s in &cfg.sinks {
+        let sink_id = s.id();
+        let events = pending_events(conn, cfg, window, &sink_id, now)?;
+        if events.is_empty() {
+            continue;
+        }
+        if let Err(e) = sink::send(s, &events).await {
+            warn!(sink = %sink_id, "alert sink failed: {:#}", e);
+            failed += 1;
+            continue;
+        }
+        for e in events {
+            storage::record_alert_sent(conn, &e.key, &sink_id, now, e.window_high)?;
+            if !delivered.iter().any(|d| d.key == e.key) {
+                delivered.push(e);
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} alert sinks failed", failed, cfg.sinks.len());
+    }
+    Ok(delivered)
+}
+
+/// Alerts of `window` not yet delivered to `sink_id` and outside its cool-down.
+fn pending_events(
+    conn: &Connection,
+    cfg: &AlertsConfig,
+    window: &WindowAlerts,
+    sink_id: &str,
+    now: i64,
+) -> anyhow::Result<Vec<AlertEvent>> {
+    let mut events = Vec::new();
+    for alert in &window.alerts {
+        let key = format!("{}:{}", alert.kind, alert.subject);
+        if let Some((sent_at, window_high)) = storage::alert_last_sent(conn, &key, sink_id)? {
+            let cooling = now.saturating_sub(sent_at) < cfg.cooldown_secs as i64;
+            if window_high == window.window_high || cooling {
+                continue;
+            }
+        }
+        events.push(AlertEvent {
+            key,
+            sent_at: now,
+            window_low: window.window_low,
+            window_high: window.window_high,
+            alert: alert.clone(),
+        });
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::TxShape;
+
+    fn rule(name: &str, metric: &str, min: Option<f64>, max: Option<f64>) -> AlertRule {
+        AlertRule {
+            name: name.to_string(),
+            metric: metric.to_string(),
+            min,
+            max,
+        }
+    }
+
+    #[test]
+    fn test_evaluate_rules() {
+        let shielded = TxShape {
+            n_vin: 0,
+            n_vout: 0,
+            n_joinsplit: 0,
+            n_sapling_spend: 1,
+            n_sapling_output: 2,
+            n_orchard_action: 0,
+            size_bucket: 2,
+            version: 4,
+            ..Default::default()
+        };
+        let transparent = TxShape {
+            n_vin: 1,
+            n_vout: 2,
+            n_sapling_spend: 0,
+            n_sapling_output: 0,
+            ..shielded.clone()
+        };
+        let stats = ShapeStats::from_shapes(&[shielded, transparent.clone(), transparent]);
+        let rules = [
+            rule("low-shielded", "shielded_share", Some(0.5), None),
+            rule("band", "shielded_share", Some(0.1), Some(0.9)),
+            rule("busy", "n_txs", None, Some(2.0)),
+        ];
+        let alerts = evaluate_rules(&stats, &rules);
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].subject, "low-shielded");
+        assert!((alerts[0].value - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(alerts[0].threshold, 0.5);
+        assert!(alerts[0].score < 0.0);
+        assert_eq!(alerts[1].subject, "busy");
+        assert_eq!(alerts[1].score, 1.0);
+    }
+}
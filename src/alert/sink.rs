@@ -0,0 +1,41 @@
+//! Alert sinks: JSON webhook, append-only NDJSON file, stdout.
+
+use super::AlertEvent;
+use crate::config::SinkConfig;
+use std::io::Write;
+use std::time::Duration;
+
+/// Send all `events` to one sink.
+pub(super) async fn send(sink: &SinkConfig, events: &[AlertEvent]) -> anyhow::Result<()> {
+    match sink {
+        SinkConfig::Webhook { url, timeout_secs } => {
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(*timeout_secs))
+                .build()?;
+            client
+                .post(url)
+                .json(&serde_json::json!({ "alerts": events }))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        SinkConfig::File { path } => {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            let mut buf = Vec::new();
+            for e in events {
+                serde_json::to_writer(&mut buf, e)?;
+                buf.push(b'\n');
+            }
+            file.write_all(&buf)?;
+        }
+        SinkConfig::Stdout => {
+            for e in events {
+                println!("{}", serde_json::to_string(e)?);
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,27 +1,47 @@
 //! Block/transaction data collection (read-only). Extracts shape metadata only.
 
+pub mod rawtx;
+pub mod script;
+
 use crate::config::Config;
-use crate::model::{ShapeStats, TxShape};
+use crate::model::block::BlockMeta;
+use crate::model::format::{ConsensusBranch, ExpiryKind, LockTimeKind, OrchardFlags, VersionGroup};
+use crate::model::{ScriptCounts, ShapeStats, TxShape};
 use crate::storage;
-use crate::util::size_bucket;
+use crate::util::{hex_decode, size_bucket};
 use base64::Engine;
 use rusqlite::Connection;
 use serde::Deserialize;
+use std::collections::HashSet;
 use std::time::Duration;
 use tracing::info;
 
 /// zcashd getblock verbosity=2 response (subset we need).
 #[derive(Debug, Deserialize)]
 struct BlockResponse {
-    #[allow(dead_code)]
     height: Option<u32>,
+    size: Option<u32>,
+    time: Option<i64>,
     tx: Option<Vec<TxResponse>>,
 }
 
+/// Shapes of a fetched block, with the block-level fields `collect` keeps.
+struct FetchedBlock {
+    size: Option<u32>,
+    time: Option<i64>,
+    shapes: Vec<TxShape>,
+}
+
 #[derive(Debug, Deserialize)]
 struct TxResponse {
     size: Option<u32>,
     version: Option<u32>,
+    locktime: Option<u32>,
+    expiryheight: Option<u32>,
+    /// Hex, present for overwintered transactions.
+    versiongroupid: Option<String>,
+    /// Hex, present for v5+ transactions when the node reports it.
+    consensusbranchid: Option<String>,
     vin: Option<Vec<serde_json::Value>>,
     vout: Option<Vec<serde_json::Value>>,
     vjoinsplit: Option<Vec<serde_json::Value>>,
@@ -30,14 +50,47 @@ struct TxResponse {
     #[serde(rename = "vShieldedOutput")]
     v_shielded_output: Option<Vec<serde_json::Value>>,
     orchard: Option<OrchardPart>,
+    /// ZSA issuance bundle (v6+). Speculative: no node release reports v6 transactions yet,
+    /// so this and the other ZSA field names follow the draft ZIP 230 field names.
+    issuance: Option<IssuancePart>,
 }
 
 #[derive(Debug, Deserialize)]
 struct OrchardPart {
     actions: Option<Vec<serde_json::Value>>,
+    flags: Option<OrchardFlagsPart>,
+    /// ZSA asset burns (v6+). Speculative field name, see `TxResponse::issuance`.
+    burn: Option<Vec<AssetBurnPart>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssetBurnPart {
+    /// Asset base (hex).
+    asset: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssuancePart {
+    actions: Option<Vec<IssueActionPart>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueActionPart {
+    /// Asset description hash (hex); with the bundle's issuer key it identifies the asset.
+    /// Speculative field name, see `TxResponse::issuance`.
+    asset_desc_hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OrchardFlagsPart {
+    enable_spends: bool,
+    enable_outputs: bool,
 }
 
-fn extract_shape(tx: &TxResponse) -> TxShape {
+/// Shape of `tx` mined at `height` (needed to classify the expiry height).
+fn extract_shape(tx: &TxResponse, height: Option<u32>) -> TxShape {
     let n_vin = tx.vin.as_ref().map(|v| v.len()).unwrap_or(0) as u32;
     let n_vout = tx.vout.as_ref().map(|v| v.len()).unwrap_or(0) as u32;
     let n_joinsplit = tx.vjoinsplit.as_ref().map(|v| v.len()).unwrap_or(0) as u32;
@@ -51,6 +104,55 @@ fn extract_shape(tx: &TxResponse) -> TxShape {
         .unwrap_or(0) as u32;
     let size = tx.size.unwrap_or(0);
     let version = tx.version.unwrap_or(1);
+    let coinbase = tx
+        .vin
+        .as_ref()
+        .and_then(|v| v.first())
+        .is_some_and(|i| i.get("coinbase").is_some());
+    let mut scripts = ScriptCounts::default();
+    let script_hex = |v: &serde_json::Value, field: &str| {
+        v.get(field)
+            .and_then(|s| s.get("hex"))
+            .and_then(|h| h.as_str())
+            .and_then(|h| hex_decode(h).ok())
+    };
+    for script in tx
+        .vout
+        .iter()
+        .flatten()
+        .filter_map(|o| script_hex(o, "scriptPubKey"))
+    {
+        script::count_output(&mut scripts, &script);
+    }
+    if !coinbase {
+        for script in tx
+            .vin
+            .iter()
+            .flatten()
+            .filter_map(|i| script_hex(i, "scriptSig"))
+        {
+            script::count_input(&mut scripts, &script);
+        }
+    }
+    let burns = tx
+        .orchard
+        .as_ref()
+        .and_then(|o| o.burn.as_deref())
+        .unwrap_or_default();
+    let issue_actions = tx
+        .issuance
+        .as_ref()
+        .and_then(|i| i.actions.as_deref())
+        .unwrap_or_default();
+    // Issued and burned assets are identified differently (see rawtx::parse_v6).
+    let asset_types = burns.iter().map(|b| &b.asset).collect::<HashSet<_>>().len()
+        + issue_actions
+            .iter()
+            .map(|a| &a.asset_desc_hash)
+            .collect::<HashSet<_>>()
+            .len();
+    let hex_id = |id: &Option<String>| id.as_deref().and_then(|h| u32::from_str_radix(h, 16).ok());
+    let branch = ConsensusBranch::from_id(hex_id(&tx.consensusbranchid));
     TxShape {
         n_vin,
         n_vout,
@@ -60,6 +162,21 @@ fn extract_shape(tx: &TxResponse) -> TxShape {
         n_orchard_action,
         size_bucket: size_bucket(size),
         version,
+        coinbase,
+        scripts,
+        locktime: LockTimeKind::classify(tx.locktime.unwrap_or(0)),
+        expiry: ExpiryKind::classify(tx.expiryheight.unwrap_or(0), height),
+        orchard_flags: tx
+            .orchard
+            .as_ref()
+            .and_then(|o| o.flags.as_ref())
+            .map(|f| OrchardFlags::from_bits(f.enable_spends as u8 | (f.enable_outputs as u8) << 1))
+            .unwrap_or_default(),
+        version_group: VersionGroup::from_id(hex_id(&tx.versiongroupid)),
+        branch,
+        n_issue_action: issue_actions.len() as u32,
+        n_burn: burns.len() as u32,
+        asset_types: asset_types as u32,
     }
 }
 
@@ -74,21 +191,25 @@ pub async fn run_collect(
     let batch_size = config.collector.batch_size;
     let delay = Duration::from_millis(config.collector.batch_delay_ms);
 
-    let mut all_shapes: Vec<TxShape> = Vec::new();
+    // Range stats are merged block by block, so memory does not grow with the range.
+    let mut range_stats = ShapeStats::default();
     let mut block_count = 0u32;
 
     for start in (low..high).step_by(batch_size as usize) {
         let end = (start + batch_size).min(high);
         for height in start..end {
             match fetch_block_at_height(&client, config, height).await {
-                Ok(Some(shapes)) => {
-                    for s in &shapes {
-                        all_shapes.push(s.clone());
-                    }
-                    if storage::get_block_stats(db, height)?.is_none() {
-                        let stats = ShapeStats::from_shapes(&shapes);
-                        storage::upsert_block_stats(db, height, &stats)?;
-                    }
+                Ok(Some(block)) => {
+                    let shapes = block.shapes;
+                    // Re-collected blocks replace their stored rows, so that the range stats
+                    // always equal the aggregate of the stored blocks.
+                    let stats = ShapeStats::from_shapes(&shapes);
+                    storage::upsert_block_stats(db, height, &stats)?;
+                    let coinbase = ShapeStats::from_coinbase_shapes(&shapes);
+                    storage::upsert_coinbase_stats(db, height, &coinbase)?;
+                    range_stats += stats;
+                    let meta = BlockMeta::from_shapes(height, &shapes, block.size, block.time);
+                    storage::upsert_block_meta(db, &meta)?;
                     block_count += 1;
                 }
                 Ok(None) => {}
@@ -102,7 +223,6 @@ pub async fn run_collect(
         }
     }
 
-    let range_stats = ShapeStats::from_shapes(&all_shapes);
     storage::save_range_stats(db, low, high, &range_stats)?;
     info!(
         low,
@@ -136,7 +256,7 @@ async fn fetch_block_at_height(
     client: &reqwest::Client,
     config: &Config,
     height: u32,
-) -> anyhow::Result<Option<Vec<TxShape>>> {
+) -> anyhow::Result<Option<FetchedBlock>> {
     let by_height = fetch_block_params(client, config, serde_json::json!([height, 2])).await;
     match by_height {
         Ok(Some(s)) => return Ok(Some(s)),
@@ -183,7 +303,7 @@ async fn fetch_block_params(
     client: &reqwest::Client,
     config: &Config,
     params: serde_json::Value,
-) -> anyhow::Result<Option<Vec<TxShape>>> {
+) -> anyhow::Result<Option<FetchedBlock>> {
     let body = serde_json::json!({
         "jsonrpc": "1.0",
         "id": "txshape",
@@ -211,10 +331,38 @@ async fn fetch_block_params(
         return Ok(None);
     }
     let block: BlockResponse = serde_json::from_value(result_val)?;
-    let txs = match block.tx {
-        Some(t) => t,
-        None => return Ok(Some(Vec::new())),
-    };
-    let shapes: Vec<TxShape> = txs.iter().map(extract_shape).collect();
-    Ok(Some(shapes))
+    let shapes: Vec<TxShape> = block
+        .tx
+        .iter()
+        .flatten()
+        .map(|tx| extract_shape(tx, block.height))
+        .collect();
+    Ok(Some(FetchedBlock {
+        size: block.size,
+        time: block.time,
+        shapes,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_v6_block() {
+        let block: BlockResponse =
+            serde_json::from_str(include_str!("../../tests/fixtures/v6_block.json")).unwrap();
+        let shapes: Vec<TxShape> = block
+            .tx
+            .unwrap()
+            .iter()
+            .map(|tx| extract_shape(tx, block.height))
+            .collect();
+        assert!(shapes[0].coinbase);
+        assert!(shapes.iter().all(|s| s.version_group == VersionGroup::Nu7));
+        assert_eq!(shapes[1].n_orchard_action, 3);
+        assert_eq!((shapes[1].n_burn, shapes[1].asset_types), (2, 2));
+        assert_eq!((shapes[2].n_issue_action, shapes[2].asset_types), (2, 2));
+        assert_eq!(shapes[2].expiry, ExpiryKind::Default40);
+    }
 }
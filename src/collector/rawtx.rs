@@ -0,0 +1,471 @@
+//! Raw transaction parsing (v1–v5 consensus encoding, and v6 as drafted in ZIP 230 for NU7)
+//! into a `TxShape`.
+//!
+//! Only component counts, the version, the serialized size, header field categories and
+//! transparent script types are kept; values, commitments and proofs are skipped over without
+//! being interpreted. ZSA asset identifiers are only compared for equality, never stored.
+
+use super::script;
+use crate::model::format::{ConsensusBranch, ExpiryKind, LockTimeKind, OrchardFlags, VersionGroup};
+use crate::model::TxShape;
+use crate::util::{hex_decode, size_bucket};
+use std::collections::HashSet;
+
+/// Sprout JoinSplit description: 304 fixed bytes + proof + two 601-byte ciphertexts.
+const JOINSPLIT_BCTV14: usize = 304 + 296 + 2 * 601;
+const JOINSPLIT_GROTH16: usize = 304 + 192 + 2 * 601;
+/// v4 Sapling spend (cv, anchor, nullifier, rk, proof, spendAuthSig).
+const SAPLING_SPEND_V4: usize = 32 * 4 + 192 + 64;
+/// v4 Sapling output (cv, cmu, epk, enc, out, proof).
+const SAPLING_OUTPUT_V4: usize = 32 * 3 + 580 + 80 + 192;
+/// v5 Sapling spend description without proof and signature (cv, nullifier, rk).
+const SAPLING_SPEND_V5: usize = 32 * 3;
+/// v5 Sapling output description without proof (cv, cmu, epk, enc, out).
+const SAPLING_OUTPUT_V5: usize = 32 * 3 + 580 + 80;
+/// Orchard action (cv, nullifier, rk, cmx, epk, enc, out).
+const ORCHARD_ACTION: usize = 32 * 5 + 580 + 80;
+/// OrchardZSA action: as ORCHARD_ACTION, with the note plaintext extended by the asset base.
+const ORCHARD_ZSA_ACTION: usize = 32 * 5 + 612 + 80;
+/// Asset burn (asset base, amount).
+const ASSET_BURN: usize = 32 + 8;
+/// Issued note (recipient, value, rho, rseed).
+const ISSUE_NOTE: usize = 43 + 8 + 32 + 32;
+/// Issuer key and issuance authorization signature, each with a leading algorithm byte.
+const ISSUER_KEY: usize = 1 + 32;
+const ISSUE_AUTH_SIG: usize = 1 + 64;
+const PROOF_GROTH16: usize = 192;
+const SIGNATURE: usize = 64;
+
+/// Read shapes from user input: a JSON `TxShape` object or array, or one hex-encoded raw
+/// transaction per line (blank lines and lines starting with '#' are skipped).
+pub fn shapes_from_input(text: &str) -> anyhow::Result<Vec<TxShape>> {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('[') {
+        return Ok(serde_json::from_str(trimmed)?);
+    }
+    if trimmed.starts_with('{') {
+        return Ok(vec![serde_json::from_str(trimmed)?]);
+    }
+    text.lines()
+        .enumerate()
+        .map(|(i, l)| (i, l.trim()))
+        .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'))
+        .map(|(i, l)| parse_tx_hex(l).map_err(|e| e.context(format!("line {}", i + 1))))
+        .collect()
+}
+
+/// Parse a hex-encoded raw transaction.
+pub fn parse_tx_hex(hex: &str) -> anyhow::Result<TxShape> {
+    parse_tx(&hex_decode(hex)?)
+}
+
+/// Parse a raw transaction. Fails on truncated input, unsupported versions or trailing bytes.
+/// The mined height is unknown, so any expiry height is classified as `other`.
+pub fn parse_tx(bytes: &[u8]) -> anyhow::Result<TxShape> {
+    parse_tx_at(bytes, None)
+}
+
+/// Like `parse_tx`, for a transaction mined at `block_height`.
+pub fn parse_tx_at(bytes: &[u8], block_height: Option<u32>) -> anyhow::Result<TxShape> {
+    let mut r = Reader { bytes, pos: 0 };
+    let header = r.u32()?;
+    let overwintered = header >> 31 == 1;
+    let version = header & 0x7fff_ffff;
+    let version_group = if overwintered { Some(r.u32()?) } else { None };
+    let mut shape = TxShape {
+        size_bucket: size_bucket(bytes.len() as u32),
+        version,
+        version_group: VersionGroup::from_id(version_group),
+        ..Default::default()
+    };
+    let (lock_time, expiry_height) = match version {
+        1..=4 => parse_v1_v4(&mut r, &mut shape, overwintered)?,
+        5 if overwintered => parse_v5(&mut r, &mut shape)?,
+        6 if overwintered => parse_v6(&mut r, &mut shape)?,
+        _ => anyhow::bail!("unsupported transaction version {}", version),
+    };
+    shape.locktime = LockTimeKind::classify(lock_time);
+    shape.expiry = ExpiryKind::classify(expiry_height, block_height);
+    if r.pos != bytes.len() {
+        anyhow::bail!("{} trailing bytes after transaction", bytes.len() - r.pos);
+    }
+    Ok(shape)
+}
+
+/// Parse the body of a v1–v4 transaction; returns (nLockTime, nExpiryHeight).
+fn parse_v1_v4(
+    r: &mut Reader,
+    shape: &mut TxShape,
+    overwintered: bool,
+) -> anyhow::Result<(u32, u32)> {
+    let version = shape.version;
+    parse_transparent(r, shape)?;
+    let lock_time = r.u32()?;
+    let expiry_height = if overwintered { r.u32()? } else { 0 };
+    if version >= 4 {
+        r.skip(8)?; // valueBalanceSapling
+        shape.n_sapling_spend = r.counted(SAPLING_SPEND_V4)?;
+        shape.n_sapling_output = r.counted(SAPLING_OUTPUT_V4)?;
+    }
+    if version >= 2 {
+        let js_size = if version >= 4 {
+            JOINSPLIT_GROTH16
+        } else {
+            JOINSPLIT_BCTV14
+        };
+        shape.n_joinsplit = r.counted(js_size)?;
+        if shape.n_joinsplit > 0 {
+            r.skip(32 + SIGNATURE)?; // joinSplitPubKey, joinSplitSig
+        }
+    }
+    if version >= 4 && shape.n_sapling_spend + shape.n_sapling_output > 0 {
+        r.skip(SIGNATURE)?; // bindingSigSapling
+    }
+    Ok((lock_time, expiry_height))
+}
+
+/// Parse the body of a v5 transaction; returns (nLockTime, nExpiryHeight).
+fn parse_v5(r: &mut Reader, shape: &mut TxShape) -> anyhow::Result<(u32, u32)> {
+    shape.branch = ConsensusBranch::from_id(Some(r.u32()?));
+    let lock_time = r.u32()?;
+    let expiry_height = r.u32()?;
+    parse_transparent(r, shape)?;
+    parse_sapling_v5(r, shape)?;
+    let actions = r.counted(ORCHARD_ACTION)?;
+    if actions > 0 {
+        shape.orchard_flags = OrchardFlags::from_bits(r.take(1)?[0]);
+        r.skip(8 + 32)?; // valueBalanceOrchard, anchorOrchard
+        let proof_len = r.compact_size()?;
+        r.skip(proof_len)?;
+        r.skip(actions as usize * SIGNATURE + SIGNATURE)?; // spendAuthSigs, bindingSig
+    }
+    shape.n_orchard_action = actions;
+    Ok((lock_time, expiry_height))
+}
+
+/// Parse the body of a v6 transaction; returns (nLockTime, nExpiryHeight). The OrchardZSA
+/// bundle is split into action groups, followed by the asset burns; the issuance bundle
+/// comes last.
+fn parse_v6(r: &mut Reader, shape: &mut TxShape) -> anyhow::Result<(u32, u32)> {
+    shape.branch = ConsensusBranch::from_id(Some(r.u32()?));
+    let lock_time = r.u32()?;
+    let expiry_height = r.u32()?;
+    r.skip(8)?; // zip233Amount
+    parse_transparent(r, shape)?;
+    parse_sapling_v5(r, shape)?;
+    let groups = r.compact_size()?;
+    let mut actions = 0u32;
+    for _ in 0..groups {
+        let n = r.counted(ORCHARD_ZSA_ACTION)?;
+        shape.orchard_flags = OrchardFlags::from_bits(r.take(1)?[0]);
+        r.skip(32)?; // anchorOrchard
+        let proof_len = r.compact_size()?;
+        r.skip(proof_len)?;
+        r.skip(4)?; // nAGExpiryHeight
+        r.skip(n as usize * SIGNATURE)?; // spendAuthSigs
+        actions = actions
+            .checked_add(n)
+            .ok_or_else(|| anyhow::anyhow!("Orchard action count out of range"))?;
+    }
+    let mut burned = HashSet::new();
+    if groups > 0 {
+        r.skip(8)?; // valueBalanceOrchard
+        let n_burn = r.compact_size()?;
+        for _ in 0..n_burn {
+            burned.insert(r.take(ASSET_BURN)?[..32].to_vec());
+        }
+        shape.n_burn = count(n_burn)?;
+        r.skip(SIGNATURE)?; // bindingSigOrchard
+    }
+    let n_issue = r.compact_size()?;
+    let mut issued = HashSet::new();
+    for _ in 0..n_issue {
+        issued.insert(r.take(32)?.to_vec()); // assetDescHash
+        r.counted(ISSUE_NOTE)?;
+        r.skip(1)?; // flagsIssuance
+    }
+    if n_issue > 0 {
+        r.skip(ISSUER_KEY + ISSUE_AUTH_SIG)?;
+    }
+    shape.n_orchard_action = actions;
+    shape.n_issue_action = count(n_issue)?;
+    // Issued assets are identified by description hash and burned ones by asset base, so an
+    // asset both issued and burned in one transaction counts twice.
+    shape.asset_types = count(issued.len() + burned.len())?;
+    Ok((lock_time, expiry_height))
+}
+
+/// Sapling bundle in the v5 layout (shared by v6).
+fn parse_sapling_v5(r: &mut Reader, shape: &mut TxShape) -> anyhow::Result<()> {
+    let spends = r.counted(SAPLING_SPEND_V5)?;
+    let outputs = r.counted(SAPLING_OUTPUT_V5)?;
+    if spends + outputs > 0 {
+        r.skip(8)?; // valueBalanceSapling
+    }
+    if spends > 0 {
+        r.skip(32)?; // anchorSapling
+    }
+    r.skip(spends as usize * (PROOF_GROTH16 + SIGNATURE) + outputs as usize * PROOF_GROTH16)?;
+    if spends + outputs > 0 {
+        r.skip(SIGNATURE)?; // bindingSigSapling
+    }
+    shape.n_sapling_spend = spends;
+    shape.n_sapling_output = outputs;
+    Ok(())
+}
+
+fn parse_transparent(r: &mut Reader, shape: &mut TxShape) -> anyhow::Result<()> {
+    let n_vin = r.compact_size()?;
+    for _ in 0..n_vin {
+        let prevout = r.take(36)?;
+        shape.coinbase = n_vin == 1 && prevout[..32] == [0; 32] && prevout[32..] == [0xff; 4];
+        let script_len = r.compact_size()?;
+        let script_sig = r.take(script_len)?;
+        if !shape.coinbase {
+            script::count_input(&mut shape.scripts, script_sig);
+        }
+        r.skip(4)?; // nSequence
+    }
+    let n_vout = r.compact_size()?;
+    for _ in 0..n_vout {
+        r.skip(8)?; // value
+        let script_len = r.compact_size()?;
+        let script = r.take(script_len)?;
+        script::count_output(&mut shape.scripts, script);
+    }
+    shape.n_vin = count(n_vin)?;
+    shape.n_vout = count(n_vout)?;
+    Ok(())
+}
+
+fn count(n: usize) -> anyhow::Result<u32> {
+    u32::try_from(n).map_err(|_| anyhow::anyhow!("count {} out of range", n))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&e| e <= self.bytes.len())
+            .ok_or_else(|| anyhow::anyhow!("transaction truncated at byte {}", self.pos))?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn skip(&mut self, n: usize) -> anyhow::Result<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn compact_size(&mut self) -> anyhow::Result<usize> {
+        let first = self.take(1)?[0];
+        let n = match first {
+            0xfd => {
+                let b = self.take(2)?;
+                u16::from_le_bytes([b[0], b[1]]) as u64
+            }
+            0xfe => self.u32()? as u64,
+            0xff => {
+                let b = self.take(8)?;
+                u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])
+            }
+            n => n as u64,
+        };
+        // No count or length can exceed the input length; reject before looping over it.
+        if n > self.bytes.len() as u64 {
+            anyhow::bail!("compact size {} exceeds transaction length", n);
+        }
+        Ok(n as usize)
+    }
+
+    /// Read a count followed by that many fixed-size elements; returns the count.
+    fn counted(&mut self, element_size: usize) -> anyhow::Result<u32> {
+        let n = self.compact_size()?;
+        self.skip(n.saturating_mul(element_size))?;
+        count(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal serializer for test transactions; all non-count fields are zero bytes.
+    #[derive(Default)]
+    struct Builder(Vec<u8>);
+
+    impl Builder {
+        fn u32(mut self, v: u32) -> Self {
+            self.0.extend_from_slice(&v.to_le_bytes());
+            self
+        }
+        fn zeros(mut self, n: usize) -> Self {
+            self.0.extend(std::iter::repeat(0).take(n));
+            self
+        }
+        fn count(mut self, n: u8) -> Self {
+            self.0.push(n);
+            self
+        }
+        fn transparent(self, n_vin: u8, n_vout: u8) -> Self {
+            let mut b = self.count(n_vin);
+            for _ in 0..n_vin {
+                b = b.zeros(36).count(107).zeros(107).zeros(4);
+            }
+            b = b.count(n_vout);
+            for _ in 0..n_vout {
+                b = b.zeros(8).count(25).zeros(25);
+            }
+            b
+        }
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        let tx = Builder::default().u32(1).transparent(1, 2).zeros(4).0;
+        let shape = parse_tx(&tx).unwrap();
+        assert_eq!((shape.n_vin, shape.n_vout, shape.version), (1, 2, 1));
+        assert!(!shape.has_shielded());
+        assert!(!shape.coinbase);
+        assert_eq!(shape.size_bucket, size_bucket(tx.len() as u32));
+
+        let coinbase = Builder::default()
+            .u32(1)
+            .count(1)
+            .zeros(32)
+            .u32(u32::MAX)
+            .count(4)
+            .zeros(4 + 4)
+            .count(1)
+            .zeros(8)
+            .count(25)
+            .zeros(25 + 4)
+            .0;
+        assert!(parse_tx(&coinbase).unwrap().coinbase);
+    }
+
+    #[test]
+    fn test_parse_v4_sapling() {
+        let tx = Builder::default()
+            .u32(4 | 1 << 31)
+            .u32(0x892f_2085)
+            .transparent(0, 1)
+            .zeros(4 + 4 + 8)
+            .count(1)
+            .zeros(SAPLING_SPEND_V4)
+            .count(2)
+            .zeros(2 * SAPLING_OUTPUT_V4)
+            .count(0)
+            .zeros(SIGNATURE)
+            .0;
+        let shape = parse_tx(&tx).unwrap();
+        assert_eq!(shape.version, 4);
+        assert_eq!(shape.version_group, VersionGroup::Sapling);
+        assert_eq!(shape.branch, ConsensusBranch::Implicit);
+        assert_eq!((shape.n_vin, shape.n_vout), (0, 1));
+        assert_eq!((shape.n_sapling_spend, shape.n_sapling_output), (1, 2));
+        assert_eq!(shape.n_joinsplit, 0);
+        assert!(parse_tx(&tx[..tx.len() - 1]).is_err());
+        let mut trailing = tx.clone();
+        trailing.push(0);
+        assert!(parse_tx(&trailing).is_err());
+    }
+
+    #[test]
+    fn test_parse_v5_orchard() {
+        let tx = Builder::default()
+            .u32(5 | 1 << 31)
+            .u32(0x26a7_270a)
+            .u32(0xc2d6_d0b4)
+            .u32(1_000)
+            .u32(1_038)
+            .transparent(1, 1)
+            .count(0)
+            .count(1)
+            .zeros(SAPLING_OUTPUT_V5)
+            .zeros(8 + PROOF_GROTH16 + SIGNATURE)
+            .count(2)
+            .zeros(2 * ORCHARD_ACTION)
+            .count(2)
+            .zeros(8 + 32)
+            .count(100)
+            .zeros(100)
+            .zeros(3 * SIGNATURE)
+            .0;
+        let hex: String = tx.iter().map(|b| format!("{:02x}", b)).collect();
+        let shape = parse_tx_hex(&hex).unwrap();
+        assert_eq!(shape.version, 5);
+        assert_eq!((shape.n_vin, shape.n_vout), (1, 1));
+        assert_eq!((shape.n_sapling_spend, shape.n_sapling_output), (0, 1));
+        assert_eq!(shape.n_orchard_action, 2);
+        assert_eq!(shape.orchard_flags, OrchardFlags::OutputsOnly);
+        assert_eq!(
+            (shape.version_group, shape.branch),
+            (VersionGroup::Nu5, ConsensusBranch::Nu5)
+        );
+        assert_eq!(shape.locktime, LockTimeKind::Height);
+        assert_eq!(shape.expiry, ExpiryKind::Other);
+        let mined = parse_tx_at(&tx, Some(1_001)).unwrap();
+        assert_eq!(mined.expiry, ExpiryKind::Default40);
+        assert_eq!(mined.tuple_key(), shape.tuple_key());
+        assert!(parse_tx(&[6, 0, 0, 0]).is_err());
+
+        let input = format!("# wallet fixtures\n{}\n\n{}\n", hex, hex);
+        assert_eq!(
+            shapes_from_input(&input).unwrap(),
+            vec![shape.clone(), shape]
+        );
+        assert!(shapes_from_input("00").is_err());
+    }
+
+    #[test]
+    fn test_parse_v6_zsa() {
+        let tx = Builder::default()
+            .u32(6 | 1 << 31)
+            .u32(0x7777_7777)
+            .zeros(4 + 4 + 4 + 8)
+            .transparent(0, 0)
+            .count(0)
+            .count(0)
+            // One action group with two actions, all flags set.
+            .count(1)
+            .count(2)
+            .zeros(2 * ORCHARD_ZSA_ACTION)
+            .count(3)
+            .zeros(32)
+            .count(10)
+            .zeros(10 + 4 + 2 * SIGNATURE)
+            // Two burns of distinct assets.
+            .zeros(8)
+            .count(2)
+            .zeros(ASSET_BURN)
+            .count(1)
+            .zeros(ASSET_BURN - 1)
+            .zeros(SIGNATURE)
+            // One issuance action with one note.
+            .count(1)
+            .zeros(32)
+            .count(1)
+            .zeros(ISSUE_NOTE + 1 + ISSUER_KEY + ISSUE_AUTH_SIG)
+            .0;
+        let shape = parse_tx(&tx).unwrap();
+        assert_eq!(shape.version, 6);
+        assert_eq!(shape.version_group, VersionGroup::Nu7);
+        assert_eq!(shape.n_orchard_action, 2);
+        assert_eq!(shape.orchard_flags, OrchardFlags::Both);
+        assert_eq!((shape.n_issue_action, shape.n_burn), (1, 2));
+        assert_eq!(shape.asset_types, 3);
+        assert!(parse_tx(&tx[..tx.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! Transparent script-type classification. Only the template a script matches is kept; no
+//! hashes, keys or signatures leave this module.
+
+use crate::model::ScriptCounts;
+
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_RETURN: u8 = 0x6a;
+const OP_DUP: u8 = 0x76;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_HASH160: u8 = 0xa9;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_CHECKMULTISIG: u8 = 0xae;
+
+/// Count one output by its scriptPubKey.
+pub fn count_output(counts: &mut ScriptCounts, script: &[u8]) {
+    match script {
+        [OP_DUP, OP_HASH160, 20, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG] if hash.len() == 20 => {
+            counts.out_p2pkh += 1
+        }
+        [OP_HASH160, 20, hash @ .., OP_EQUAL] if hash.len() == 20 => counts.out_p2sh += 1,
+        [OP_RETURN, ..] => counts.out_op_return += 1,
+        _ => counts.out_nonstandard += 1,
+    }
+}
+
+/// Count one (non-coinbase) input by its scriptSig.
+pub fn count_input(counts: &mut ScriptCounts, script_sig: &[u8]) {
+    let Some(pushes) = pushes(script_sig) else {
+        counts.in_other += 1;
+        return;
+    };
+    match pushes.as_slice() {
+        [sig, key] if !sig.is_empty() && matches!(key.len(), 33 | 65) => counts.in_p2pkh += 1,
+        [.., redeem] if pushes.len() >= 2 && is_multisig(redeem) => counts.in_p2sh_multisig += 1,
+        [_, .., redeem] if !redeem.is_empty() => counts.in_p2sh += 1,
+        _ => counts.in_other += 1,
+    }
+}
+
+/// m-of-n CHECKMULTISIG script: OP_m <n keys> OP_n OP_CHECKMULTISIG.
+fn is_multisig(script: &[u8]) -> bool {
+    let (Some(&first), [.., last_n, OP_CHECKMULTISIG]) = (script.first(), script) else {
+        return false;
+    };
+    if !(OP_1..=OP_16).contains(&first) || !(OP_1..=OP_16).contains(last_n) {
+        return false;
+    }
+    let n = (last_n - OP_1 + 1) as usize;
+    match pushes(&script[1..script.len() - 2]) {
+        Some(keys) => {
+            keys.len() == n && first <= *last_n && keys.iter().all(|k| matches!(k.len(), 33 | 65))
+        }
+        None => false,
+    }
+}
+
+/// Data pushes of a push-only script; `None` if it contains other opcodes or is truncated.
+fn pushes(script: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < script.len() {
+        let op = script[pos];
+        pos += 1;
+        let len = match op {
+            OP_0 => 0,
+            1..=0x4b => op as usize,
+            OP_PUSHDATA1 => {
+                let n = *script.get(pos)? as usize;
+                pos += 1;
+                n
+            }
+            OP_PUSHDATA2 => {
+                let n = u16::from_le_bytes(script.get(pos..pos + 2)?.try_into().ok()?) as usize;
+                pos += 2;
+                n
+            }
+            OP_PUSHDATA4 => {
+                let n = u32::from_le_bytes(script.get(pos..pos + 4)?.try_into().ok()?) as usize;
+                pos += 4;
+                n
+            }
+            _ => return None,
+        };
+        out.push(script.get(pos..pos.checked_add(len)?)?);
+        pos += len;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![data.len() as u8];
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[test]
+    fn test_classify_outputs() {
+        let mut c = ScriptCounts::default();
+        let mut p2pkh = vec![OP_DUP, OP_HASH160, 20];
+        p2pkh.extend([0; 20]);
+        p2pkh.extend([OP_EQUALVERIFY, OP_CHECKSIG]);
+        count_output(&mut c, &p2pkh);
+        let mut p2sh = vec![OP_HASH160, 20];
+        p2sh.extend([0; 20]);
+        p2sh.push(OP_EQUAL);
+        count_output(&mut c, &p2sh);
+        count_output(&mut c, &[OP_RETURN, 4, 1, 2, 3, 4]);
+        count_output(&mut c, &p2pkh[..24]);
+        assert_eq!(
+            c.outputs(),
+            [
+                ("p2pkh", 1),
+                ("p2sh", 1),
+                ("op_return", 1),
+                ("nonstandard", 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_classify_inputs() {
+        let mut c = ScriptCounts::default();
+        let sig = [0x30; 71];
+        let key = [0x02; 33];
+        count_input(&mut c, &[push(&sig), push(&key)].concat());
+
+        let mut redeem = vec![OP_1 + 1];
+        for _ in 0..3 {
+            redeem.extend(push(&key));
+        }
+        redeem.extend([OP_1 + 2, OP_CHECKMULTISIG]);
+        let multisig = [
+            vec![OP_0],
+            push(&sig),
+            push(&sig),
+            vec![OP_PUSHDATA1, redeem.len() as u8],
+            redeem.clone(),
+        ]
+        .concat();
+        count_input(&mut c, &multisig);
+
+        count_input(&mut c, &[push(&[1]), push(&[OP_1, OP_CHECKSIG])].concat());
+        count_input(&mut c, &push(&sig));
+        count_input(&mut c, &[OP_DUP]);
+        assert_eq!(
+            c.inputs(),
+            [
+                ("p2pkh", 1),
+                ("p2sh_multisig", 1),
+                ("p2sh", 1),
+                ("other", 2)
+            ]
+        );
+        assert!(is_multisig(&redeem));
+        assert!(!is_multisig(&redeem[..redeem.len() - 1]));
+    }
+}
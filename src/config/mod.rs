@@ -1,5 +1,7 @@
 //! Configuration load and validation.
 
+use crate::model::format::Network;
+use crate::model::padding::PaddingPolicy;
 use anyhow::{Context, Result};
 use serde::Deserialize;
 use std::path::Path;
@@ -10,6 +12,13 @@ pub struct Config {
     pub node: NodeConfig,
     pub storage: StorageConfig,
     pub collector: CollectorConfig,
+    #[serde(default)]
+    pub flood: FloodConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    /// Padding policies compared by `simulate-padding`.
+    #[serde(default)]
+    pub padding: Vec<PaddingPolicy>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -43,6 +52,10 @@ pub struct CollectorConfig {
     /// Delay in milliseconds between batch requests.
     #[serde(default = "default_batch_delay_ms")]
     pub batch_delay_ms: u64,
+    /// Chain the node follows ("main" or "test"); reports use its upgrade heights to find
+    /// version groups mined after a newer one activated.
+    #[serde(default)]
+    pub network: Network,
 }
 
 fn default_batch_size() -> u32 {
@@ -53,6 +66,148 @@ fn default_batch_delay_ms() -> u64 {
     500
 }
 
+/// Shape flood detection thresholds (see `detect-floods`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct FloodConfig {
+    /// Number of consecutive blocks per detection window.
+    #[serde(default = "default_flood_window_blocks")]
+    pub window_blocks: u32,
+    /// Minimum share (0..1) of window transactions with the same shape tuple.
+    #[serde(default = "default_flood_min_share")]
+    pub min_share: f64,
+    /// Minimum number of transactions with that shape in the window.
+    #[serde(default = "default_flood_min_count")]
+    pub min_count: u64,
+}
+
+impl Default for FloodConfig {
+    fn default() -> Self {
+        FloodConfig {
+            window_blocks: default_flood_window_blocks(),
+            min_share: default_flood_min_share(),
+            min_count: default_flood_min_count(),
+        }
+    }
+}
+
+fn default_flood_window_blocks() -> u32 {
+    12
+}
+
+fn default_flood_min_share() -> f64 {
+    0.5
+}
+
+fn default_flood_min_count() -> u64 {
+    200
+}
+
+/// Anomaly alert thresholds (see `alerts`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertsConfig {
+    /// Blocks per window; the latest window is scored against the windows before it.
+    #[serde(default = "default_alerts_window_blocks")]
+    pub window_blocks: u32,
+    /// Number of trailing windows in the baseline.
+    #[serde(default = "default_alerts_baseline_windows")]
+    pub baseline_windows: u32,
+    /// Minimum |z-score| of a metric or bucket share that raises an alert.
+    #[serde(default = "default_alerts_z_threshold")]
+    pub z_threshold: f64,
+    /// Minimum Jensen-Shannon divergence (bits) from the pooled baseline that raises an alert.
+    #[serde(default = "default_alerts_divergence_threshold")]
+    pub divergence_threshold: f64,
+    /// Buckets below this share in both the baseline and the latest window are not scored.
+    #[serde(default = "default_alerts_bucket_min_share")]
+    pub bucket_min_share: f64,
+    /// Fixed bands on latest-window metrics, checked in addition to the baseline.
+    #[serde(default)]
+    pub rules: Vec<AlertRule>,
+    /// Where alerts are delivered; none means print only.
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+    /// Seconds before the same alert is delivered again.
+    #[serde(default = "default_alerts_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+/// Threshold rule: alert when `metric` of the latest window leaves [min, max].
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    /// Window metric: shielded_share, transparent_share, size_entropy, grace_action_share or
+    /// n_txs.
+    pub metric: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Alert sink.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SinkConfig {
+    /// POST a JSON body {"alerts": [...]} to `url`.
+    Webhook {
+        url: String,
+        #[serde(default = "default_timeout_secs")]
+        timeout_secs: u64,
+    },
+    /// Append one JSON object per alert to `path`.
+    File { path: std::path::PathBuf },
+    /// Print one JSON object per alert to stdout.
+    Stdout,
+}
+
+impl SinkConfig {
+    /// Stable identity of the sink, used to track delivery per sink.
+    pub fn id(&self) -> String {
+        match self {
+            SinkConfig::Webhook { url, .. } => format!("webhook:{}", url),
+            SinkConfig::File { path } => format!("file:{}", path.display()),
+            SinkConfig::Stdout => "stdout".to_string(),
+        }
+    }
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        AlertsConfig {
+            window_blocks: default_alerts_window_blocks(),
+            baseline_windows: default_alerts_baseline_windows(),
+            z_threshold: default_alerts_z_threshold(),
+            divergence_threshold: default_alerts_divergence_threshold(),
+            bucket_min_share: default_alerts_bucket_min_share(),
+            rules: Vec::new(),
+            sinks: Vec::new(),
+            cooldown_secs: default_alerts_cooldown_secs(),
+        }
+    }
+}
+
+fn default_alerts_window_blocks() -> u32 {
+    144
+}
+
+fn default_alerts_baseline_windows() -> u32 {
+    7
+}
+
+fn default_alerts_z_threshold() -> f64 {
+    3.0
+}
+
+fn default_alerts_divergence_threshold() -> f64 {
+    0.05
+}
+
+fn default_alerts_bucket_min_share() -> f64 {
+    0.01
+}
+
+fn default_alerts_cooldown_secs() -> u64 {
+    3600
+}
+
 impl Config {
     /// Load and validate config from a TOML file.
     pub fn load(path: &Path) -> Result<Config> {
@@ -70,6 +225,61 @@ impl Config {
         if self.collector.batch_size == 0 {
             anyhow::bail!("collector.batch_size must be positive");
         }
+        if self.flood.window_blocks == 0 {
+            anyhow::bail!("flood.window_blocks must be positive");
+        }
+        if !(self.flood.min_share > 0.0 && self.flood.min_share <= 1.0) {
+            anyhow::bail!("flood.min_share must be in (0, 1]");
+        }
+        if self.alerts.window_blocks == 0 {
+            anyhow::bail!("alerts.window_blocks must be positive");
+        }
+        if self.alerts.baseline_windows < 2 {
+            anyhow::bail!("alerts.baseline_windows must be at least 2");
+        }
+        if self.alerts.z_threshold <= 0.0 || self.alerts.divergence_threshold <= 0.0 {
+            anyhow::bail!("alerts thresholds must be positive");
+        }
+        let metrics = crate::model::baseline::window_metric_names();
+        for rule in &self.alerts.rules {
+            if !metrics.contains(&rule.metric.as_str()) {
+                anyhow::bail!(
+                    "alerts rule {:?}: unknown metric {:?} (expected one of {})",
+                    rule.name,
+                    rule.metric,
+                    metrics.join(", ")
+                );
+            }
+            match (rule.min, rule.max) {
+                (None, None) => anyhow::bail!("alerts rule {:?} needs min or max", rule.name),
+                (Some(lo), Some(hi)) if lo > hi => {
+                    anyhow::bail!("alerts rule {:?}: min must not exceed max", rule.name)
+                }
+                _ => {}
+            }
+        }
+        let mut names = std::collections::HashSet::new();
+        for policy in &self.padding {
+            if policy.name.is_empty() || policy.name == "none" {
+                anyhow::bail!("padding policy name must be non-empty and not \"none\"");
+            }
+            if !names.insert(policy.name.as_str()) {
+                anyhow::bail!("duplicate padding policy {:?}", policy.name);
+            }
+            if policy.pad_size_bucket.is_some_and(|b| b > 5) {
+                anyhow::bail!(
+                    "padding policy {:?}: pad_size_bucket must be 0..=5",
+                    policy.name
+                );
+            }
+        }
+        for sink in &self.alerts.sinks {
+            if let SinkConfig::Webhook { url, .. } = sink {
+                if url.is_empty() {
+                    anyhow::bail!("alerts webhook url must be non-empty");
+                }
+            }
+        }
         Ok(())
     }
 }
@@ -90,5 +300,57 @@ db_path = "txshape.db"
 [collector]
 batch_size = 10
 batch_delay_ms = 500
+# "main" or "test": upgrade activation heights used to find lagging version groups.
+network = "main"
+
+# Shape flood detection (detect-floods): a window is flagged when one shape tuple
+# reaches min_share of its transactions and at least min_count transactions.
+[flood]
+window_blocks = 12
+min_share = 0.5
+min_count = 200
+
+# Anomaly alerts (alerts): the latest window is scored against the trailing
+# baseline_windows windows; the command exits with status 1 when any alert fires.
+[alerts]
+window_blocks = 144
+baseline_windows = 7
+z_threshold = 3.0
+divergence_threshold = 0.05
+bucket_min_share = 0.01
+cooldown_secs = 3600
+
+# Fixed bands on the latest window (metrics: shielded_share, transparent_share,
+# size_entropy, grace_action_share, n_txs).
+# [[alerts.rules]]
+# name = "shielded-share-band"
+# metric = "shielded_share"
+# min = 0.05
+# max = 0.9
+
+# Sinks (kind = "webhook" | "file" | "stdout"); without sinks alerts are only printed.
+# [[alerts.sinks]]
+# kind = "webhook"
+# url = "http://127.0.0.1:9000/alerts"
+# [[alerts.sinks]]
+# kind = "file"
+# path = "alerts.ndjson"
+
+# Padding what-if policies (simulate-padding). Each policy rewrites stored shape tuples:
+# min_outputs pads present shielded bundles, round_pow2 rounds shielded counts up to a
+# power of two, pad_size_bucket raises every tx to at least that size bucket (0..=5).
+[[padding]]
+name = "min-2-outputs"
+min_outputs = 2
+
+[[padding]]
+name = "pow2-actions"
+round_pow2 = true
+
+[[padding]]
+name = "pow2-bucket-3"
+min_outputs = 2
+round_pow2 = true
+pad_size_bucket = 3
 "#
 }
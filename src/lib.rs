@@ -1,8 +1,10 @@
 //! zcash-txshape: Transaction Shape Analyzer for Zcash (Read-Only, Aggregate, Non-Attributing).
 
+pub mod alert;
 pub mod collector;
 pub mod config;
 pub mod model;
 pub mod report;
 pub mod storage;
+pub mod synth;
 pub mod util;
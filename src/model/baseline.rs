@@ -0,0 +1,413 @@
+//! Trailing baseline of per-window shape statistics and anomaly scoring of the latest window.
+//!
+//! The baseline keeps the mean and standard deviation of headline metrics and of every bucket
+//! share across the previous N windows, plus the pooled histograms of those windows. The latest
+//! window is scored with z-scores against the former and Jensen–Shannon divergence against the
+//! latter.
+
+use super::divergence::{align, Divergence};
+use super::{Histogram, ShapeStats};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Lower bound on baseline standard deviations, so a perfectly flat baseline does not turn
+/// every tiny change into an infinite z-score.
+pub const MIN_STD: f64 = 1e-3;
+
+/// Fewest baseline values a metric or bucket share needs to be scored; with fewer there is no
+/// standard deviation to score against.
+pub const MIN_BASELINE_VALUES: usize = 2;
+
+/// Named scalar metric of a window; `None` when the window has no data for it.
+type WindowMetric = (&'static str, fn(&ShapeStats) -> Option<f64>);
+
+/// Headline metrics tracked by the baseline.
+const METRICS: [WindowMetric; 4] = [
+    ("shielded_share", |s| share(s.with_shielded, s.n_txs)),
+    ("transparent_share", |s| share(s.with_transparent, s.n_txs)),
+    ("size_entropy", |s| Some(s.size_entropy)),
+    ("grace_action_share", ShapeStats::grace_action_share),
+];
+
+/// Value of a named window metric (a baseline metric or "n_txs"); `None` for unknown names
+/// and for metrics the window has no data for.
+pub fn window_metric(stats: &ShapeStats, name: &str) -> Option<f64> {
+    if name == "n_txs" {
+        return Some(stats.n_txs as f64);
+    }
+    METRICS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .and_then(|(_, f)| f(stats))
+}
+
+/// Names accepted by [`window_metric`].
+pub fn window_metric_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = METRICS.iter().map(|(n, _)| *n).collect();
+    names.push("n_txs");
+    names
+}
+
+/// Mean and standard deviation of one value across baseline windows.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricBaseline {
+    pub name: String,
+    /// Baseline windows with a value (windows stored before the metric existed have none).
+    pub n: usize,
+    pub mean: f64,
+    pub std: f64,
+}
+
+impl MetricBaseline {
+    /// Whether there are enough values to score against.
+    pub fn sufficient(&self) -> bool {
+        self.n >= MIN_BASELINE_VALUES
+    }
+}
+
+/// Per-bucket share baselines and pooled counts of one histogram.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBaseline {
+    pub histogram: String,
+    pub ordered: bool,
+    /// Baseline windows with data for this histogram.
+    pub windows: usize,
+    /// Share baseline per bucket label (name = bucket label).
+    pub shares: Vec<MetricBaseline>,
+    /// Counts summed over all baseline windows.
+    pub pooled: Vec<(String, u64)>,
+}
+
+/// Baseline over a run of windows preceding the scored one.
+#[derive(Debug, Clone, Serialize)]
+pub struct Baseline {
+    pub windows: usize,
+    pub metrics: Vec<MetricBaseline>,
+    pub histograms: Vec<HistogramBaseline>,
+}
+
+/// Alert thresholds used by [`Baseline::score`].
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// Minimum |z| of a metric or bucket share.
+    pub z: f64,
+    /// Minimum Jensen–Shannon divergence (bits) of a histogram from the pooled baseline.
+    pub divergence: f64,
+    /// Buckets below this share in both the baseline mean and the latest window are ignored.
+    pub bucket_min_share: f64,
+}
+
+/// One threshold breach of the latest window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    /// "metric", "bucket_share", "divergence" or "rule".
+    pub kind: String,
+    /// Metric name, "histogram:bucket", histogram name, or rule name.
+    pub subject: String,
+    pub value: f64,
+    /// Baseline mean (0 for divergence and rules).
+    pub baseline: f64,
+    /// z-score, the divergence itself, or the distance past a rule bound.
+    pub score: f64,
+    pub threshold: f64,
+}
+
+impl Baseline {
+    /// Build a baseline from non-empty windows (windows without transactions are skipped, as
+    /// are windows without data for a metric or histogram, e.g. stored before it existed).
+    pub fn from_windows(windows: &[ShapeStats]) -> Baseline {
+        let windows: Vec<&ShapeStats> = windows.iter().filter(|s| s.n_txs > 0).collect();
+        let metrics = METRICS
+            .iter()
+            .map(|(name, f)| {
+                let values: Vec<f64> = windows.iter().filter_map(|s| f(s)).collect();
+                mean_std(name, &values)
+            })
+            .collect();
+        let per_window: Vec<Vec<Histogram>> = windows.iter().map(|s| s.histograms()).collect();
+        let n_hist = per_window.first().map(|h| h.len()).unwrap_or(0);
+        let histograms = (0..n_hist)
+            .map(|k| {
+                let hists: Vec<&Histogram> = per_window.iter().map(|h| &h[k]).collect();
+                let pooled = pool(&hists);
+                let indexed: Vec<BucketShares> = hists
+                    .iter()
+                    .filter(|h| h.total() > 0)
+                    .map(|h| bucket_shares(h))
+                    .collect();
+                let shares = pooled
+                    .iter()
+                    .map(|(label, _)| {
+                        let values: Vec<f64> = indexed.iter().map(|s| share_of(s, label)).collect();
+                        mean_std(label, &values)
+                    })
+                    .collect();
+                HistogramBaseline {
+                    histogram: hists[0].name.to_string(),
+                    ordered: hists[0].ordered,
+                    windows: indexed.len(),
+                    shares,
+                    pooled,
+                }
+            })
+            .collect();
+        Baseline {
+            windows: windows.len(),
+            metrics,
+            histograms,
+        }
+    }
+
+    /// Metrics and histograms with fewer than [`MIN_BASELINE_VALUES`] baseline values, which
+    /// [`Baseline::score`] skips.
+    pub fn insufficient(&self) -> Vec<&str> {
+        let metrics = self
+            .metrics
+            .iter()
+            .filter(|m| !m.sufficient())
+            .map(|m| m.name.as_str());
+        let histograms = self
+            .histograms
+            .iter()
+            .filter(|h| h.windows < MIN_BASELINE_VALUES)
+            .map(|h| h.histogram.as_str());
+        metrics.chain(histograms).collect()
+    }
+
+    /// Score `latest` against the baseline. Metric breaches come first, then per histogram its
+    /// divergence and bucket-share breaches (largest |z| first). Metrics and histograms
+    /// `latest` has no data for, or with too few baseline values (see
+    /// [`Baseline::insufficient`]), are not scored.
+    pub fn score(&self, latest: &ShapeStats, t: Thresholds) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for ((name, f), b) in METRICS.iter().zip(&self.metrics) {
+            let Some(value) = f(latest).filter(|_| b.sufficient()) else {
+                continue;
+            };
+            let z = (value - b.mean) / b.std.max(MIN_STD);
+            if z.abs() >= t.z {
+                alerts.push(alert("metric", name, value, b.mean, z, t.z));
+            }
+        }
+        for (hb, h) in self.histograms.iter().zip(latest.histograms()) {
+            if h.total() == 0 || hb.windows < MIN_BASELINE_VALUES {
+                continue;
+            }
+            let base = Histogram {
+                name: h.name,
+                ordered: hb.ordered,
+                buckets: hb.pooled.clone(),
+            };
+            let aligned = align(&base, &h);
+            let js = Divergence::between(&aligned.a, &aligned.b, hb.ordered).jensen_shannon;
+            if js >= t.divergence {
+                alerts.push(alert("divergence", h.name, js, 0.0, js, t.divergence));
+            }
+            let baselines: HashMap<&str, &MetricBaseline> =
+                hb.shares.iter().map(|m| (m.name.as_str(), m)).collect();
+            let mut buckets: Vec<Alert> = Vec::new();
+            let latest_shares = bucket_shares(&h);
+            for label in &aligned.labels {
+                let value = share_of(&latest_shares, label);
+                let (mean, std) = baselines
+                    .get(label.as_str())
+                    .map(|m| (m.mean, m.std))
+                    .unwrap_or((0.0, 0.0));
+                if value.max(mean) < t.bucket_min_share {
+                    continue;
+                }
+                let z = (value - mean) / std.max(MIN_STD);
+                if z.abs() >= t.z {
+                    let subject = format!("{}:{}", h.name, label);
+                    buckets.push(alert("bucket_share", &subject, value, mean, z, t.z));
+                }
+            }
+            buckets.sort_by(|a, b| b.score.abs().total_cmp(&a.score.abs()));
+            alerts.extend(buckets);
+        }
+        alerts
+    }
+}
+
+fn alert(
+    kind: &str,
+    subject: &str,
+    value: f64,
+    baseline: f64,
+    score: f64,
+    threshold: f64,
+) -> Alert {
+    Alert {
+        kind: kind.to_string(),
+        subject: subject.to_string(),
+        value,
+        baseline,
+        score,
+        threshold,
+    }
+}
+
+/// Sample mean and standard deviation (n − 1).
+fn mean_std(name: &str, values: &[f64]) -> MetricBaseline {
+    let n = values.len() as f64;
+    let mean = if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / n
+    };
+    let std = if values.len() < 2 {
+        0.0
+    } else {
+        (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+    };
+    MetricBaseline {
+        name: name.to_string(),
+        n: values.len(),
+        mean,
+        std,
+    }
+}
+
+/// Sum histograms by label, keeping the first histogram's order and appending new labels.
+fn pool(hists: &[&Histogram]) -> Vec<(String, u64)> {
+    let mut out: Vec<(String, u64)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for h in hists {
+        for (label, count) in &h.buckets {
+            match index.get(label) {
+                Some(&i) => out[i].1 += count,
+                None => {
+                    index.insert(label.clone(), out.len());
+                    out.push((label.clone(), *count));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Share of each bucket of one histogram, by label.
+type BucketShares<'a> = HashMap<&'a str, f64>;
+
+fn bucket_shares(h: &Histogram) -> BucketShares<'_> {
+    let total = h.total();
+    h.buckets
+        .iter()
+        .map(|(l, c)| (l.as_str(), share(*c, total).unwrap_or(0.0)))
+        .collect()
+}
+
+fn share_of(shares: &BucketShares, label: &str) -> f64 {
+    shares.get(label).copied().unwrap_or(0.0)
+}
+
+fn share(count: u64, total: u64) -> Option<f64> {
+    (total > 0).then(|| count as f64 / total as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::TxShape;
+
+    fn shape(n_vin: u32, n_sapling_output: u32) -> TxShape {
+        TxShape {
+            n_vin,
+            n_vout: 2,
+            n_joinsplit: 0,
+            n_sapling_spend: 0,
+            n_sapling_output,
+            n_orchard_action: 0,
+            size_bucket: 1,
+            version: 4,
+            ..Default::default()
+        }
+    }
+
+    fn window(transparent: usize, shielded: usize) -> ShapeStats {
+        let mut shapes = vec![shape(1, 0); transparent];
+        shapes.extend(vec![shape(0, 2); shielded]);
+        ShapeStats::from_shapes(&shapes)
+    }
+
+    const THRESHOLDS: Thresholds = Thresholds {
+        z: 3.0,
+        divergence: 0.05,
+        bucket_min_share: 0.01,
+    };
+
+    #[test]
+    fn test_baseline_mean_std() {
+        let windows = vec![window(80, 20), window(70, 30), window(75, 25)];
+        let b = Baseline::from_windows(&windows);
+        assert_eq!(b.windows, 3);
+        let shielded = b
+            .metrics
+            .iter()
+            .find(|m| m.name == "shielded_share")
+            .unwrap();
+        assert!((shielded.mean - 0.25).abs() < 1e-12);
+        assert!((shielded.std - 0.05).abs() < 1e-12);
+        let vv = b
+            .histograms
+            .iter()
+            .find(|h| h.histogram == "vin_vout")
+            .unwrap();
+        assert_eq!(vv.pooled.iter().map(|(_, c)| c).sum::<u64>(), 300);
+    }
+
+    #[test]
+    fn test_score_flags_shift_only() {
+        let windows = vec![
+            window(80, 20),
+            window(78, 22),
+            window(81, 19),
+            window(79, 21),
+        ];
+        let b = Baseline::from_windows(&windows);
+        assert!(b.score(&window(80, 20), THRESHOLDS).is_empty());
+        let alerts = b.score(&window(20, 80), THRESHOLDS);
+        assert!(alerts
+            .iter()
+            .any(|a| a.kind == "metric" && a.subject == "shielded_share" && a.score > 3.0));
+        assert!(alerts
+            .iter()
+            .any(|a| a.kind == "divergence" && a.subject == "vin_vout"));
+        assert!(alerts
+            .iter()
+            .any(|a| a.kind == "bucket_share" && a.subject == "vin_vout:0_2"));
+    }
+
+    #[test]
+    fn test_legacy_window_is_not_a_baseline() {
+        // Stored before logical actions and shape tuples were recorded.
+        let mut legacy = window(80, 20);
+        legacy.logical_actions_hist.clear();
+        legacy.shape_hist.clear();
+        let b = Baseline::from_windows(&[legacy, window(80, 20)]);
+        assert_eq!(b.windows, 2);
+        let grace = b
+            .metrics
+            .iter()
+            .find(|m| m.name == "grace_action_share")
+            .unwrap();
+        assert_eq!((grace.n, grace.std), (1, 0.0));
+        // The test shapes carry no script counts, so neither window has script data.
+        assert_eq!(
+            b.insufficient(),
+            [
+                "grace_action_share",
+                "shape",
+                "logical_actions",
+                "script_out",
+                "script_in"
+            ]
+        );
+        // A single baseline value would put 0.75 against 0.8 at z = -50.
+        let alerts = b.score(&window(75, 25), THRESHOLDS);
+        assert!(alerts.iter().all(|a| a.subject != "grace_action_share"
+            && !a.subject.starts_with("shape")
+            && !a.subject.starts_with("logical_actions")));
+        assert!(alerts.iter().any(|a| a.subject == "shielded_share"));
+    }
+}
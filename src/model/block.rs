@@ -0,0 +1,315 @@
+//! Block-level composition: transaction count, size and fullness against the block size
+//! limit, shielded share and the interval since the previous block, as distributions over
+//! blocks, and how they correlate with per-block shape diversity.
+
+use super::{labeled_buckets, Histogram, TxShape};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Consensus block size limit in bytes.
+pub const MAX_BLOCK_SIZE: u32 = 2_000_000;
+
+pub const TXS_LABELS: [&str; 5] = ["1", "2-10", "11-50", "51-200", "201+"];
+pub const FULLNESS_LABELS: [&str; 5] = ["<1%", "1-5%", "5-25%", "25-75%", "75%+"];
+pub const SHIELDED_LABELS: [&str; 4] = ["none", "minority", "majority", "all"];
+pub const INTERVAL_LABELS: [&str; 5] = ["<30s", "30-60s", "60-120s", "120-300s", "300s+"];
+
+/// Facts about one block as a unit, stored next to its shape histograms.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BlockMeta {
+    pub height: u32,
+    /// All transactions, coinbase included.
+    pub n_txs: u64,
+    /// Transactions with a shielded component, coinbase included.
+    pub shielded_txs: u64,
+    /// Serialized block size in bytes, if the node reported it.
+    pub size: Option<u32>,
+    /// Block header time (Unix seconds), if the node reported it.
+    pub time: Option<i64>,
+}
+
+impl BlockMeta {
+    pub fn from_shapes(
+        height: u32,
+        shapes: &[TxShape],
+        size: Option<u32>,
+        time: Option<i64>,
+    ) -> Self {
+        BlockMeta {
+            height,
+            n_txs: shapes.len() as u64,
+            shielded_txs: shapes.iter().filter(|s| s.has_shielded()).count() as u64,
+            size,
+            time,
+        }
+    }
+
+    /// Share of the block size limit used; `None` when the size is unknown.
+    pub fn fullness(&self) -> Option<f64> {
+        Some(self.size? as f64 / MAX_BLOCK_SIZE as f64)
+    }
+
+    pub fn shielded_share(&self) -> f64 {
+        if self.n_txs == 0 {
+            0.0
+        } else {
+            self.shielded_txs as f64 / self.n_txs as f64
+        }
+    }
+
+    fn txs_label(&self) -> &'static str {
+        let i = match self.n_txs {
+            0..=1 => 0,
+            2..=10 => 1,
+            11..=50 => 2,
+            51..=200 => 3,
+            _ => 4,
+        };
+        TXS_LABELS[i]
+    }
+
+    fn fullness_label(&self) -> Option<&'static str> {
+        let f = self.fullness()?;
+        let i = [0.01, 0.05, 0.25, 0.75]
+            .iter()
+            .position(|&t| f < t)
+            .unwrap_or(4);
+        Some(FULLNESS_LABELS[i])
+    }
+
+    fn shielded_label(&self) -> &'static str {
+        let i = match self.shielded_txs {
+            0 => 0,
+            n if n == self.n_txs => 3,
+            n if 2 * n > self.n_txs => 2,
+            _ => 1,
+        };
+        SHIELDED_LABELS[i]
+    }
+}
+
+fn interval_label(secs: i64) -> &'static str {
+    let i = [30, 60, 120, 300]
+        .iter()
+        .position(|&t| secs < t)
+        .unwrap_or(4);
+    INTERVAL_LABELS[i]
+}
+
+/// Seconds since the previous block, for blocks whose predecessor is also in `blocks` (sorted
+/// by height) and both times are known. Header times are not monotonic, so intervals can be
+/// negative.
+pub fn intervals(blocks: &[BlockMeta]) -> Vec<(u32, i64)> {
+    blocks
+        .windows(2)
+        .filter(|w| w[1].height == w[0].height + 1)
+        .filter_map(|w| Some((w[1].height, w[1].time? - w[0].time?)))
+        .collect()
+}
+
+/// Summary and distributions of block-level features over a range.
+#[derive(Debug, Clone)]
+pub struct BlockComposition {
+    pub n_blocks: u64,
+    pub mean_txs: f64,
+    /// Blocks with a known size; fullness covers only these.
+    pub sized_blocks: u64,
+    /// `None` when no block has a known size.
+    pub mean_fullness: Option<f64>,
+    pub max_fullness: Option<f64>,
+    pub mean_shielded_share: f64,
+    /// `None` when no two consecutive blocks have known times.
+    pub mean_interval_secs: Option<f64>,
+    /// Block counts per bucket: "block_txs", "block_fullness", "block_shielded",
+    /// "block_interval".
+    pub histograms: Vec<Histogram>,
+}
+
+/// Compose `blocks` (sorted by height).
+pub fn compose(blocks: &[BlockMeta]) -> BlockComposition {
+    let mut txs = HashMap::new();
+    let mut fullness = HashMap::new();
+    let mut shielded = HashMap::new();
+    let mut interval = HashMap::new();
+    for b in blocks {
+        *txs.entry(b.txs_label().to_string()).or_insert(0) += 1;
+        if let Some(label) = b.fullness_label() {
+            *fullness.entry(label.to_string()).or_insert(0) += 1;
+        }
+        *shielded.entry(b.shielded_label().to_string()).or_insert(0) += 1;
+    }
+    let gaps = intervals(blocks);
+    for &(_, secs) in &gaps {
+        *interval
+            .entry(interval_label(secs).to_string())
+            .or_insert(0) += 1;
+    }
+    let mean = |values: &mut dyn Iterator<Item = f64>, n: usize| {
+        if n == 0 {
+            0.0
+        } else {
+            values.sum::<f64>() / n as f64
+        }
+    };
+    let n = blocks.len();
+    let sized: Vec<f64> = blocks.iter().filter_map(BlockMeta::fullness).collect();
+    BlockComposition {
+        n_blocks: n as u64,
+        mean_txs: mean(&mut blocks.iter().map(|b| b.n_txs as f64), n),
+        sized_blocks: sized.len() as u64,
+        mean_fullness: (!sized.is_empty()).then(|| mean(&mut sized.iter().copied(), sized.len())),
+        max_fullness: (!sized.is_empty()).then(|| sized.iter().copied().fold(0.0, f64::max)),
+        mean_shielded_share: mean(&mut blocks.iter().map(BlockMeta::shielded_share), n),
+        mean_interval_secs: (!gaps.is_empty())
+            .then(|| mean(&mut gaps.iter().map(|g| g.1 as f64), gaps.len())),
+        histograms: vec![
+            Histogram {
+                name: "block_txs",
+                ordered: true,
+                buckets: labeled_buckets(TXS_LABELS, &txs),
+            },
+            Histogram {
+                name: "block_fullness",
+                ordered: true,
+                buckets: labeled_buckets(FULLNESS_LABELS, &fullness),
+            },
+            Histogram {
+                name: "block_shielded",
+                ordered: true,
+                buckets: labeled_buckets(SHIELDED_LABELS, &shielded),
+            },
+            Histogram {
+                name: "block_interval",
+                ordered: true,
+                buckets: labeled_buckets(INTERVAL_LABELS, &interval),
+            },
+        ],
+    }
+}
+
+/// Pearson correlation of one block feature with per-block shape entropy.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiversityCorrelation {
+    pub feature: &'static str,
+    /// Blocks with both values.
+    pub n: usize,
+    /// `None` with fewer than three blocks or a constant series.
+    pub pearson: Option<f64>,
+}
+
+/// Correlate block features with `shape_entropy` (bits, by height) over `blocks` (sorted).
+/// Blocks without a known size are left out of the fullness correlation only.
+pub fn diversity_correlations(
+    blocks: &[BlockMeta],
+    shape_entropy: &HashMap<u32, f64>,
+) -> Vec<DiversityCorrelation> {
+    let interval: HashMap<u32, i64> = intervals(blocks).into_iter().collect();
+    let value = |feature: &str, b: &BlockMeta| match feature {
+        "txs" => Some(b.n_txs as f64),
+        "fullness" => b.fullness(),
+        "shielded_share" => Some(b.shielded_share()),
+        _ => interval.get(&b.height).map(|&s| s as f64),
+    };
+    ["txs", "fullness", "shielded_share", "interval"]
+        .into_iter()
+        .map(|feature| {
+            let (xs, ys): (Vec<f64>, Vec<f64>) = blocks
+                .iter()
+                .filter_map(|b| Some((value(feature, b)?, *shape_entropy.get(&b.height)?)))
+                .unzip();
+            DiversityCorrelation {
+                feature,
+                n: xs.len(),
+                pearson: pearson(&xs, &ys),
+            }
+        })
+        .collect()
+}
+
+fn pearson(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    if xs.len() < 3 {
+        return None;
+    }
+    let n = xs.len() as f64;
+    let (mx, my) = (xs.iter().sum::<f64>() / n, ys.iter().sum::<f64>() / n);
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        sxy += (x - mx) * (y - my);
+        sxx += (x - mx) * (x - mx);
+        syy += (y - my) * (y - my);
+    }
+    (sxx > 0.0 && syy > 0.0).then(|| sxy / (sxx * syy).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(height: u32, n_txs: u64, shielded_txs: u64, size: u32, time: i64) -> BlockMeta {
+        BlockMeta {
+            height,
+            n_txs,
+            shielded_txs,
+            size: Some(size),
+            time: Some(time),
+        }
+    }
+
+    #[test]
+    fn test_compose() {
+        let blocks = [
+            meta(10, 1, 0, 2_000, 1_000),
+            meta(11, 40, 40, 400_000, 1_070),
+            meta(12, 300, 100, 1_800_000, 1_400),
+            // Gap: no interval for 14.
+            meta(14, 5, 3, 30_000, 1_500),
+            // Unknown size: no fullness.
+            BlockMeta {
+                size: None,
+                ..meta(20, 2, 0, 0, 2_000)
+            },
+        ];
+        let c = compose(&blocks);
+        assert_eq!((c.n_blocks, c.sized_blocks), (5, 4));
+        assert_eq!(intervals(&blocks), vec![(11, 70), (12, 330)]);
+        assert_eq!(c.mean_interval_secs, Some(200.0));
+        assert!((c.max_fullness.unwrap() - 0.9).abs() < 1e-12);
+        let get = |name: &str| {
+            c.histograms
+                .iter()
+                .find(|h| h.name == name)
+                .unwrap()
+                .buckets
+                .clone()
+        };
+        assert_eq!(get("block_txs")[0], ("1".to_string(), 1));
+        assert_eq!(get("block_fullness")[4], ("75%+".to_string(), 1));
+        assert_eq!(get("block_fullness").iter().map(|b| b.1).sum::<u64>(), 4);
+        assert_eq!(
+            get("block_shielded")
+                .iter()
+                .map(|b| b.1)
+                .collect::<Vec<_>>(),
+            vec![2, 1, 1, 1]
+        );
+        assert_eq!(get("block_interval")[2], ("60-120s".to_string(), 1));
+        assert_eq!(get("block_interval")[4], ("300s+".to_string(), 1));
+    }
+
+    #[test]
+    fn test_diversity_correlations() {
+        let blocks: Vec<BlockMeta> = (0..5)
+            .map(|i| meta(i, 10 + i as u64, 0, 10_000 * (i + 1), 75 * i as i64))
+            .collect();
+        let entropy: HashMap<u32, f64> = (0..5).map(|i| (i, 3.0 - i as f64)).collect();
+        let corr = diversity_correlations(&blocks, &entropy);
+        let txs = corr.iter().find(|c| c.feature == "txs").unwrap();
+        assert_eq!(txs.n, 5);
+        assert!((txs.pearson.unwrap() + 1.0).abs() < 1e-12);
+        // Constant shielded share and interval.
+        let shielded = corr.iter().find(|c| c.feature == "shielded_share").unwrap();
+        assert_eq!(shielded.pearson, None);
+        let interval = corr.iter().find(|c| c.feature == "interval").unwrap();
+        assert_eq!((interval.n, interval.pearson), (4, None));
+    }
+}
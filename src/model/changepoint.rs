@@ -0,0 +1,247 @@
+//! Change-point detection over metric time series (one value per window of blocks).
+//!
+//! Two complementary detectors:
+//! - CUSUM with bootstrap confidence and binary segmentation (Taylor's method): finds mean
+//!   shifts and reports the share of shuffled series with a smaller CUSUM range.
+//! - Bayesian online change-point detection (Adams & MacKay) with a Normal–Gamma model:
+//!   reports the posterior probability that a new segment starts at a window.
+
+use super::significance::ln_gamma;
+use crate::util::Rng;
+use serde::Serialize;
+
+/// Shuffles used to estimate CUSUM confidence.
+pub const CUSUM_BOOTSTRAPS: usize = 500;
+/// Shortest segment (in windows) that binary segmentation will split further.
+pub const MIN_SEGMENT: usize = 4;
+/// Expected run length between changes (windows) for the BOCPD hazard.
+pub const EXPECTED_RUN: f64 = 100.0;
+/// Windows of look-ahead used when scoring a BOCPD change.
+pub const BOCPD_LAG: usize = 5;
+const SEED: u64 = 0x6370_6474;
+
+/// Detected change: the new regime starts at `index` (window index into the series).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangePoint {
+    pub index: usize,
+    /// Confidence in 0..=1 (bootstrap share for CUSUM, posterior probability for BOCPD).
+    pub confidence: f64,
+}
+
+/// CUSUM change points with binary segmentation; only changes with confidence ≥ `min_confidence` are kept.
+pub fn cusum(series: &[f64], min_confidence: f64) -> Vec<ChangePoint> {
+    let mut rng = Rng::new(SEED);
+    let mut out = Vec::new();
+    segment_cusum(series, 0, min_confidence, &mut rng, &mut out);
+    out.sort_by_key(|c| c.index);
+    out
+}
+
+fn segment_cusum(
+    seg: &[f64],
+    offset: usize,
+    min_confidence: f64,
+    rng: &mut Rng,
+    out: &mut Vec<ChangePoint>,
+) {
+    if seg.len() < MIN_SEGMENT {
+        return;
+    }
+    let (split, range) = cusum_range(seg);
+    if range == 0.0 {
+        return;
+    }
+    let mut shuffled = seg.to_vec();
+    let mut below = 0usize;
+    for _ in 0..CUSUM_BOOTSTRAPS {
+        for i in (1..shuffled.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            shuffled.swap(i, j);
+        }
+        if cusum_range(&shuffled).1 < range {
+            below += 1;
+        }
+    }
+    let confidence = below as f64 / CUSUM_BOOTSTRAPS as f64;
+    if confidence < min_confidence {
+        return;
+    }
+    out.push(ChangePoint {
+        index: offset + split,
+        confidence,
+    });
+    segment_cusum(&seg[..split], offset, min_confidence, rng, out);
+    segment_cusum(&seg[split..], offset + split, min_confidence, rng, out);
+}
+
+/// (index where the new regime starts, max − min of the cumulative sum of deviations).
+fn cusum_range(seg: &[f64]) -> (usize, f64) {
+    let mean = seg.iter().sum::<f64>() / seg.len() as f64;
+    let (mut sum, mut min, mut max) = (0.0f64, 0.0f64, 0.0f64);
+    let (mut split, mut best) = (0usize, 0.0f64);
+    for (i, x) in seg.iter().enumerate() {
+        sum += x - mean;
+        min = min.min(sum);
+        max = max.max(sum);
+        if i + 1 < seg.len() && sum.abs() > best {
+            best = sum.abs();
+            split = i + 1;
+        }
+    }
+    (split, max - min)
+}
+
+/// Bayesian online change points with probability ≥ `min_confidence`, after non-maximum suppression.
+pub fn bocpd(series: &[f64], min_confidence: f64) -> Vec<ChangePoint> {
+    let n = series.len();
+    let Some(x) = standardize(series) else {
+        return Vec::new();
+    };
+    let hazard = 1.0 / EXPECTED_RUN;
+    // Run-length posterior: r = number of observations in the current segment.
+    let mut run = vec![1.0];
+    let mut params = vec![NormalGamma::PRIOR];
+    let mut start_prob = vec![0.0; n];
+    for (t, &xt) in x.iter().enumerate() {
+        let pred: Vec<f64> = params.iter().map(|p| p.predictive(xt)).collect();
+        let mut next = Vec::with_capacity(run.len() + 1);
+        next.push(0.0);
+        let mut change_mass = 0.0;
+        for (r, p) in run.iter().zip(&pred) {
+            next.push(r * p * (1.0 - hazard));
+            change_mass += r * p * hazard;
+        }
+        next[0] = change_mass;
+        let total: f64 = next.iter().sum();
+        if total > 0.0 {
+            next.iter_mut().for_each(|v| *v /= total);
+        }
+        let mut next_params = Vec::with_capacity(params.len() + 1);
+        next_params.push(NormalGamma::PRIOR);
+        next_params.extend(params.iter().map(|p| p.update(xt)));
+        run = next;
+        params = next_params;
+        // After observing x_t, run length r means the segment started at t + 1 - r.
+        // Score start s once BOCPD_LAG observations of the new segment are in (or at the end).
+        let scored: Vec<usize> = if t + 1 == n {
+            (t.saturating_sub(BOCPD_LAG - 1).max(1)..=t).collect()
+        } else if t >= BOCPD_LAG {
+            vec![t + 1 - BOCPD_LAG]
+        } else {
+            Vec::new()
+        };
+        for s in scored {
+            // Allow the start to be off by one window.
+            start_prob[s] = (s.saturating_sub(1).max(1)..=(s + 1).min(t))
+                .map(|j| run.get(t + 1 - j).copied().unwrap_or(0.0))
+                .sum();
+        }
+    }
+    let mut out: Vec<ChangePoint> = Vec::new();
+    for s in 1..n {
+        let p = start_prob[s];
+        if p < min_confidence {
+            continue;
+        }
+        let lo = s.saturating_sub(BOCPD_LAG);
+        let hi = (s + BOCPD_LAG).min(n - 1);
+        let is_max = (lo..=hi).all(|j| start_prob[j] < p || (start_prob[j] == p && j >= s));
+        if is_max {
+            out.push(ChangePoint {
+                index: s,
+                confidence: p.min(1.0),
+            });
+        }
+    }
+    out
+}
+
+/// Scale the series by a robust noise estimate (MAD of first differences) so the prior is unit-free.
+fn standardize(series: &[f64]) -> Option<Vec<f64>> {
+    if series.len() < 2 {
+        return None;
+    }
+    let mean = series.iter().sum::<f64>() / series.len() as f64;
+    let mut diffs: Vec<f64> = series.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
+    diffs.sort_by(|a, b| a.total_cmp(b));
+    let mut scale = diffs[diffs.len() / 2] / (0.6745 * std::f64::consts::SQRT_2);
+    if scale <= 0.0 {
+        let var = series.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / series.len() as f64;
+        scale = var.sqrt();
+    }
+    if scale <= 0.0 {
+        return None;
+    }
+    Some(series.iter().map(|x| (x - mean) / scale).collect())
+}
+
+/// Normal–Gamma posterior over an unknown mean and precision.
+#[derive(Debug, Clone, Copy)]
+struct NormalGamma {
+    mu: f64,
+    kappa: f64,
+    alpha: f64,
+    beta: f64,
+}
+
+impl NormalGamma {
+    const PRIOR: NormalGamma = NormalGamma {
+        mu: 0.0,
+        kappa: 1.0,
+        alpha: 1.0,
+        beta: 1.0,
+    };
+
+    fn update(&self, x: f64) -> NormalGamma {
+        NormalGamma {
+            mu: (self.kappa * self.mu + x) / (self.kappa + 1.0),
+            kappa: self.kappa + 1.0,
+            alpha: self.alpha + 0.5,
+            beta: self.beta + self.kappa * (x - self.mu).powi(2) / (2.0 * (self.kappa + 1.0)),
+        }
+    }
+
+    /// Student-t posterior predictive density at x.
+    fn predictive(&self, x: f64) -> f64 {
+        let nu = 2.0 * self.alpha;
+        let scale2 = self.beta * (self.kappa + 1.0) / (self.alpha * self.kappa);
+        let z = (x - self.mu).powi(2) / (nu * scale2);
+        let log_norm = ln_gamma((nu + 1.0) / 2.0)
+            - ln_gamma(nu / 2.0)
+            - 0.5 * (nu * std::f64::consts::PI * scale2).ln();
+        (log_norm - (nu + 1.0) / 2.0 * (1.0 + z).ln()).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step_series() -> Vec<f64> {
+        let mut rng = Rng::new(1);
+        (0..80)
+            .map(|i| {
+                let level = if i < 50 { 0.30 } else { 0.60 };
+                level + 0.02 * rng.next_normal()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cusum_finds_step() {
+        let cps = cusum(&step_series(), 0.95);
+        assert!(!cps.is_empty());
+        assert!(cps
+            .iter()
+            .any(|c| (48..=52).contains(&c.index) && c.confidence >= 0.95));
+        assert!(cusum(&[0.5; 20], 0.95).is_empty());
+    }
+
+    #[test]
+    fn test_bocpd_finds_step() {
+        let cps = bocpd(&step_series(), 0.5);
+        assert_eq!(cps.len(), 1, "{:?}", cps);
+        assert!((49..=51).contains(&cps[0].index));
+        assert!(bocpd(&[0.5; 20], 0.5).is_empty());
+    }
+}
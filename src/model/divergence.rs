@@ -0,0 +1,327 @@
+//! Divergence between two shape distributions (composition, independent of volume).
+
+use super::Histogram;
+use serde::Serialize;
+
+/// Pseudo-count added to every bucket before computing KL and PSI (Jeffreys prior).
+pub const SMOOTHING: f64 = 0.5;
+
+/// Divergence metrics between histogram A and histogram B.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Divergence {
+    /// Jensen–Shannon divergence in bits (0..=1).
+    pub jensen_shannon: f64,
+    /// KL(A || B) in bits, with additive smoothing.
+    pub kl_ab: f64,
+    /// KL(B || A) in bits, with additive smoothing.
+    pub kl_ba: f64,
+    /// Total variation distance (0..=1).
+    pub total_variation: f64,
+    /// Population stability index (natural log, smoothed; B is the "actual", A the "expected").
+    pub psi: f64,
+    /// Earth mover's distance in bucket units; only defined for ordered histograms.
+    pub wasserstein: Option<f64>,
+}
+
+/// Divergence tagged with the histogram it was computed for.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramDivergence {
+    pub histogram: String,
+    #[serde(flatten)]
+    pub divergence: Divergence,
+}
+
+/// Two histograms over the union of their buckets, in a shared order.
+#[derive(Debug, Clone, Default)]
+pub struct AlignedCounts {
+    pub labels: Vec<String>,
+    pub a: Vec<u64>,
+    pub b: Vec<u64>,
+}
+
+/// Align `a` and `b` by bucket label: A's order first, then buckets only present in B.
+pub fn align(a: &Histogram, b: &Histogram) -> AlignedCounts {
+    let mut out = AlignedCounts::default();
+    let b_counts: std::collections::HashMap<&str, u64> =
+        b.buckets.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+    for (label, count) in &a.buckets {
+        out.labels.push(label.clone());
+        out.a.push(*count);
+        out.b
+            .push(b_counts.get(label.as_str()).copied().unwrap_or(0));
+    }
+    let a_labels: std::collections::HashSet<&str> =
+        a.buckets.iter().map(|(k, _)| k.as_str()).collect();
+    for (label, count) in &b.buckets {
+        if !a_labels.contains(label.as_str()) {
+            out.labels.push(label.clone());
+            out.a.push(0);
+            out.b.push(*count);
+        }
+    }
+    out
+}
+
+impl Divergence {
+    /// Compare two aligned count vectors. Returns all zeros if either side is empty.
+    pub fn between(a: &[u64], b: &[u64], ordered: bool) -> Self {
+        let p = shares(a, 0.0);
+        let q = shares(b, 0.0);
+        if p.is_empty() || q.is_empty() {
+            return Divergence::default();
+        }
+        let ps = shares(a, SMOOTHING);
+        let qs = shares(b, SMOOTHING);
+        let m: Vec<f64> = p.iter().zip(&q).map(|(x, y)| (x + y) / 2.0).collect();
+        Divergence {
+            jensen_shannon: (kl(&p, &m) + kl(&q, &m)) / 2.0,
+            kl_ab: kl(&ps, &qs),
+            kl_ba: kl(&qs, &ps),
+            total_variation: p.iter().zip(&q).map(|(x, y)| (x - y).abs()).sum::<f64>() / 2.0,
+            psi: ps
+                .iter()
+                .zip(&qs)
+                .map(|(e, a)| (a - e) * (a / e).ln())
+                .sum(),
+            wasserstein: ordered.then(|| wasserstein(&p, &q)),
+        }
+    }
+}
+
+/// Divergence for every pair of same-named histograms.
+pub fn histogram_divergences(a: &[Histogram], b: &[Histogram]) -> Vec<HistogramDivergence> {
+    a.iter()
+        .zip(b)
+        .map(|(ha, hb)| {
+            let aligned = align(ha, hb);
+            HistogramDivergence {
+                histogram: ha.name.to_string(),
+                divergence: Divergence::between(&aligned.a, &aligned.b, ha.ordered),
+            }
+        })
+        .collect()
+}
+
+/// Per-bucket comparison of one bucket between range A and range B.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BucketDelta {
+    pub bucket: String,
+    pub count_a: u64,
+    pub count_b: u64,
+    pub share_a: f64,
+    pub share_b: f64,
+    /// share_b - share_a.
+    pub share_delta: f64,
+    /// share_b / share_a; None when the bucket is absent from A.
+    pub ratio: Option<f64>,
+}
+
+/// Order of per-bucket deltas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BucketSort {
+    /// Largest |share_delta| first.
+    #[default]
+    AbsShareDelta,
+    /// Largest growth first, largest shrink last.
+    ShareDelta,
+    /// Largest ratio first; buckets new in B lead.
+    Ratio,
+    /// Largest |count_b - count_a| first.
+    CountDelta,
+    /// Histogram bucket order.
+    Bucket,
+}
+
+impl BucketSort {
+    pub const ALL: [BucketSort; 5] = [
+        BucketSort::AbsShareDelta,
+        BucketSort::ShareDelta,
+        BucketSort::Ratio,
+        BucketSort::CountDelta,
+        BucketSort::Bucket,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BucketSort::AbsShareDelta => "abs-share-delta",
+            BucketSort::ShareDelta => "share-delta",
+            BucketSort::Ratio => "ratio",
+            BucketSort::CountDelta => "count-delta",
+            BucketSort::Bucket => "bucket",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<BucketSort> {
+        BucketSort::ALL.into_iter().find(|s| s.label() == label)
+    }
+}
+
+/// Per-bucket comparison of one histogram, in the requested `BucketSort` order.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBucketDeltas {
+    pub histogram: String,
+    pub buckets: Vec<BucketDelta>,
+}
+
+/// Per-bucket deltas over the union of buckets, in `sort` order (ties keep bucket order).
+/// Buckets empty in both ranges are skipped.
+pub fn bucket_deltas(a: &Histogram, b: &Histogram, sort: BucketSort) -> Vec<BucketDelta> {
+    let aligned = align(a, b);
+    let total_a: u64 = aligned.a.iter().sum();
+    let total_b: u64 = aligned.b.iter().sum();
+    let share = |c: u64, total: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            c as f64 / total as f64
+        }
+    };
+    let mut out: Vec<BucketDelta> = aligned
+        .labels
+        .into_iter()
+        .zip(aligned.a.into_iter().zip(aligned.b))
+        .filter(|(_, (count_a, count_b))| count_a + count_b > 0)
+        .map(|(bucket, (count_a, count_b))| {
+            let share_a = share(count_a, total_a);
+            let share_b = share(count_b, total_b);
+            BucketDelta {
+                bucket,
+                count_a,
+                count_b,
+                share_a,
+                share_b,
+                share_delta: share_b - share_a,
+                ratio: (share_a > 0.0).then(|| share_b / share_a),
+            }
+        })
+        .collect();
+    let count_delta = |d: &BucketDelta| d.count_b.abs_diff(d.count_a);
+    match sort {
+        BucketSort::AbsShareDelta => {
+            out.sort_by(|x, y| y.share_delta.abs().total_cmp(&x.share_delta.abs()))
+        }
+        BucketSort::ShareDelta => out.sort_by(|x, y| y.share_delta.total_cmp(&x.share_delta)),
+        BucketSort::Ratio => out.sort_by(|x, y| {
+            let ratio = |d: &BucketDelta| d.ratio.unwrap_or(f64::INFINITY);
+            ratio(y).total_cmp(&ratio(x))
+        }),
+        BucketSort::CountDelta => out.sort_by_key(|d| std::cmp::Reverse(count_delta(d))),
+        BucketSort::Bucket => {}
+    }
+    out
+}
+
+/// Per-bucket deltas for every pair of same-named histograms.
+pub fn histogram_bucket_deltas(
+    a: &[Histogram],
+    b: &[Histogram],
+    sort: BucketSort,
+) -> Vec<HistogramBucketDeltas> {
+    a.iter()
+        .zip(b)
+        .map(|(ha, hb)| HistogramBucketDeltas {
+            histogram: ha.name.to_string(),
+            buckets: bucket_deltas(ha, hb, sort),
+        })
+        .collect()
+}
+
+/// Normalized shares with `pseudo` added to every bucket; empty if the total is 0.
+fn shares(counts: &[u64], pseudo: f64) -> Vec<f64> {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    let denom = total as f64 + pseudo * counts.len() as f64;
+    counts
+        .iter()
+        .map(|&c| (c as f64 + pseudo) / denom)
+        .collect()
+}
+
+/// KL(p || q) in bits; terms with p = 0 contribute nothing.
+fn kl(p: &[f64], q: &[f64]) -> f64 {
+    p.iter()
+        .zip(q)
+        .filter(|(x, _)| **x > 0.0)
+        .map(|(x, y)| x * (x / y).log2())
+        .sum()
+}
+
+/// 1-Wasserstein distance between two distributions on the same ordered buckets.
+fn wasserstein(p: &[f64], q: &[f64]) -> f64 {
+    let mut cdf_gap = 0.0;
+    let mut dist = 0.0;
+    for (x, y) in p.iter().zip(q) {
+        cdf_gap += x - y;
+        dist += cdf_gap.abs();
+    }
+    dist
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_distributions() {
+        let d = Divergence::between(&[10, 20, 30], &[1, 2, 3], true);
+        assert!(d.jensen_shannon.abs() < 1e-12);
+        assert!(d.total_variation.abs() < 1e-12);
+        assert!(d.wasserstein.unwrap().abs() < 1e-12);
+        assert!(d.kl_ab.abs() < 0.01 && d.psi.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_disjoint_distributions() {
+        let d = Divergence::between(&[10, 0, 0], &[0, 0, 10], true);
+        assert!((d.jensen_shannon - 1.0).abs() < 1e-12);
+        assert!((d.total_variation - 1.0).abs() < 1e-12);
+        assert!((d.wasserstein.unwrap() - 2.0).abs() < 1e-12);
+        assert!(d.kl_ab.is_finite() && d.kl_ab > 1.0);
+        assert!(Divergence::between(&[1], &[1], false).wasserstein.is_none());
+        assert_eq!(Divergence::between(&[], &[5], false), Divergence::default());
+    }
+
+    #[test]
+    fn test_align_union() {
+        let a = Histogram {
+            name: "vin_vout",
+            ordered: false,
+            buckets: vec![("1_1".to_string(), 3), ("1_2".to_string(), 1)],
+        };
+        let b = Histogram {
+            name: "vin_vout",
+            ordered: false,
+            buckets: vec![("2_2".to_string(), 4), ("1_2".to_string(), 2)],
+        };
+        let aligned = align(&a, &b);
+        assert_eq!(aligned.labels, ["1_1", "1_2", "2_2"]);
+        assert_eq!(aligned.a, [3, 1, 0]);
+        assert_eq!(aligned.b, [0, 2, 4]);
+
+        let deltas = bucket_deltas(&a, &b, BucketSort::default());
+        let order: Vec<&str> = deltas.iter().map(|d| d.bucket.as_str()).collect();
+        assert_eq!(order, ["1_1", "2_2", "1_2"]);
+        assert!((deltas[0].share_delta + 0.75).abs() < 1e-12);
+        assert_eq!(deltas[0].ratio, Some(0.0));
+        assert_eq!(deltas[1].ratio, None);
+        assert!((deltas[2].ratio.unwrap() - 4.0 / 3.0).abs() < 1e-12);
+
+        let order = |sort| -> Vec<String> {
+            bucket_deltas(&a, &b, sort)
+                .into_iter()
+                .map(|d| d.bucket)
+                .collect()
+        };
+        assert_eq!(order(BucketSort::ShareDelta), ["2_2", "1_2", "1_1"]);
+        assert_eq!(order(BucketSort::Ratio), ["2_2", "1_2", "1_1"]);
+        assert_eq!(order(BucketSort::CountDelta), ["2_2", "1_1", "1_2"]);
+        assert_eq!(order(BucketSort::Bucket), ["1_1", "1_2", "2_2"]);
+        assert_eq!(
+            BucketSort::from_label("count-delta"),
+            Some(BucketSort::CountDelta)
+        );
+        assert_eq!(BucketSort::from_label("size"), None);
+    }
+}
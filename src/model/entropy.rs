@@ -0,0 +1,133 @@
+//! Bias-corrected Shannon entropy estimators and bootstrap confidence intervals.
+//!
+//! The plug-in estimator (`util::entropy`) underestimates entropy on small samples, which
+//! dominates per-block and low-traffic histograms. All values are in bits.
+
+use crate::util::{entropy, Rng};
+use serde::Serialize;
+
+/// Number of bootstrap resamples used for confidence intervals.
+pub const BOOTSTRAP_RESAMPLES: usize = 200;
+/// Two-sided confidence level of bootstrap intervals.
+pub const CONFIDENCE_LEVEL: f64 = 0.95;
+/// Fixed seed so that reports are reproducible.
+const BOOTSTRAP_SEED: u64 = 0x7873_6861_7065;
+
+/// Entropy estimates for one histogram, with a bootstrap interval around the Chao–Shen estimate.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EntropyEstimate {
+    pub plugin: f64,
+    pub miller_madow: f64,
+    pub chao_shen: f64,
+    /// Lower bound of the percentile bootstrap interval (Chao–Shen).
+    pub ci_low: f64,
+    /// Upper bound of the percentile bootstrap interval (Chao–Shen).
+    pub ci_high: f64,
+}
+
+impl EntropyEstimate {
+    pub fn from_counts(counts: &[u64]) -> Self {
+        let (ci_low, ci_high) =
+            bootstrap_ci(counts, chao_shen, BOOTSTRAP_RESAMPLES, CONFIDENCE_LEVEL);
+        EntropyEstimate {
+            plugin: entropy(counts),
+            miller_madow: miller_madow(counts),
+            chao_shen: chao_shen(counts),
+            ci_low,
+            ci_high,
+        }
+    }
+
+    /// Whether two intervals are disjoint, i.e. the entropy change is unlikely to be sampling noise.
+    pub fn differs_from(&self, other: &EntropyEstimate) -> bool {
+        self.ci_high < other.ci_low || other.ci_high < self.ci_low
+    }
+}
+
+/// Miller–Madow estimator: plug-in entropy plus (K - 1) / 2n, K = observed buckets.
+pub fn miller_madow(counts: &[u64]) -> f64 {
+    let n: u64 = counts.iter().sum();
+    if n == 0 {
+        return 0.0;
+    }
+    let observed = counts.iter().filter(|&&c| c > 0).count() as f64;
+    entropy(counts) + (observed - 1.0) / (2.0 * n as f64 * std::f64::consts::LN_2)
+}
+
+/// Chao–Shen estimator: coverage-adjusted probabilities with Horvitz–Thompson weighting.
+pub fn chao_shen(counts: &[u64]) -> f64 {
+    let n: u64 = counts.iter().sum();
+    if n == 0 {
+        return 0.0;
+    }
+    let mut singletons = counts.iter().filter(|&&c| c == 1).count() as u64;
+    if singletons == n {
+        // All singletons gives zero coverage; use the standard n - 1 adjustment.
+        singletons = n - 1;
+    }
+    let nf = n as f64;
+    let coverage = 1.0 - singletons as f64 / nf;
+    -counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let pa = coverage * c as f64 / nf;
+            let inclusion = 1.0 - (1.0 - pa).powf(nf);
+            pa * pa.log2() / inclusion
+        })
+        .sum::<f64>()
+}
+
+/// Percentile bootstrap interval of `estimator` under multinomial resampling of `counts`.
+pub fn bootstrap_ci(
+    counts: &[u64],
+    estimator: fn(&[u64]) -> f64,
+    resamples: usize,
+    level: f64,
+) -> (f64, f64) {
+    let n: u64 = counts.iter().sum();
+    if n == 0 || resamples == 0 {
+        return (0.0, 0.0);
+    }
+    let mut rng = Rng::new(BOOTSTRAP_SEED);
+    let mut values: Vec<f64> = (0..resamples)
+        .map(|_| estimator(&rng.multinomial(n, counts)))
+        .collect();
+    values.sort_by(|a, b| a.total_cmp(b));
+    let alpha = (1.0 - level) / 2.0;
+    let last = (values.len() - 1) as f64;
+    let lo = values[(alpha * last).floor() as usize];
+    let hi = values[((1.0 - alpha) * last).ceil() as usize];
+    (lo, hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corrections_exceed_plugin() {
+        let counts = [3, 2, 1, 1, 1];
+        let plugin = entropy(&counts);
+        assert!(miller_madow(&counts) > plugin);
+        assert!(chao_shen(&counts) > plugin);
+        assert_eq!(miller_madow(&[]), 0.0);
+        assert_eq!(chao_shen(&[0, 0]), 0.0);
+        // A single bucket has no uncertainty under any estimator.
+        assert_eq!(chao_shen(&[10]), 0.0);
+    }
+
+    #[test]
+    fn test_bootstrap_interval() {
+        let small = EntropyEstimate::from_counts(&[4, 3, 2, 1]);
+        assert!(small.ci_low <= small.chao_shen && small.chao_shen <= small.ci_high);
+        let large = EntropyEstimate::from_counts(&[4000, 3000, 2000, 1000]);
+        assert!(large.ci_high - large.ci_low < small.ci_high - small.ci_low);
+        assert_eq!(
+            EntropyEstimate::from_counts(&[4000, 3000, 2000, 1000]),
+            large
+        );
+        let skewed = EntropyEstimate::from_counts(&[9700, 100, 100, 100]);
+        assert!(large.differs_from(&skewed));
+    }
+}
@@ -0,0 +1,136 @@
+//! Shape flood (spam burst) detection over consecutive blocks.
+//!
+//! A window of consecutive heights is flagged when a single full shape tuple accounts for at
+//! least `min_share` of its transactions and at least `min_count` transactions in total, as
+//! during the 2022 sandblasting episode. Overlapping flagged windows with the same dominant
+//! shape are merged and trimmed to the blocks that contain it.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A flagged block range [low, high) dominated by one shape tuple.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FloodWindow {
+    pub low: u32,
+    pub high: u32,
+    /// Dominant shape tuple (TxShape::tuple_key).
+    pub shape: String,
+    /// Transactions with the dominant shape in the window.
+    pub shape_count: u64,
+    /// All transactions in the window.
+    pub n_txs: u64,
+    /// shape_count / n_txs.
+    pub share: f64,
+}
+
+/// Per-block input: (height, tx count, shape-tuple histogram), ordered by height.
+pub type BlockShapes = (u32, u64, HashMap<String, u64>);
+
+/// Detect flood windows of `window_blocks` consecutive heights (0 is treated as 1).
+pub fn detect_floods(
+    blocks: &[BlockShapes],
+    window_blocks: u32,
+    min_share: f64,
+    min_count: u64,
+) -> Vec<FloodWindow> {
+    let window_blocks = window_blocks.max(1);
+    let mut flagged: Vec<(u32, u32, String)> = Vec::new();
+    let mut hist: HashMap<&str, u64> = HashMap::new();
+    let mut n_txs = 0u64;
+    let mut end = 0usize;
+    for (start, (h, _, _)) in blocks.iter().enumerate() {
+        let w_hi = h.saturating_add(window_blocks);
+        while end < blocks.len() && blocks[end].0 < w_hi {
+            n_txs += blocks[end].1;
+            for (k, v) in &blocks[end].2 {
+                *hist.entry(k.as_str()).or_insert(0) += v;
+            }
+            end += 1;
+        }
+        if let Some((shape, &count)) = hist.iter().max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0))) {
+            if n_txs > 0 && count >= min_count && count as f64 / n_txs as f64 >= min_share {
+                match flagged.last_mut() {
+                    Some((_, hi, s)) if *hi >= *h && s == shape => *hi = w_hi,
+                    _ => flagged.push((*h, w_hi, shape.to_string())),
+                }
+            }
+        }
+        n_txs -= blocks[start].1;
+        for (k, v) in &blocks[start].2 {
+            if let Some(c) = hist.get_mut(k.as_str()) {
+                *c -= v;
+                if *c == 0 {
+                    hist.remove(k.as_str());
+                }
+            }
+        }
+    }
+    flagged
+        .into_iter()
+        .filter_map(|(lo, hi, shape)| summarize(blocks, lo, hi, shape))
+        .collect()
+}
+
+/// Trim [lo, hi) to the blocks containing `shape` and compute the window totals.
+fn summarize(blocks: &[BlockShapes], lo: u32, hi: u32, shape: String) -> Option<FloodWindow> {
+    let span: Vec<&BlockShapes> = blocks
+        .iter()
+        .filter(|(h, _, _)| *h >= lo && *h < hi)
+        .collect();
+    let first = span.iter().position(|b| b.2.contains_key(&shape))?;
+    let last = span.iter().rposition(|b| b.2.contains_key(&shape))?;
+    let span = &span[first..=last];
+    let n_txs: u64 = span.iter().map(|b| b.1).sum();
+    let shape_count: u64 = span.iter().filter_map(|b| b.2.get(&shape)).sum();
+    Some(FloodWindow {
+        low: span[0].0,
+        high: span[span.len() - 1].0 + 1,
+        shape,
+        shape_count,
+        n_txs,
+        share: shape_count as f64 / n_txs.max(1) as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(height: u32, counts: &[(&str, u64)]) -> BlockShapes {
+        let hist: HashMap<String, u64> = counts.iter().map(|(k, v)| (k.to_string(), *v)).collect();
+        (height, hist.values().sum(), hist)
+    }
+
+    #[test]
+    fn test_detects_and_merges_burst() {
+        let spam = "0_0_0_1_2_0_3_4";
+        let mut blocks: Vec<BlockShapes> = (0..20)
+            .map(|h| block(h, &[("1_2_0_0_0_0_1_4", 5), ("1_1_0_0_0_0_0_4", 5)]))
+            .collect();
+        for b in blocks.iter_mut().filter(|b| (8..12).contains(&b.0)) {
+            *b = block(b.0, &[("1_2_0_0_0_0_1_4", 5), (spam, 200)]);
+        }
+        let floods = detect_floods(&blocks, 3, 0.8, 100);
+        assert_eq!(floods.len(), 1, "{:?}", floods);
+        let f = &floods[0];
+        assert_eq!((f.low, f.high), (8, 12));
+        assert_eq!(f.shape, spam);
+        assert_eq!(f.shape_count, 800);
+        assert_eq!(f.n_txs, 820);
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let blocks: Vec<BlockShapes> = (0..10).map(|h| block(h, &[("a", 9), ("b", 1)])).collect();
+        assert!(detect_floods(&blocks, 5, 0.95, 1).is_empty());
+        assert!(detect_floods(&blocks, 5, 0.5, 1000).is_empty());
+        let all = detect_floods(&blocks, 5, 0.5, 10);
+        assert_eq!(all.len(), 1);
+        assert_eq!((all[0].low, all[0].high), (0, 10));
+        // A zero-block window does not underflow; it scans single blocks.
+        assert_eq!(
+            detect_floods(&blocks, 0, 0.5, 9),
+            detect_floods(&blocks, 1, 0.5, 9)
+        );
+    }
+}
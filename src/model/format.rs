@@ -0,0 +1,519 @@
+//! Transaction format flags: categorical encodings of header fields that wallets set
+//! differently. Raw field values are never kept, only the category they fall into.
+
+use serde::{Deserialize, Serialize};
+
+/// nLockTime values below this are block heights, at or above it Unix times.
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+/// Default expiry deltas: 20 blocks as set by ZIP 203, and 40 since Blossom halved the block
+/// target spacing (ZIP 208), keeping the same wall-clock time.
+pub const DEFAULT_EXPIRY_DELTAS: [u32; 2] = [20, 40];
+/// Blocks a transaction may wait between creation and mining and still count as using a
+/// default expiry delta.
+pub const EXPIRY_SLACK: u32 = 10;
+/// Outputs (Sapling) or actions (Orchard) a bundle needs to follow the dummy-padding
+/// convention of the zcashd and librustzcash builders.
+pub const PADDED_MIN_OUTPUTS: u32 = 2;
+/// Issuance action, asset burn and asset type counts at or above this share the last
+/// histogram bucket ("3+").
+pub const ZSA_COUNT_CAP: u32 = 3;
+/// Labels of the ZSA count histograms (issuance actions, burns, asset types).
+pub const ZSA_COUNT_LABELS: [&str; 4] = ["0", "1", "2", "3+"];
+
+/// Network upgrades with a consensus branch id, oldest first: (branch, id, mainnet and
+/// testnet activation heights). NU7 is missing until its branch id and activation heights are
+/// set: v6 transactions are recognised by their version group, their branch id counts as
+/// "other", and they never lag.
+const UPGRADES: [(ConsensusBranch, u32, u32, u32); 8] = [
+    (ConsensusBranch::Overwinter, 0x5ba8_1b19, 347_500, 207_500),
+    (ConsensusBranch::Sapling, 0x76b8_09bb, 419_200, 280_000),
+    (ConsensusBranch::Blossom, 0x2bb4_0e60, 653_600, 584_000),
+    (ConsensusBranch::Heartwood, 0xf5b9_230b, 903_000, 903_800),
+    (ConsensusBranch::Canopy, 0xe9ff_75a6, 1_046_400, 1_028_500),
+    (ConsensusBranch::Nu5, 0xc2d6_d0b4, 1_687_104, 1_842_420),
+    (ConsensusBranch::Nu6, 0xc8e7_1055, 2_726_400, 2_976_000),
+    (ConsensusBranch::Nu6_1, 0x4dec_4df0, 3_146_400, 3_536_500),
+];
+
+/// Chain whose activation heights decide which consensus branch and version group are current.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Main,
+    Test,
+}
+
+/// How nLockTime is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockTimeKind {
+    #[default]
+    Zero,
+    Height,
+    Time,
+}
+
+/// How nExpiryHeight is set, relative to the height the transaction was mined at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryKind {
+    /// No expiry (0, or a pre-Overwinter transaction).
+    #[default]
+    None,
+    /// Mined height + 20, minus at most EXPIRY_SLACK blocks.
+    #[serde(rename = "default_20")]
+    Default20,
+    /// Mined height + 40, minus at most EXPIRY_SLACK blocks.
+    #[serde(rename = "default_40")]
+    Default40,
+    /// Any other expiry, or any expiry when the mined height is unknown.
+    Other,
+}
+
+/// nVersionGroupId of an overwintered transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionGroup {
+    /// Not overwintered (v1–v2), so no version group.
+    #[default]
+    None,
+    Overwinter,
+    Sapling,
+    Nu5,
+    /// v6 (NU7, draft ZIP 230).
+    Nu7,
+    Other,
+}
+
+/// nConsensusBranchId a transaction commits to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsensusBranch {
+    /// Not part of the encoding (before v5) or not reported by the node.
+    #[default]
+    Implicit,
+    Overwinter,
+    Sapling,
+    Blossom,
+    Heartwood,
+    Canopy,
+    Nu5,
+    Nu6,
+    #[serde(rename = "nu6_1")]
+    Nu6_1,
+    Other,
+}
+
+/// enableSpends / enableOutputs flags of an Orchard bundle (meaningless without actions).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrchardFlags {
+    #[default]
+    Both,
+    SpendsOnly,
+    OutputsOnly,
+    Disabled,
+}
+
+/// Which halves of a Sapling bundle are present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaplingBundle {
+    None,
+    SpendsOnly,
+    OutputsOnly,
+    Both,
+}
+
+/// Whether the shielded bundles of a transaction follow the dummy-padding convention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundlePadding {
+    /// No Sapling or Orchard bundle.
+    None,
+    /// Every present bundle has at least PADDED_MIN_OUTPUTS outputs / actions.
+    Padded,
+    Unpadded,
+}
+
+impl LockTimeKind {
+    pub const ALL: [LockTimeKind; 3] =
+        [LockTimeKind::Zero, LockTimeKind::Height, LockTimeKind::Time];
+
+    pub fn classify(lock_time: u32) -> LockTimeKind {
+        match lock_time {
+            0 => LockTimeKind::Zero,
+            t if t < LOCKTIME_THRESHOLD => LockTimeKind::Height,
+            _ => LockTimeKind::Time,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LockTimeKind::Zero => "zero",
+            LockTimeKind::Height => "height",
+            LockTimeKind::Time => "time",
+        }
+    }
+}
+
+impl ExpiryKind {
+    pub const ALL: [ExpiryKind; 4] = [
+        ExpiryKind::None,
+        ExpiryKind::Default20,
+        ExpiryKind::Default40,
+        ExpiryKind::Other,
+    ];
+
+    /// Classify `expiry_height` of a transaction mined at `block_height`.
+    pub fn classify(expiry_height: u32, block_height: Option<u32>) -> ExpiryKind {
+        if expiry_height == 0 {
+            return ExpiryKind::None;
+        }
+        let Some(height) = block_height else {
+            return ExpiryKind::Other;
+        };
+        let delta = expiry_height.checked_sub(height);
+        match DEFAULT_EXPIRY_DELTAS
+            .iter()
+            .position(|&d| delta.is_some_and(|x| x <= d && x + EXPIRY_SLACK >= d))
+        {
+            Some(0) => ExpiryKind::Default20,
+            Some(_) => ExpiryKind::Default40,
+            None => ExpiryKind::Other,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExpiryKind::None => "none",
+            ExpiryKind::Default20 => "default_20",
+            ExpiryKind::Default40 => "default_40",
+            ExpiryKind::Other => "other",
+        }
+    }
+}
+
+impl VersionGroup {
+    pub const ALL: [VersionGroup; 6] = [
+        VersionGroup::None,
+        VersionGroup::Overwinter,
+        VersionGroup::Sapling,
+        VersionGroup::Nu5,
+        VersionGroup::Nu7,
+        VersionGroup::Other,
+    ];
+
+    /// Classify a version group id; `None` for transactions that are not overwintered.
+    pub fn from_id(id: Option<u32>) -> VersionGroup {
+        match id {
+            None => VersionGroup::None,
+            Some(0x03c4_8270) => VersionGroup::Overwinter,
+            Some(0x892f_2085) => VersionGroup::Sapling,
+            Some(0x26a7_270a) => VersionGroup::Nu5,
+            Some(0x7777_7777) => VersionGroup::Nu7,
+            Some(_) => VersionGroup::Other,
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<VersionGroup> {
+        VersionGroup::ALL.into_iter().find(|g| g.label() == label)
+    }
+
+    /// Upgrade that introduced this version group, if its activation heights are known.
+    fn upgrade(self) -> Option<ConsensusBranch> {
+        match self {
+            VersionGroup::Overwinter => Some(ConsensusBranch::Overwinter),
+            VersionGroup::Sapling => Some(ConsensusBranch::Sapling),
+            VersionGroup::Nu5 => Some(ConsensusBranch::Nu5),
+            VersionGroup::None | VersionGroup::Nu7 | VersionGroup::Other => None,
+        }
+    }
+
+    /// Whether a transaction of this group mined at `height` lags behind: a newer version
+    /// group was already active there, so it was built by a wallet that kept the older
+    /// transaction version (e.g. v4 after NU5). Unlike branch ids, which consensus pins to the
+    /// active upgrade, this is observable in mined blocks.
+    pub fn lags_at(self, height: u32, network: Network) -> bool {
+        let Some(own) = self.upgrade().and_then(|u| u.activation_height(network)) else {
+            return false;
+        };
+        VersionGroup::ALL
+            .into_iter()
+            .filter_map(|g| g.upgrade()?.activation_height(network))
+            .any(|h| h > own && h <= height)
+    }
+
+    /// Version group id of a known group.
+    pub fn id(self) -> Option<u32> {
+        match self {
+            VersionGroup::Overwinter => Some(0x03c4_8270),
+            VersionGroup::Sapling => Some(0x892f_2085),
+            VersionGroup::Nu5 => Some(0x26a7_270a),
+            VersionGroup::Nu7 => Some(0x7777_7777),
+            VersionGroup::None | VersionGroup::Other => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VersionGroup::None => "none",
+            VersionGroup::Overwinter => "overwinter",
+            VersionGroup::Sapling => "sapling",
+            VersionGroup::Nu5 => "nu5",
+            VersionGroup::Nu7 => "nu7",
+            VersionGroup::Other => "other",
+        }
+    }
+}
+
+impl ConsensusBranch {
+    pub const ALL: [ConsensusBranch; 10] = [
+        ConsensusBranch::Implicit,
+        ConsensusBranch::Overwinter,
+        ConsensusBranch::Sapling,
+        ConsensusBranch::Blossom,
+        ConsensusBranch::Heartwood,
+        ConsensusBranch::Canopy,
+        ConsensusBranch::Nu5,
+        ConsensusBranch::Nu6,
+        ConsensusBranch::Nu6_1,
+        ConsensusBranch::Other,
+    ];
+
+    /// Classify an explicit branch id; `None` when the transaction carries none.
+    pub fn from_id(id: Option<u32>) -> ConsensusBranch {
+        match id {
+            None => ConsensusBranch::Implicit,
+            Some(id) => UPGRADES
+                .iter()
+                .find(|u| u.1 == id)
+                .map_or(ConsensusBranch::Other, |u| u.0),
+        }
+    }
+
+    /// Branch id of a known upgrade.
+    pub fn id(self) -> Option<u32> {
+        UPGRADES.iter().find(|u| u.0 == self).map(|u| u.1)
+    }
+
+    /// Branch of the latest upgrade active at `height` (`Implicit` before Overwinter).
+    pub fn active_at(height: u32, network: Network) -> ConsensusBranch {
+        UPGRADES
+            .iter()
+            .rev()
+            .find(|u| match network {
+                Network::Main => height >= u.2,
+                Network::Test => height >= u.3,
+            })
+            .map_or(ConsensusBranch::Implicit, |u| u.0)
+    }
+
+    /// Activation height of this upgrade on `network`.
+    pub fn activation_height(self, network: Network) -> Option<u32> {
+        UPGRADES
+            .iter()
+            .find(|u| u.0 == self)
+            .map(|u| match network {
+                Network::Main => u.2,
+                Network::Test => u.3,
+            })
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ConsensusBranch::Implicit => "implicit",
+            ConsensusBranch::Overwinter => "overwinter",
+            ConsensusBranch::Sapling => "sapling",
+            ConsensusBranch::Blossom => "blossom",
+            ConsensusBranch::Heartwood => "heartwood",
+            ConsensusBranch::Canopy => "canopy",
+            ConsensusBranch::Nu5 => "nu5",
+            ConsensusBranch::Nu6 => "nu6",
+            ConsensusBranch::Nu6_1 => "nu6_1",
+            ConsensusBranch::Other => "other",
+        }
+    }
+}
+
+/// Label of a ZSA count bucket (see ZSA_COUNT_LABELS).
+pub fn zsa_count_label(n: u32) -> &'static str {
+    ZSA_COUNT_LABELS[n.min(ZSA_COUNT_CAP) as usize]
+}
+
+impl OrchardFlags {
+    /// Labels of the "orchard_flags" histogram: "none" for transactions without actions,
+    /// then the flag combinations.
+    pub const LABELS: [&'static str; 5] =
+        ["none", "both", "spends_only", "outputs_only", "disabled"];
+
+    pub fn from_bits(flags: u8) -> OrchardFlags {
+        match (flags & 1 != 0, flags & 2 != 0) {
+            (true, true) => OrchardFlags::Both,
+            (true, false) => OrchardFlags::SpendsOnly,
+            (false, true) => OrchardFlags::OutputsOnly,
+            (false, false) => OrchardFlags::Disabled,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OrchardFlags::Both => "both",
+            OrchardFlags::SpendsOnly => "spends_only",
+            OrchardFlags::OutputsOnly => "outputs_only",
+            OrchardFlags::Disabled => "disabled",
+        }
+    }
+}
+
+impl SaplingBundle {
+    pub const ALL: [SaplingBundle; 4] = [
+        SaplingBundle::None,
+        SaplingBundle::SpendsOnly,
+        SaplingBundle::OutputsOnly,
+        SaplingBundle::Both,
+    ];
+
+    pub fn classify(n_spend: u32, n_output: u32) -> SaplingBundle {
+        match (n_spend > 0, n_output > 0) {
+            (false, false) => SaplingBundle::None,
+            (true, false) => SaplingBundle::SpendsOnly,
+            (false, true) => SaplingBundle::OutputsOnly,
+            (true, true) => SaplingBundle::Both,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SaplingBundle::None => "none",
+            SaplingBundle::SpendsOnly => "spends_only",
+            SaplingBundle::OutputsOnly => "outputs_only",
+            SaplingBundle::Both => "both",
+        }
+    }
+}
+
+impl BundlePadding {
+    pub const ALL: [BundlePadding; 3] = [
+        BundlePadding::None,
+        BundlePadding::Padded,
+        BundlePadding::Unpadded,
+    ];
+
+    /// Classify from Sapling spend/output and Orchard action counts.
+    pub fn classify(n_spend: u32, n_output: u32, n_action: u32) -> BundlePadding {
+        let sapling = n_spend + n_output > 0;
+        if !sapling && n_action == 0 {
+            return BundlePadding::None;
+        }
+        let sapling_ok = !sapling || n_output >= PADDED_MIN_OUTPUTS;
+        let orchard_ok = n_action == 0 || n_action >= PADDED_MIN_OUTPUTS;
+        if sapling_ok && orchard_ok {
+            BundlePadding::Padded
+        } else {
+            BundlePadding::Unpadded
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BundlePadding::None => "none",
+            BundlePadding::Padded => "padded",
+            BundlePadding::Unpadded => "unpadded",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(LockTimeKind::classify(0), LockTimeKind::Zero);
+        assert_eq!(LockTimeKind::classify(2_000_000), LockTimeKind::Height);
+        assert_eq!(LockTimeKind::classify(1_700_000_000), LockTimeKind::Time);
+
+        assert_eq!(ExpiryKind::classify(0, Some(100)), ExpiryKind::None);
+        assert_eq!(ExpiryKind::classify(140, Some(100)), ExpiryKind::Default40);
+        assert_eq!(ExpiryKind::classify(135, Some(105)), ExpiryKind::Default40);
+        assert_eq!(ExpiryKind::classify(120, Some(100)), ExpiryKind::Default20);
+        assert_eq!(ExpiryKind::classify(115, Some(100)), ExpiryKind::Default20);
+        assert_eq!(ExpiryKind::classify(125, Some(100)), ExpiryKind::Other);
+        assert_eq!(ExpiryKind::classify(90, Some(100)), ExpiryKind::Other);
+        assert_eq!(ExpiryKind::classify(140, None), ExpiryKind::Other);
+        assert_eq!(
+            serde_json::to_string(&ExpiryKind::Default40).unwrap(),
+            format!("\"{}\"", ExpiryKind::Default40.label())
+        );
+    }
+
+    #[test]
+    fn test_bundle_categories() {
+        assert_eq!(OrchardFlags::from_bits(3), OrchardFlags::Both);
+        assert_eq!(OrchardFlags::from_bits(2), OrchardFlags::OutputsOnly);
+        assert_eq!(OrchardFlags::from_bits(0), OrchardFlags::Disabled);
+        assert_eq!(SaplingBundle::classify(1, 0), SaplingBundle::SpendsOnly);
+        assert_eq!(SaplingBundle::classify(0, 0), SaplingBundle::None);
+        assert_eq!(BundlePadding::classify(0, 0, 0), BundlePadding::None);
+        assert_eq!(BundlePadding::classify(1, 2, 0), BundlePadding::Padded);
+        assert_eq!(BundlePadding::classify(1, 1, 0), BundlePadding::Unpadded);
+        // A spends-only Sapling bundle has no outputs to pad.
+        assert_eq!(BundlePadding::classify(1, 0, 2), BundlePadding::Unpadded);
+        assert_eq!(BundlePadding::classify(0, 0, 2), BundlePadding::Padded);
+        assert_eq!(BundlePadding::classify(0, 0, 1), BundlePadding::Unpadded);
+    }
+
+    #[test]
+    fn test_version_group_and_branch() {
+        assert_eq!(VersionGroup::from_id(None), VersionGroup::None);
+        assert_eq!(
+            VersionGroup::from_id(Some(0x892f_2085)),
+            VersionGroup::Sapling
+        );
+        assert_eq!(VersionGroup::from_id(Some(1)), VersionGroup::Other);
+        assert_eq!(zsa_count_label(0), "0");
+        assert_eq!(zsa_count_label(7), "3+");
+        for g in VersionGroup::ALL.into_iter().filter(|g| g.id().is_some()) {
+            assert_eq!(VersionGroup::from_id(g.id()), g);
+        }
+        assert_eq!(ConsensusBranch::from_id(None), ConsensusBranch::Implicit);
+        assert_eq!(
+            ConsensusBranch::from_id(Some(0xc2d6_d0b4)),
+            ConsensusBranch::Nu5
+        );
+        assert_eq!(ConsensusBranch::from_id(Some(0)), ConsensusBranch::Other);
+
+        assert_eq!(
+            ConsensusBranch::active_at(1, Network::Main),
+            ConsensusBranch::Implicit
+        );
+        assert_eq!(
+            ConsensusBranch::active_at(1_687_104, Network::Main),
+            ConsensusBranch::Nu5
+        );
+        assert_eq!(
+            ConsensusBranch::active_at(1_687_104, Network::Test),
+            ConsensusBranch::Canopy
+        );
+        assert_eq!(
+            ConsensusBranch::Nu5.activation_height(Network::Test),
+            Some(1_842_420)
+        );
+        assert_eq!(
+            ConsensusBranch::Other.activation_height(Network::Main),
+            None
+        );
+
+        assert!(VersionGroup::Sapling.lags_at(1_687_104, Network::Main));
+        assert!(!VersionGroup::Sapling.lags_at(1_687_103, Network::Main));
+        assert!(!VersionGroup::Sapling.lags_at(1_687_104, Network::Test));
+        assert!(VersionGroup::Overwinter.lags_at(419_200, Network::Main));
+        assert!(!VersionGroup::Nu5.lags_at(3_000_000, Network::Main));
+        assert!(!VersionGroup::None.lags_at(3_000_000, Network::Main));
+        assert_eq!(VersionGroup::from_label("nu5"), Some(VersionGroup::Nu5));
+        assert_eq!(
+            serde_json::to_string(&ConsensusBranch::Nu6_1).unwrap(),
+            format!("\"{}\"", ConsensusBranch::Nu6_1.label())
+        );
+    }
+}
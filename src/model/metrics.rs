@@ -0,0 +1,131 @@
+//! Diversity metrics over shape histograms.
+
+use serde::Serialize;
+
+/// Number of most common buckets summed for the top-k concentration metric.
+pub const TOP_K: usize = 3;
+
+/// Diversity metrics for one histogram (counts per bucket).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DiversityMetrics {
+    /// Total count over all buckets.
+    pub total: u64,
+    /// Number of buckets with a non-zero count.
+    pub distinct: usize,
+    /// Shannon entropy in bits.
+    pub shannon: f64,
+    /// Shannon entropy divided by log2 of the number of non-empty buckets (Pielou evenness,
+    /// 0..=1), so fixed-label and open-keyed histograms are comparable.
+    pub normalized_entropy: f64,
+    /// Rényi entropy of order 2 (collision entropy) in bits.
+    pub renyi2: f64,
+    /// Min-entropy (Rényi order ∞) in bits: -log2 of the largest share.
+    pub min_entropy: f64,
+    /// Gini–Simpson index: probability that two random txs fall in different buckets.
+    pub gini_simpson: f64,
+    /// Effective number of shapes (Hill number of order 1, 2^H).
+    pub effective_shapes: f64,
+    /// Share of transactions whose bucket occurs exactly once.
+    pub singleton_fraction: f64,
+    /// Share of the largest bucket.
+    pub top1_share: f64,
+    /// Combined share of the TOP_K largest buckets.
+    pub top_k_share: f64,
+}
+
+impl DiversityMetrics {
+    /// Compute all metrics for a distribution given as counts per bucket.
+    /// Zero-count buckets do not affect any metric.
+    pub fn from_counts(counts: &[u64]) -> Self {
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return DiversityMetrics::default();
+        }
+        let shannon = crate::util::entropy(counts);
+        let distinct = counts.iter().filter(|&&c| c > 0).count();
+        let normalized_entropy = if distinct > 1 {
+            shannon / (distinct as f64).log2()
+        } else {
+            0.0
+        };
+        let collision = collision_probability(counts);
+        DiversityMetrics {
+            total,
+            distinct,
+            shannon,
+            normalized_entropy,
+            renyi2: -collision.log2(),
+            min_entropy: -top_k_share(counts, 1).log2(),
+            gini_simpson: 1.0 - collision,
+            effective_shapes: shannon.exp2(),
+            singleton_fraction: counts.iter().filter(|&&c| c == 1).count() as f64 / total as f64,
+            top1_share: top_k_share(counts, 1),
+            top_k_share: top_k_share(counts, TOP_K),
+        }
+    }
+}
+
+/// Probability that two draws (with replacement) land in the same bucket: Σ p_i².
+pub fn collision_probability(counts: &[u64]) -> f64 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let n = total as f64;
+    counts
+        .iter()
+        .map(|&c| {
+            let p = c as f64 / n;
+            p * p
+        })
+        .sum()
+}
+
+/// Combined share of the `k` largest buckets. Returns 0 if total is 0.
+pub fn top_k_share(counts: &[u64], k: usize) -> f64 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let mut sorted = counts.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    sorted.iter().take(k).sum::<u64>() as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniform_metrics() {
+        let m = DiversityMetrics::from_counts(&[5, 5, 5, 5]);
+        assert_eq!(m.distinct, 4);
+        assert!((m.shannon - 2.0).abs() < 1e-10);
+        assert!((m.normalized_entropy - 1.0).abs() < 1e-10);
+        assert!((m.renyi2 - 2.0).abs() < 1e-10);
+        assert!((m.min_entropy - 2.0).abs() < 1e-10);
+        assert!((m.gini_simpson - 0.75).abs() < 1e-10);
+        assert!((m.effective_shapes - 4.0).abs() < 1e-10);
+        assert!((m.top_k_share - 0.75).abs() < 1e-10);
+        assert_eq!(m.singleton_fraction, 0.0);
+    }
+
+    #[test]
+    fn test_skewed_metrics() {
+        let m = DiversityMetrics::from_counts(&[8, 1, 1, 0]);
+        assert_eq!(m.total, 10);
+        assert_eq!(m.distinct, 3);
+        assert!((m.top1_share - 0.8).abs() < 1e-10);
+        assert!((m.singleton_fraction - 0.2).abs() < 1e-10);
+        assert!(m.min_entropy <= m.renyi2 && m.renyi2 <= m.shannon);
+        // Empty buckets (fixed labels) normalize like absent keys (open histograms).
+        assert_eq!(
+            m.normalized_entropy,
+            DiversityMetrics::from_counts(&[8, 1, 1]).normalized_entropy
+        );
+        assert_eq!(
+            DiversityMetrics::from_counts(&[]),
+            DiversityMetrics::default()
+        );
+    }
+}
@@ -2,8 +2,36 @@
 
 use serde::{Deserialize, Serialize};
 
+pub mod baseline;
+pub mod block;
+pub mod changepoint;
+pub mod divergence;
+pub mod entropy;
+pub mod flood;
+pub mod format;
+pub mod metrics;
+pub mod mutual_info;
+pub mod padding;
+pub mod score;
+pub mod significance;
+
+use entropy::EntropyEstimate;
+use format::{
+    zsa_count_label, BundlePadding, ConsensusBranch, ExpiryKind, LockTimeKind, OrchardFlags,
+    SaplingBundle, VersionGroup,
+};
+use metrics::DiversityMetrics;
+
+/// ZIP-317 grace actions: transactions with at most this many logical actions pay the
+/// minimum conventional fee.
+pub const ZIP317_GRACE_ACTIONS: u32 = 2;
+/// Logical-action counts at or above this share the last histogram bucket ("16+").
+pub const LOGICAL_ACTIONS_CAP: u32 = 16;
+/// Histograms counting transparent inputs or outputs rather than transactions.
+const NON_TX_HISTOGRAMS: [&str; 2] = ["script_out", "script_in"];
+
 /// Single transaction shape (metadata only; no addresses or values).
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TxShape {
     /// Number of transparent inputs.
     pub n_vin: u32,
@@ -21,6 +49,79 @@ pub struct TxShape {
     pub size_bucket: u8,
     /// Transaction version (1–6).
     pub version: u32,
+    /// Coinbase transaction (its single input spends no previous output). Not part of the
+    /// tuple key; coinbase shapes are kept out of user statistics and stored on their own.
+    #[serde(default)]
+    pub coinbase: bool,
+    /// Transparent script types (never the scripts themselves). Not part of the tuple key.
+    #[serde(default)]
+    pub scripts: ScriptCounts,
+    /// nLockTime category. Not part of the tuple key.
+    #[serde(default)]
+    pub locktime: LockTimeKind,
+    /// nExpiryHeight category relative to the mined height. Not part of the tuple key.
+    #[serde(default)]
+    pub expiry: ExpiryKind,
+    /// Orchard bundle flags (only meaningful with actions). Not part of the tuple key.
+    #[serde(default)]
+    pub orchard_flags: OrchardFlags,
+    /// nVersionGroupId category. Not part of the tuple key.
+    #[serde(default)]
+    pub version_group: VersionGroup,
+    /// nConsensusBranchId category. Not part of the tuple key.
+    #[serde(default)]
+    pub branch: ConsensusBranch,
+    /// Number of ZSA issuance actions (v6+). Not part of the tuple key.
+    #[serde(default)]
+    pub n_issue_action: u32,
+    /// Number of ZSA asset burns in the Orchard bundle (v6+). Not part of the tuple key.
+    #[serde(default)]
+    pub n_burn: u32,
+    /// Distinct asset types issued or burned (v6+). Not part of the tuple key.
+    #[serde(default)]
+    pub asset_types: u32,
+}
+
+/// Transparent outputs and inputs per script type. Outputs or inputs whose script is not
+/// available are not counted; the coinbase input is never counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptCounts {
+    pub out_p2pkh: u32,
+    pub out_p2sh: u32,
+    pub out_op_return: u32,
+    /// Any other output script (bare pubkey, bare multisig, nonstandard).
+    pub out_nonstandard: u32,
+    /// scriptSig of a signature and a public key.
+    pub in_p2pkh: u32,
+    /// P2SH redeem of an m-of-n multisig script.
+    pub in_p2sh_multisig: u32,
+    /// P2SH redeem of any other script.
+    pub in_p2sh: u32,
+    /// Any other scriptSig (e.g. a bare signature spending P2PK).
+    pub in_other: u32,
+}
+
+impl ScriptCounts {
+    /// Output counts labeled as in the "script_out" histogram.
+    pub fn outputs(&self) -> [(&'static str, u32); 4] {
+        [
+            ("p2pkh", self.out_p2pkh),
+            ("p2sh", self.out_p2sh),
+            ("op_return", self.out_op_return),
+            ("nonstandard", self.out_nonstandard),
+        ]
+    }
+
+    /// Input counts labeled as in the "script_in" histogram.
+    pub fn inputs(&self) -> [(&'static str, u32); 4] {
+        [
+            ("p2pkh", self.in_p2pkh),
+            ("p2sh_multisig", self.in_p2sh_multisig),
+            ("p2sh", self.in_p2sh),
+            ("other", self.in_other),
+        ]
+    }
 }
 
 impl TxShape {
@@ -36,6 +137,96 @@ impl TxShape {
             || self.n_sapling_output > 0
             || self.n_orchard_action > 0
     }
+
+    /// ZIP-317 logical actions. Transparent sizes are not part of the shape, so the
+    /// transparent term counts inputs and outputs as standard P2PKH-sized (one action each).
+    pub fn logical_actions(&self) -> u32 {
+        self.n_vin.max(self.n_vout)
+            + 2 * self.n_joinsplit
+            + self.n_sapling_spend.max(self.n_sapling_output)
+            + self.n_orchard_action
+    }
+
+    /// Full shape tuple as a histogram key:
+    /// "vin_vout_joinsplit_saplingspend_saplingoutput_orchardaction_sizebucket_version".
+    pub fn tuple_key(&self) -> String {
+        format!(
+            "{}_{}_{}_{}_{}_{}_{}_{}",
+            self.n_vin,
+            self.n_vout,
+            self.n_joinsplit,
+            self.n_sapling_spend,
+            self.n_sapling_output,
+            self.n_orchard_action,
+            self.size_bucket,
+            self.version
+        )
+    }
+
+    /// Coinbase histogram key: "miner_funding_saplingoutput_orchardaction_version". Funding
+    /// outputs are the P2SH transparent outputs, which is how the founders' reward and the
+    /// funding streams are paid; the miner outputs are the other transparent outputs.
+    /// Shielded coinbase (post-Heartwood) shows up as Sapling outputs or Orchard actions.
+    pub fn coinbase_key(&self) -> String {
+        let funding = self.scripts.out_p2sh.min(self.n_vout);
+        format!(
+            "{}_{}_{}_{}_{}",
+            self.n_vout - funding,
+            funding,
+            self.n_sapling_output,
+            self.n_orchard_action,
+            self.version
+        )
+    }
+
+    /// "orchard_flags" histogram label: "none" without Orchard actions, else the flags.
+    pub fn orchard_flags_label(&self) -> &'static str {
+        if self.n_orchard_action == 0 {
+            "none"
+        } else {
+            self.orchard_flags.label()
+        }
+    }
+
+    pub fn sapling_bundle(&self) -> SaplingBundle {
+        SaplingBundle::classify(self.n_sapling_spend, self.n_sapling_output)
+    }
+
+    pub fn bundle_padding(&self) -> BundlePadding {
+        BundlePadding::classify(
+            self.n_sapling_spend,
+            self.n_sapling_output,
+            self.n_orchard_action,
+        )
+    }
+
+    /// Inverse of [`TxShape::tuple_key`]; `None` if the key is malformed.
+    pub fn from_tuple_key(key: &str) -> Option<TxShape> {
+        let f: Vec<&str> = key.split('_').collect();
+        if f.len() != 8 {
+            return None;
+        }
+        Some(TxShape {
+            n_vin: f[0].parse().ok()?,
+            n_vout: f[1].parse().ok()?,
+            n_joinsplit: f[2].parse().ok()?,
+            n_sapling_spend: f[3].parse().ok()?,
+            n_sapling_output: f[4].parse().ok()?,
+            n_orchard_action: f[5].parse().ok()?,
+            size_bucket: f[6].parse().ok()?,
+            version: f[7].parse().ok()?,
+            coinbase: false,
+            scripts: ScriptCounts::default(),
+            locktime: LockTimeKind::default(),
+            expiry: ExpiryKind::default(),
+            orchard_flags: OrchardFlags::default(),
+            version_group: VersionGroup::default(),
+            branch: ConsensusBranch::default(),
+            n_issue_action: 0,
+            n_burn: 0,
+            asset_types: 0,
+        })
+    }
 }
 
 /// Aggregate shape statistics for a block or range.
@@ -53,20 +244,103 @@ pub struct ShapeStats {
     pub with_transparent: u64,
     /// Count of txs with shielded component.
     pub with_shielded: u64,
-    /// Shannon entropy of size_bucket distribution.
+    /// Shannon entropy of size_bucket distribution, Miller–Madow corrected (bits), so that
+    /// low-traffic blocks are not biased low. Always recomputed from `size_bucket_hist`.
     pub size_entropy: f64,
+    /// Histogram: count per full shape tuple; key = TxShape::tuple_key.
+    #[serde(default)]
+    pub shape_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per ZIP-317 logical-action count (uncapped).
+    #[serde(default)]
+    pub logical_actions_hist: std::collections::HashMap<u32, u64>,
+    /// Histogram: coinbase transactions per coinbase shape; key = TxShape::coinbase_key.
+    /// Coinbase txs are never in the user statistics above; see `from_coinbase_shapes`.
+    /// Not a view in `histograms()`.
+    #[serde(default)]
+    pub coinbase_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: transparent outputs per script type (see ScriptCounts::outputs).
+    #[serde(default)]
+    pub script_out_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: transparent inputs per script type (see ScriptCounts::inputs).
+    #[serde(default)]
+    pub script_in_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per nLockTime category (see LockTimeKind::label).
+    #[serde(default)]
+    pub locktime_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per nExpiryHeight category (see ExpiryKind::label).
+    #[serde(default)]
+    pub expiry_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per Orchard flag combination (see OrchardFlags::LABELS).
+    #[serde(default)]
+    pub orchard_flags_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per Sapling bundle composition (see SaplingBundle::label).
+    #[serde(default)]
+    pub sapling_bundle_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per dummy-padding conformance (see BundlePadding::label).
+    #[serde(default)]
+    pub bundle_padding_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per version group (see VersionGroup::label).
+    #[serde(default)]
+    pub version_group_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per consensus branch id (see ConsensusBranch::label).
+    #[serde(default)]
+    pub branch_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per ZSA issuance action count (see format::ZSA_COUNT_LABELS). The ZSA
+    /// histograms are recorded but are not views in `histograms()` until NU7 activates, as the
+    /// v6 format is still a draft.
+    #[serde(default)]
+    pub issuance_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per ZSA asset burn count.
+    #[serde(default)]
+    pub burn_hist: std::collections::HashMap<String, u64>,
+    /// Histogram: count per number of distinct ZSA asset types.
+    #[serde(default)]
+    pub asset_types_hist: std::collections::HashMap<String, u64>,
 }
 
 impl ShapeStats {
+    /// Stats of user transactions; coinbase shapes only go to `coinbase_hist`.
     pub fn from_shapes(shapes: &[TxShape]) -> Self {
-        let n_txs = shapes.len() as u64;
+        Self::build(shapes, false)
+    }
+
+    /// Stats of the coinbase transactions alone, counted like user transactions (with an
+    /// empty `coinbase_hist`), so reports can merge them into user stats on request.
+    pub fn from_coinbase_shapes(shapes: &[TxShape]) -> Self {
+        Self::build(shapes, true)
+    }
+
+    fn build(shapes: &[TxShape], coinbase: bool) -> Self {
+        let mut n_txs = 0u64;
         let mut vin_vout_hist = std::collections::HashMap::new();
         let mut size_bucket_hist = [0u64; 6];
         let mut version_hist = std::collections::HashMap::new();
         let mut with_transparent = 0u64;
         let mut with_shielded = 0u64;
+        let mut shape_hist = std::collections::HashMap::new();
+        let mut logical_actions_hist = std::collections::HashMap::new();
+        let mut coinbase_hist = std::collections::HashMap::new();
+        let mut script_out_hist = std::collections::HashMap::new();
+        let mut script_in_hist = std::collections::HashMap::new();
+        let mut locktime_hist = std::collections::HashMap::new();
+        let mut expiry_hist = std::collections::HashMap::new();
+        let mut orchard_flags_hist = std::collections::HashMap::new();
+        let mut sapling_bundle_hist = std::collections::HashMap::new();
+        let mut bundle_padding_hist = std::collections::HashMap::new();
+        let mut version_group_hist = std::collections::HashMap::new();
+        let mut branch_hist = std::collections::HashMap::new();
+        let mut issuance_hist = std::collections::HashMap::new();
+        let mut burn_hist = std::collections::HashMap::new();
+        let mut asset_types_hist = std::collections::HashMap::new();
 
         for s in shapes {
+            if s.coinbase != coinbase {
+                if s.coinbase {
+                    *coinbase_hist.entry(s.coinbase_key()).or_insert(0) += 1;
+                }
+                continue;
+            }
+            n_txs += 1;
             let key = format!("{}_{}", s.n_vin, s.n_vout);
             *vin_vout_hist.entry(key).or_insert(0) += 1;
             if s.size_bucket < 6 {
@@ -79,9 +353,47 @@ impl ShapeStats {
             if s.has_shielded() {
                 with_shielded += 1;
             }
+            *shape_hist.entry(s.tuple_key()).or_insert(0) += 1;
+            *logical_actions_hist.entry(s.logical_actions()).or_insert(0) += 1;
+            for (label, n) in s.scripts.outputs() {
+                if n > 0 {
+                    *script_out_hist.entry(label.to_string()).or_insert(0) += n as u64;
+                }
+            }
+            for (label, n) in s.scripts.inputs() {
+                if n > 0 {
+                    *script_in_hist.entry(label.to_string()).or_insert(0) += n as u64;
+                }
+            }
+            *locktime_hist
+                .entry(s.locktime.label().to_string())
+                .or_insert(0) += 1;
+            *expiry_hist.entry(s.expiry.label().to_string()).or_insert(0) += 1;
+            *orchard_flags_hist
+                .entry(s.orchard_flags_label().to_string())
+                .or_insert(0) += 1;
+            *sapling_bundle_hist
+                .entry(s.sapling_bundle().label().to_string())
+                .or_insert(0) += 1;
+            *bundle_padding_hist
+                .entry(s.bundle_padding().label().to_string())
+                .or_insert(0) += 1;
+            *version_group_hist
+                .entry(s.version_group.label().to_string())
+                .or_insert(0) += 1;
+            *branch_hist.entry(s.branch.label().to_string()).or_insert(0) += 1;
+            *issuance_hist
+                .entry(zsa_count_label(s.n_issue_action).to_string())
+                .or_insert(0) += 1;
+            *burn_hist
+                .entry(zsa_count_label(s.n_burn).to_string())
+                .or_insert(0) += 1;
+            *asset_types_hist
+                .entry(zsa_count_label(s.asset_types).to_string())
+                .or_insert(0) += 1;
         }
 
-        let size_entropy = crate::util::entropy(&size_bucket_hist);
+        let size_entropy = entropy::miller_madow(&size_bucket_hist);
 
         ShapeStats {
             n_txs,
@@ -91,8 +403,374 @@ impl ShapeStats {
             with_transparent,
             with_shielded,
             size_entropy,
+            shape_hist,
+            logical_actions_hist,
+            coinbase_hist,
+            script_out_hist,
+            script_in_hist,
+            locktime_hist,
+            expiry_hist,
+            orchard_flags_hist,
+            sapling_bundle_hist,
+            bundle_padding_hist,
+            version_group_hist,
+            branch_hist,
+            issuance_hist,
+            burn_hist,
+            asset_types_hist,
         }
     }
+
+    /// Add `other`'s counts (e.g. of another block) into these stats and recompute derived
+    /// metrics, so that merging per-block stats equals building from all their shapes. Counts
+    /// saturate at `u64::MAX` rather than overflow, so merging never fails.
+    pub fn merge(&mut self, other: &ShapeStats) {
+        // Always `Some`: saturating addition cannot fail.
+        let _ = self.combine(other, |a, b| Some(a.saturating_add(b)));
+    }
+
+    /// These stats minus `other`'s counts, with derived metrics recomputed; exact when `other`
+    /// was merged into these stats (e.g. a sub-range). `None` when `other` has a count these
+    /// stats do not cover, i.e. it is not part of them.
+    pub fn diff(&self, other: &ShapeStats) -> Option<ShapeStats> {
+        let mut out = self.clone();
+        out.combine(other, u64::checked_sub)?;
+        Some(out)
+    }
+
+    /// Apply `op` to every count of `self` and `other`, drop empty buckets and recompute
+    /// `size_entropy`. `None` as soon as `op` does; `self` is then partly combined.
+    fn combine(&mut self, other: &ShapeStats, op: fn(u64, u64) -> Option<u64>) -> Option<()> {
+        // Exhaustive, so that a new field cannot be left out of merging.
+        let ShapeStats {
+            n_txs,
+            vin_vout_hist,
+            size_bucket_hist,
+            version_hist,
+            with_transparent,
+            with_shielded,
+            size_entropy: _,
+            shape_hist,
+            logical_actions_hist,
+            coinbase_hist,
+            script_out_hist,
+            script_in_hist,
+            locktime_hist,
+            expiry_hist,
+            orchard_flags_hist,
+            sapling_bundle_hist,
+            bundle_padding_hist,
+            version_group_hist,
+            branch_hist,
+            issuance_hist,
+            burn_hist,
+            asset_types_hist,
+        } = other;
+        self.n_txs = op(self.n_txs, *n_txs)?;
+        self.with_transparent = op(self.with_transparent, *with_transparent)?;
+        self.with_shielded = op(self.with_shielded, *with_shielded)?;
+        for (a, &b) in self.size_bucket_hist.iter_mut().zip(size_bucket_hist) {
+            *a = op(*a, b)?;
+        }
+        combine_hist(&mut self.vin_vout_hist, vin_vout_hist, op)?;
+        combine_hist(&mut self.version_hist, version_hist, op)?;
+        combine_hist(&mut self.shape_hist, shape_hist, op)?;
+        combine_hist(&mut self.logical_actions_hist, logical_actions_hist, op)?;
+        combine_hist(&mut self.coinbase_hist, coinbase_hist, op)?;
+        combine_hist(&mut self.script_out_hist, script_out_hist, op)?;
+        combine_hist(&mut self.script_in_hist, script_in_hist, op)?;
+        combine_hist(&mut self.locktime_hist, locktime_hist, op)?;
+        combine_hist(&mut self.expiry_hist, expiry_hist, op)?;
+        combine_hist(&mut self.orchard_flags_hist, orchard_flags_hist, op)?;
+        combine_hist(&mut self.sapling_bundle_hist, sapling_bundle_hist, op)?;
+        combine_hist(&mut self.bundle_padding_hist, bundle_padding_hist, op)?;
+        combine_hist(&mut self.version_group_hist, version_group_hist, op)?;
+        combine_hist(&mut self.branch_hist, branch_hist, op)?;
+        combine_hist(&mut self.issuance_hist, issuance_hist, op)?;
+        combine_hist(&mut self.burn_hist, burn_hist, op)?;
+        combine_hist(&mut self.asset_types_hist, asset_types_hist, op)?;
+        self.size_entropy = entropy::miller_madow(&self.size_bucket_hist);
+        Some(())
+    }
+
+    /// Coinbase transactions seen (one per block).
+    pub fn coinbase_txs(&self) -> u64 {
+        self.coinbase_hist.values().sum()
+    }
+
+    /// Transactions within the ZIP-317 grace actions (paying the minimum fee).
+    pub fn grace_action_txs(&self) -> u64 {
+        self.logical_actions_hist
+            .iter()
+            .filter(|(&k, _)| k <= ZIP317_GRACE_ACTIONS)
+            .map(|(_, &v)| v)
+            .sum()
+    }
+
+    /// Transactions with a recorded logical-action count. Blocks stored before the histogram
+    /// existed have transactions but no counts, so this can be below `n_txs`.
+    pub fn logical_actions_txs(&self) -> u64 {
+        self.logical_actions_hist.values().sum()
+    }
+
+    /// Share of the transactions with a recorded logical-action count that are within the
+    /// ZIP-317 grace actions; `None` when no transaction has one.
+    pub fn grace_action_share(&self) -> Option<f64> {
+        match self.logical_actions_txs() {
+            0 => None,
+            n => Some(self.grace_action_txs() as f64 / n as f64),
+        }
+    }
+
+    /// Per-transaction histograms recorded for fewer than `n_txs` transactions, with the
+    /// number of transactions missing from each (blocks stored before the histogram existed).
+    pub fn uncovered_txs(&self) -> Vec<(&'static str, u64)> {
+        self.histograms()
+            .iter()
+            .filter(|h| !NON_TX_HISTOGRAMS.contains(&h.name))
+            .filter_map(|h| {
+                let missing = self.n_txs.saturating_sub(h.total());
+                (missing > 0).then_some((h.name, missing))
+            })
+            .collect()
+    }
+
+    /// All histograms as named views with buckets in a stable order.
+    pub fn histograms(&self) -> Vec<Histogram> {
+        let size_bucket = self
+            .size_bucket_hist
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| (i.to_string(), v))
+            .collect();
+        let mut version: Vec<(u32, u64)> =
+            self.version_hist.iter().map(|(&k, &v)| (k, v)).collect();
+        version.sort_unstable();
+        let mut logical_actions = vec![0u64; LOGICAL_ACTIONS_CAP as usize + 1];
+        for (&k, &v) in &self.logical_actions_hist {
+            logical_actions[k.min(LOGICAL_ACTIONS_CAP) as usize] += v;
+        }
+        vec![
+            Histogram {
+                name: "vin_vout",
+                ordered: false,
+                buckets: sorted_buckets(&self.vin_vout_hist),
+            },
+            Histogram {
+                name: "size_bucket",
+                ordered: true,
+                buckets: size_bucket,
+            },
+            Histogram {
+                name: "version",
+                ordered: false,
+                buckets: version
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+            },
+            Histogram {
+                name: "shape",
+                ordered: false,
+                buckets: sorted_buckets(&self.shape_hist),
+            },
+            Histogram {
+                name: "logical_actions",
+                ordered: true,
+                buckets: logical_actions
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, v)| {
+                        let label = if i as u32 == LOGICAL_ACTIONS_CAP {
+                            format!("{}+", i)
+                        } else {
+                            i.to_string()
+                        };
+                        (label, v)
+                    })
+                    .collect(),
+            },
+            Histogram {
+                name: "script_out",
+                ordered: false,
+                buckets: labeled_buckets(
+                    ScriptCounts::default().outputs().map(|(l, _)| l),
+                    &self.script_out_hist,
+                ),
+            },
+            Histogram {
+                name: "script_in",
+                ordered: false,
+                buckets: labeled_buckets(
+                    ScriptCounts::default().inputs().map(|(l, _)| l),
+                    &self.script_in_hist,
+                ),
+            },
+            Histogram {
+                name: "locktime",
+                ordered: false,
+                buckets: labeled_buckets(
+                    LockTimeKind::ALL.map(LockTimeKind::label),
+                    &self.locktime_hist,
+                ),
+            },
+            Histogram {
+                name: "expiry",
+                ordered: false,
+                buckets: labeled_buckets(ExpiryKind::ALL.map(ExpiryKind::label), &self.expiry_hist),
+            },
+            Histogram {
+                name: "orchard_flags",
+                ordered: false,
+                buckets: labeled_buckets(OrchardFlags::LABELS, &self.orchard_flags_hist),
+            },
+            Histogram {
+                name: "sapling_bundle",
+                ordered: false,
+                buckets: labeled_buckets(
+                    SaplingBundle::ALL.map(SaplingBundle::label),
+                    &self.sapling_bundle_hist,
+                ),
+            },
+            Histogram {
+                name: "bundle_padding",
+                ordered: false,
+                buckets: labeled_buckets(
+                    BundlePadding::ALL.map(BundlePadding::label),
+                    &self.bundle_padding_hist,
+                ),
+            },
+            Histogram {
+                name: "version_group",
+                ordered: false,
+                buckets: labeled_buckets(
+                    VersionGroup::ALL.map(VersionGroup::label),
+                    &self.version_group_hist,
+                ),
+            },
+            Histogram {
+                name: "consensus_branch",
+                ordered: false,
+                buckets: labeled_buckets(
+                    ConsensusBranch::ALL.map(ConsensusBranch::label),
+                    &self.branch_hist,
+                ),
+            },
+        ]
+    }
+
+    /// Diversity metrics and entropy estimates for every histogram.
+    pub fn diversity(&self) -> Vec<HistogramDiversity> {
+        self.histograms()
+            .iter()
+            .map(|h| {
+                let counts = h.counts();
+                HistogramDiversity {
+                    histogram: h.name.to_string(),
+                    metrics: DiversityMetrics::from_counts(&counts),
+                    entropy: EntropyEstimate::from_counts(&counts),
+                }
+            })
+            .collect()
+    }
+}
+
+impl std::ops::AddAssign<&ShapeStats> for ShapeStats {
+    fn add_assign(&mut self, other: &ShapeStats) {
+        self.merge(other);
+    }
+}
+
+impl std::ops::AddAssign for ShapeStats {
+    fn add_assign(&mut self, other: ShapeStats) {
+        self.merge(&other);
+    }
+}
+
+impl std::iter::Sum for ShapeStats {
+    fn sum<I: Iterator<Item = ShapeStats>>(iter: I) -> Self {
+        iter.fold(ShapeStats::default(), |mut acc, s| {
+            acc += s;
+            acc
+        })
+    }
+}
+
+impl<'a> std::iter::Sum<&'a ShapeStats> for ShapeStats {
+    fn sum<I: Iterator<Item = &'a ShapeStats>>(iter: I) -> Self {
+        iter.fold(ShapeStats::default(), |mut acc, s| {
+            acc += s;
+            acc
+        })
+    }
+}
+
+fn combine_hist<K: Clone + Eq + std::hash::Hash>(
+    hist: &mut std::collections::HashMap<K, u64>,
+    other: &std::collections::HashMap<K, u64>,
+    op: fn(u64, u64) -> Option<u64>,
+) -> Option<()> {
+    for (k, &v) in other {
+        let n = hist.entry(k.clone()).or_insert(0);
+        *n = op(*n, v)?;
+    }
+    hist.retain(|_, n| *n > 0);
+    Some(())
+}
+
+/// Named view of one `ShapeStats` histogram.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    /// Histogram name (e.g. "vin_vout", "size_bucket").
+    pub name: &'static str,
+    /// Whether buckets have a natural order (size buckets), as needed by ordinal metrics.
+    pub ordered: bool,
+    /// (bucket label, count) pairs.
+    pub buckets: Vec<(String, u64)>,
+}
+
+impl Histogram {
+    /// Sum of all bucket counts.
+    pub fn total(&self) -> u64 {
+        self.buckets.iter().map(|(_, n)| n).sum()
+    }
+
+    /// Counts per bucket, in bucket order.
+    pub fn counts(&self) -> Vec<u64> {
+        self.buckets.iter().map(|(_, c)| *c).collect()
+    }
+}
+
+/// Diversity metrics tagged with the histogram they were computed for.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramDiversity {
+    pub histogram: String,
+    #[serde(flatten)]
+    pub metrics: DiversityMetrics,
+    /// Bias-corrected entropy estimates with a bootstrap interval.
+    pub entropy: EntropyEstimate,
+}
+
+/// Buckets of a label-keyed histogram, ordered numerically by their "_"-separated fields
+/// (e.g. "2_1" before "10_1"); non-numeric labels sort last, alphabetically.
+fn sorted_buckets(hist: &std::collections::HashMap<String, u64>) -> Vec<(String, u64)> {
+    let mut buckets: Vec<(String, u64)> = hist.iter().map(|(k, &v)| (k.clone(), v)).collect();
+    buckets.sort_by_cached_key(|(k, _)| {
+        let numeric: Option<Vec<u32>> = k.split('_').map(|f| f.parse().ok()).collect();
+        (numeric.is_none(), numeric.unwrap_or_default(), k.clone())
+    });
+    buckets
+}
+
+/// Buckets of a histogram with a fixed label set, in that order (missing labels count 0).
+fn labeled_buckets<const N: usize>(
+    labels: [&str; N],
+    hist: &std::collections::HashMap<String, u64>,
+) -> Vec<(String, u64)> {
+    labels
+        .iter()
+        .map(|l| (l.to_string(), hist.get(*l).copied().unwrap_or(0)))
+        .collect()
 }
 
 #[cfg(test)]
@@ -111,6 +789,7 @@ mod tests {
             n_orchard_action: 0,
             size_bucket: 0,
             version: 4,
+            ..Default::default()
         };
         assert!(s.has_transparent());
         assert!(!s.has_shielded());
@@ -128,6 +807,7 @@ mod tests {
                 n_orchard_action: 0,
                 size_bucket: size_bucket(300),
                 version: 4,
+                ..Default::default()
             },
             TxShape {
                 n_vin: 1,
@@ -138,11 +818,202 @@ mod tests {
                 n_orchard_action: 0,
                 size_bucket: size_bucket(300),
                 version: 4,
+                ..Default::default()
             },
         ];
         let stats = ShapeStats::from_shapes(&shapes);
         assert_eq!(stats.n_txs, 2);
         assert_eq!(stats.vin_vout_hist.get("1_2"), Some(&2));
         assert_eq!(stats.with_transparent, 2);
+        assert_eq!(stats.shape_hist.get("1_2_0_0_0_0_1_4"), Some(&2));
+        assert_eq!(
+            TxShape::from_tuple_key("1_2_0_0_0_0_1_4"),
+            Some(shapes[0].clone())
+        );
+        assert_eq!(stats.logical_actions_hist.get(&2), Some(&2));
+        assert_eq!(stats.grace_action_txs(), 2);
+        assert_eq!(TxShape::from_tuple_key("1_2"), None);
+        assert_eq!(TxShape::from_tuple_key("1_2_0_0_0_0_x_4"), None);
+    }
+
+    #[test]
+    fn test_histograms_and_diversity() {
+        let mut stats = ShapeStats::default();
+        stats.vin_vout_hist.insert("10_1".to_string(), 1);
+        stats.vin_vout_hist.insert("2_1".to_string(), 3);
+        stats.size_bucket_hist = [1, 3, 0, 0, 0, 0];
+        stats.version_hist.insert(5, 2);
+        stats.version_hist.insert(4, 2);
+        stats.n_txs = 4;
+        let hists = stats.histograms();
+        let names: Vec<&str> = hists.iter().map(|h| h.name).collect();
+        assert_eq!(
+            names,
+            [
+                "vin_vout",
+                "size_bucket",
+                "version",
+                "shape",
+                "logical_actions",
+                "script_out",
+                "script_in",
+                "locktime",
+                "expiry",
+                "orchard_flags",
+                "sapling_bundle",
+                "bundle_padding",
+                "version_group",
+                "consensus_branch",
+            ]
+        );
+        assert_eq!(hists[0].buckets[0].0, "2_1");
+        assert_eq!(hists[1].counts().len(), 6);
+        assert_eq!(hists[2].buckets[0].0, "4");
+        let div = stats.diversity();
+        assert_eq!(div.len(), 14);
+        assert!((div[2].metrics.shannon - 1.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_logical_actions() {
+        let shape = |n_vin, n_vout, n_joinsplit, spends, outputs, actions| TxShape {
+            n_vin,
+            n_vout,
+            n_joinsplit,
+            n_sapling_spend: spends,
+            n_sapling_output: outputs,
+            n_orchard_action: actions,
+            size_bucket: 1,
+            version: 5,
+            ..Default::default()
+        };
+        assert_eq!(shape(1, 2, 0, 0, 0, 0).logical_actions(), 2);
+        assert_eq!(shape(0, 0, 0, 1, 2, 0).logical_actions(), 2);
+        assert_eq!(shape(0, 1, 0, 0, 0, 2).logical_actions(), 3);
+        assert_eq!(shape(0, 0, 1, 0, 0, 0).logical_actions(), 2);
+        let shapes = [shape(0, 0, 0, 0, 0, 2), shape(0, 0, 0, 1, 40, 0)];
+        let stats = ShapeStats::from_shapes(&shapes);
+        assert_eq!(stats.grace_action_txs(), 1);
+        let hist = &stats.histograms()[4];
+        assert_eq!(hist.buckets.len(), LOGICAL_ACTIONS_CAP as usize + 1);
+        assert_eq!(hist.buckets[2], ("2".to_string(), 1));
+        assert_eq!(hist.buckets[16], ("16+".to_string(), 1));
+        assert_eq!(stats.grace_action_share(), Some(0.5));
+        assert!(stats.uncovered_txs().is_empty());
+
+        // A block stored before logical actions were recorded adds transactions but no counts.
+        let mut legacy = ShapeStats::from_shapes(&shapes);
+        legacy.logical_actions_hist.clear();
+        assert_eq!(legacy.grace_action_share(), None);
+        let mut merged = stats.clone();
+        merged += &legacy;
+        assert_eq!(merged.logical_actions_txs(), 2);
+        assert_eq!(merged.grace_action_share(), Some(0.5));
+        assert_eq!(merged.uncovered_txs(), vec![("logical_actions", 2)]);
+    }
+
+    #[test]
+    fn test_coinbase_kept_out_of_user_stats() {
+        let user = TxShape {
+            n_vin: 1,
+            n_vout: 2,
+            size_bucket: 1,
+            version: 5,
+            ..Default::default()
+        };
+        let coinbase = TxShape {
+            n_vin: 1,
+            n_vout: 3,
+            n_orchard_action: 2,
+            size_bucket: 2,
+            version: 5,
+            coinbase: true,
+            scripts: ScriptCounts {
+                out_p2pkh: 1,
+                out_p2sh: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let shapes = [user.clone(), coinbase.clone(), user];
+        let stats = ShapeStats::from_shapes(&shapes);
+        assert_eq!(stats.n_txs, 2);
+        assert_eq!(stats.with_shielded, 0);
+        assert_eq!(stats.shape_hist.len(), 1);
+        assert_eq!(stats.coinbase_hist.get("1_2_0_2_5"), Some(&1));
+        assert_eq!(stats.coinbase_txs(), 1);
+        assert!(stats.histograms().iter().all(|h| h.name != "coinbase"));
+        let only = ShapeStats::from_coinbase_shapes(&shapes);
+        assert_eq!((only.n_txs, only.with_shielded), (1, 1));
+        assert!(only.coinbase_hist.is_empty());
+        let mut all = stats.clone();
+        all += &only;
+        assert_eq!(all.n_txs, 3);
+        assert_eq!(all.with_shielded, 1);
+        assert_eq!(all.coinbase_hist, stats.coinbase_hist);
+        assert_eq!(
+            TxShape::from_tuple_key(&coinbase.tuple_key()).map(|s| s.coinbase),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_merge_and_diff() {
+        let shape = |vin: u32, n_orchard_action: u32, size_bucket: u8, coinbase: bool| TxShape {
+            n_vin: vin,
+            n_vout: 2,
+            n_orchard_action,
+            size_bucket,
+            version: 5,
+            coinbase,
+            ..Default::default()
+        };
+        let a = vec![
+            shape(1, 0, 1, true),
+            shape(1, 0, 1, false),
+            shape(0, 2, 3, false),
+        ];
+        let b = vec![shape(1, 0, 1, true), shape(2, 0, 2, false)];
+        let all: Vec<TxShape> = a.iter().chain(&b).cloned().collect();
+        let (sa, sb) = (ShapeStats::from_shapes(&a), ShapeStats::from_shapes(&b));
+        let expected = ShapeStats::from_shapes(&all);
+        // Bias-corrected: above the plug-in estimate for a small sample.
+        assert!(expected.size_entropy > crate::util::entropy(&expected.size_bucket_hist));
+
+        let mut merged = sa.clone();
+        merged += &sb;
+        let summed: ShapeStats = [sa.clone(), sb.clone()].into_iter().sum();
+        for stats in [&merged, &summed] {
+            assert_eq!(stats.n_txs, expected.n_txs);
+            assert_eq!(stats.size_bucket_hist, expected.size_bucket_hist);
+            assert_eq!(stats.shape_hist, expected.shape_hist);
+            assert_eq!(stats.coinbase_hist, expected.coinbase_hist);
+            assert_eq!(stats.logical_actions_hist, expected.logical_actions_hist);
+            assert!((stats.size_entropy - expected.size_entropy).abs() < 1e-12);
+        }
+
+        let back = merged.diff(&sb).unwrap();
+        assert_eq!(back.n_txs, sa.n_txs);
+        assert_eq!(back.with_shielded, sa.with_shielded);
+        assert_eq!(back.vin_vout_hist, sa.vin_vout_hist);
+        assert_eq!(back.shape_hist, sa.shape_hist);
+        assert!((back.size_entropy - sa.size_entropy).abs() < 1e-12);
+        // Diffing with itself empties every bucket.
+        let none = merged.diff(&merged).unwrap();
+        assert_eq!(none.n_txs, 0);
+        assert!(none.shape_hist.is_empty());
+        assert_eq!(none.size_entropy, 0.0);
+        // Not part of these stats.
+        assert!(sb.diff(&merged).is_none());
+        assert!(sa.diff(&sb).is_none());
+
+        let empty: ShapeStats = std::iter::empty::<&ShapeStats>().sum();
+        assert_eq!(empty.n_txs, 0);
+
+        // Merging saturates instead of panicking.
+        let mut full = sa.clone();
+        full.n_txs = u64::MAX;
+        full += &sb;
+        assert_eq!(full.n_txs, u64::MAX);
     }
 }
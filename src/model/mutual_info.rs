@@ -0,0 +1,153 @@
+//! Mutual information and conditional entropy between the eight shape-tuple dimensions,
+//! computed from the joint shape-tuple counts (`ShapeStats::shape_hist`), to find dimensions
+//! that are redundant (one predicts the other) and pairs that jointly carry distinguishing
+//! information. The other categorical histograms (scripts, lock time, expiry, Orchard flags,
+//! version group) are stored as marginals only, so no pair involving them can be computed.
+//!
+//! Entropies use the Miller–Madow correction, so that sparse joint histograms do not inflate
+//! mutual information; estimates are clamped to their theoretical bounds. All values in bits.
+
+use super::entropy::miller_madow;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Shape dimensions, in `TxShape::tuple_key` order.
+pub const DIMENSIONS: [&str; 8] = [
+    "vin",
+    "vout",
+    "joinsplit",
+    "sapling_spend",
+    "sapling_output",
+    "orchard_action",
+    "size_bucket",
+    "version",
+];
+
+/// Share of a dimension's entropy another must explain to be reported as redundant.
+pub const REDUNDANT_EXPLAINED: f64 = 0.9;
+
+/// Pairwise information matrix over `DIMENSIONS`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DimensionMatrix {
+    pub dimensions: Vec<&'static str>,
+    pub n_txs: u64,
+    /// Entropy H(D) of each dimension.
+    pub entropy: Vec<f64>,
+    /// `mutual_information[i][j]` = I(Di; Dj); symmetric, the diagonal is H(Di).
+    pub mutual_information: Vec<Vec<f64>>,
+    /// `conditional_entropy[i][j]` = H(Di | Dj); the diagonal is 0.
+    pub conditional_entropy: Vec<Vec<f64>>,
+}
+
+/// One dimension largely determining another.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Redundancy {
+    pub predictor: &'static str,
+    pub predicted: &'static str,
+    /// Share of H(predicted) explained by the predictor: I / H(predicted).
+    pub explained: f64,
+}
+
+/// Build the matrix from full shape-tuple counts; malformed keys are skipped.
+pub fn dimension_matrix(shape_hist: &HashMap<String, u64>) -> DimensionMatrix {
+    let tuples: Vec<(Vec<&str>, u64)> = shape_hist
+        .iter()
+        .map(|(k, &n)| (k.split('_').collect::<Vec<_>>(), n))
+        .filter(|(f, n)| f.len() == DIMENSIONS.len() && *n > 0)
+        .collect();
+    let d = DIMENSIONS.len();
+    let joint_entropy = |i: usize, j: usize| {
+        let mut counts: HashMap<(&str, &str), u64> = HashMap::new();
+        for (f, n) in &tuples {
+            *counts.entry((f[i], f[j])).or_insert(0) += n;
+        }
+        miller_madow(&counts.into_values().collect::<Vec<_>>())
+    };
+    let joint: Vec<Vec<f64>> = (0..d)
+        .map(|i| (0..d).map(|j| joint_entropy(i, j)).collect())
+        .collect();
+    let entropy: Vec<f64> = (0..d).map(|i| joint[i][i]).collect();
+    let mut mutual_information = vec![vec![0.0; d]; d];
+    let mut conditional_entropy = vec![vec![0.0; d]; d];
+    for i in 0..d {
+        for j in 0..d {
+            if i == j {
+                mutual_information[i][j] = entropy[i];
+                continue;
+            }
+            mutual_information[i][j] =
+                (entropy[i] + entropy[j] - joint[i][j]).clamp(0.0, entropy[i].min(entropy[j]));
+            conditional_entropy[i][j] = (joint[i][j] - entropy[j]).clamp(0.0, entropy[i]);
+        }
+    }
+    DimensionMatrix {
+        dimensions: DIMENSIONS.to_vec(),
+        n_txs: tuples.iter().map(|(_, n)| n).sum(),
+        entropy,
+        mutual_information,
+        conditional_entropy,
+    }
+}
+
+impl DimensionMatrix {
+    /// Ordered pairs where the predictor explains at least `min_explained` of the predicted
+    /// dimension's entropy, most redundant first. Constant dimensions are never predicted.
+    pub fn redundancies(&self, min_explained: f64) -> Vec<Redundancy> {
+        let d = self.dimensions.len();
+        let mut out: Vec<Redundancy> = (0..d)
+            .flat_map(|i| (0..d).map(move |j| (i, j)))
+            .filter(|&(i, j)| i != j && self.entropy[j] > 0.0)
+            .map(|(i, j)| Redundancy {
+                predictor: self.dimensions[i],
+                predicted: self.dimensions[j],
+                explained: self.mutual_information[i][j] / self.entropy[j],
+            })
+            .filter(|r| r.explained >= min_explained)
+            .collect();
+        out.sort_by(|a, b| {
+            b.explained
+                .total_cmp(&a.explained)
+                .then_with(|| a.predictor.cmp(b.predictor))
+                .then_with(|| a.predicted.cmp(b.predicted))
+        });
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dimension_matrix() {
+        // Version determines the size bucket; vout is independent of both.
+        let hist: HashMap<String, u64> = [
+            ("1_1_0_0_0_0_1_4", 100),
+            ("1_2_0_0_0_0_1_4", 100),
+            ("0_1_0_0_0_2_3_5", 100),
+            ("0_2_0_0_0_2_3_5", 100),
+            ("bad_key", 7),
+        ]
+        .into_iter()
+        .map(|(k, n)| (k.to_string(), n))
+        .collect();
+        let m = dimension_matrix(&hist);
+        assert_eq!(m.n_txs, 400);
+        let idx = |name| DIMENSIONS.iter().position(|&d| d == name).unwrap();
+        let (vout, size, version) = (idx("vout"), idx("size_bucket"), idx("version"));
+        assert!((m.entropy[version] - 1.0).abs() < 0.01);
+        assert!((m.mutual_information[version][size] - m.entropy[size]).abs() < 0.01);
+        assert!(m.conditional_entropy[size][version] < 0.01);
+        assert!(m.mutual_information[vout][version] < 0.01);
+        assert!((m.conditional_entropy[vout][version] - 1.0).abs() < 0.01);
+        assert_eq!(m.entropy[idx("joinsplit")], 0.0);
+
+        let r = m.redundancies(0.9);
+        assert!(r
+            .iter()
+            .any(|r| r.predictor == "version" && r.predicted == "size_bucket"));
+        assert!(r
+            .iter()
+            .all(|r| r.predicted != "vout" && r.predictor != "vout"));
+    }
+}
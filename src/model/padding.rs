@@ -0,0 +1,237 @@
+//! Padding-policy what-if simulation over retained shape-tuple counts.
+//!
+//! A policy rewrites each stored shape tuple (e.g. "pad every shielded bundle to 2 outputs") and
+//! the simulation reports how distinguishable the padded traffic would be and what it would
+//! cost in bytes. Sizes are only known by bucket, so byte figures are estimates: each bucket is
+//! represented by a typical size, and padded elements add their serialized size.
+
+use super::metrics::DiversityMetrics;
+use super::TxShape;
+use crate::util::{size_bucket, BUCKET_TYPICAL_SIZE};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Pre-padding size estimate of a transaction in `bucket`.
+fn bucket_size(bucket: u8) -> u64 {
+    BUCKET_TYPICAL_SIZE[(bucket as usize).min(5)] as u64
+}
+
+/// Smallest size (bytes) in each size bucket.
+const BUCKET_MIN: [u64; 6] = [0, 257, 513, 1025, 2049, 4097];
+/// Serialized bytes added per padded element.
+const SAPLING_SPEND_BYTES: u64 = 384;
+const SAPLING_OUTPUT_BYTES: u64 = 948;
+/// Orchard action description, its spend authorization signature and its share of the proof.
+const ORCHARD_ACTION_BYTES: u64 = 820 + 64 + 2272;
+
+/// Declarative padding rules; unset rules leave the shape unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PaddingPolicy {
+    pub name: String,
+    /// Pad every present shielded bundle to at least this many outputs (Sapling outputs,
+    /// Orchard actions).
+    #[serde(default)]
+    pub min_outputs: Option<u32>,
+    /// Round non-zero Sapling spend/output and Orchard action counts up to a power of two.
+    #[serde(default)]
+    pub round_pow2: bool,
+    /// Pad every transaction to at least this size bucket (0..=5).
+    #[serde(default)]
+    pub pad_size_bucket: Option<u8>,
+}
+
+/// Traffic properties after applying one policy.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PaddingOutcome {
+    pub policy: String,
+    pub n_txs: u64,
+    /// Transactions whose shape the policy changed.
+    pub padded_txs: u64,
+    pub distinct_shapes: usize,
+    /// Shannon entropy (bits) of the full shape-tuple distribution.
+    pub shape_entropy: f64,
+    pub effective_shapes: f64,
+    /// Shannon entropy (bits) of the size-bucket distribution.
+    pub size_entropy: f64,
+    /// Estimated total bytes after padding.
+    pub est_bytes: u64,
+    /// Estimated bytes added by padding.
+    pub overhead_bytes: u64,
+    /// overhead_bytes / estimated bytes before padding.
+    pub overhead_ratio: f64,
+    /// Tuple keys that could not be parsed (their transactions are left out).
+    pub skipped_txs: u64,
+}
+
+impl PaddingPolicy {
+    /// The identity policy, for the unpadded reference row.
+    pub fn none() -> PaddingPolicy {
+        PaddingPolicy {
+            name: "none".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Padded shape and the estimated bytes added.
+    pub fn apply(&self, shape: &TxShape) -> (TxShape, u64) {
+        let mut out = shape.clone();
+        let pad = |n: u32| {
+            if n > 0 && self.round_pow2 {
+                n.next_power_of_two()
+            } else {
+                n
+            }
+        };
+        let sapling = shape.n_sapling_spend + shape.n_sapling_output > 0;
+        out.n_sapling_spend = pad(shape.n_sapling_spend);
+        out.n_sapling_output = pad(shape.n_sapling_output);
+        out.n_orchard_action = pad(shape.n_orchard_action);
+        if let Some(min) = self.min_outputs {
+            if sapling {
+                out.n_sapling_output = out.n_sapling_output.max(min);
+            }
+            if shape.n_orchard_action > 0 {
+                out.n_orchard_action = out.n_orchard_action.max(min);
+            }
+        }
+        let mut added = (out.n_sapling_spend - shape.n_sapling_spend) as u64 * SAPLING_SPEND_BYTES
+            + (out.n_sapling_output - shape.n_sapling_output) as u64 * SAPLING_OUTPUT_BYTES
+            + (out.n_orchard_action - shape.n_orchard_action) as u64 * ORCHARD_ACTION_BYTES;
+        let size = bucket_size(shape.size_bucket) + added;
+        out.size_bucket = out
+            .size_bucket
+            .max(size_bucket(size.min(u32::MAX as u64) as u32));
+        if let Some(min_bucket) = self.pad_size_bucket {
+            let min_bucket = min_bucket.min(5);
+            if out.size_bucket < min_bucket {
+                let target = BUCKET_MIN[min_bucket as usize];
+                added += target.saturating_sub(size);
+                out.size_bucket = min_bucket;
+            }
+        }
+        (out, added)
+    }
+}
+
+/// Apply `policy` to a shape-tuple histogram (TxShape::tuple_key → count).
+pub fn simulate(shape_hist: &HashMap<String, u64>, policy: &PaddingPolicy) -> PaddingOutcome {
+    let mut padded_hist: HashMap<String, u64> = HashMap::new();
+    let mut size_hist = [0u64; 6];
+    let (mut n_txs, mut padded_txs, mut skipped_txs) = (0u64, 0u64, 0u64);
+    let (mut base_bytes, mut overhead_bytes) = (0u64, 0u64);
+    for (key, &count) in shape_hist {
+        let Some(shape) = TxShape::from_tuple_key(key) else {
+            skipped_txs += count;
+            continue;
+        };
+        let (padded, added) = policy.apply(&shape);
+        n_txs += count;
+        if padded != shape {
+            padded_txs += count;
+        }
+        base_bytes += bucket_size(shape.size_bucket) * count;
+        overhead_bytes += added * count;
+        size_hist[(padded.size_bucket as usize).min(5)] += count;
+        *padded_hist.entry(padded.tuple_key()).or_insert(0) += count;
+    }
+    let counts: Vec<u64> = padded_hist.values().copied().collect();
+    let metrics = DiversityMetrics::from_counts(&counts);
+    PaddingOutcome {
+        policy: policy.name.clone(),
+        n_txs,
+        padded_txs,
+        distinct_shapes: metrics.distinct,
+        shape_entropy: metrics.shannon,
+        effective_shapes: metrics.effective_shapes,
+        size_entropy: crate::util::entropy(&size_hist),
+        est_bytes: base_bytes + overhead_bytes,
+        overhead_bytes,
+        overhead_ratio: if base_bytes == 0 {
+            0.0
+        } else {
+            overhead_bytes as f64 / base_bytes as f64
+        },
+        skipped_txs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(n_sapling_output: u32, n_orchard_action: u32, size_bucket: u8) -> TxShape {
+        TxShape {
+            n_vin: 0,
+            n_vout: 0,
+            n_joinsplit: 0,
+            n_sapling_spend: 1,
+            n_sapling_output,
+            n_orchard_action,
+            size_bucket,
+            version: 5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply_rules() {
+        let min2 = PaddingPolicy {
+            name: "min2".to_string(),
+            min_outputs: Some(2),
+            ..Default::default()
+        };
+        let (padded, added) = min2.apply(&shape(1, 0, 2));
+        assert_eq!(padded.n_sapling_output, 2);
+        assert_eq!(padded.n_orchard_action, 0);
+        assert_eq!(added, SAPLING_OUTPUT_BYTES);
+        assert_eq!(padded.size_bucket, 3);
+
+        let pow2 = PaddingPolicy {
+            name: "pow2".to_string(),
+            round_pow2: true,
+            ..Default::default()
+        };
+        let (padded, added) = pow2.apply(&shape(2, 3, 5));
+        assert_eq!((padded.n_sapling_output, padded.n_orchard_action), (2, 4));
+        assert_eq!(added, ORCHARD_ACTION_BYTES);
+
+        let bucket = PaddingPolicy {
+            name: "bucket".to_string(),
+            pad_size_bucket: Some(3),
+            ..Default::default()
+        };
+        let (padded, added) = bucket.apply(&shape(2, 0, 1));
+        assert_eq!(padded.size_bucket, 3);
+        assert_eq!(added, BUCKET_MIN[3] - bucket_size(1));
+        assert_eq!(
+            PaddingPolicy::none().apply(&shape(1, 0, 2)),
+            (shape(1, 0, 2), 0)
+        );
+    }
+
+    #[test]
+    fn test_simulate_reduces_distinct_shapes() {
+        let mut hist = HashMap::new();
+        for (n, c) in [(1u32, 10u64), (2, 20), (3, 5)] {
+            hist.insert(shape(n, 0, 2).tuple_key(), c);
+        }
+        hist.insert("garbage".to_string(), 4);
+        let none = simulate(&hist, &PaddingPolicy::none());
+        assert_eq!(none.distinct_shapes, 3);
+        assert_eq!(none.overhead_bytes, 0);
+        assert_eq!(none.skipped_txs, 4);
+        let pow2 = PaddingPolicy {
+            name: "pow2".to_string(),
+            min_outputs: Some(2),
+            round_pow2: true,
+            pad_size_bucket: Some(4),
+        };
+        let padded = simulate(&hist, &pow2);
+        assert_eq!(padded.n_txs, 35);
+        assert_eq!(padded.distinct_shapes, 2);
+        assert!(padded.shape_entropy < none.shape_entropy);
+        assert_eq!(padded.padded_txs, 35);
+        assert!(padded.overhead_ratio > 0.0);
+        assert_eq!(padded.size_entropy, 0.0);
+    }
+}
@@ -0,0 +1,163 @@
+//! Shape conformance scoring: how common a transaction's full shape tuple is in a reference
+//! distribution of chain traffic.
+
+use super::divergence::SMOOTHING;
+use super::{ShapeStats, TxShape};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// `format` tag of exported baseline files.
+pub const BASELINE_FORMAT: &str = "zcash-txshape-baseline";
+/// Current baseline file version.
+pub const BASELINE_VERSION: u32 = 1;
+
+/// Reference distribution of full shape tuples (TxShape::tuple_key → count). Shapes are kept
+/// sorted so exported files are stable under version control.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceDistribution {
+    pub total: u64,
+    pub shapes: BTreeMap<String, u64>,
+}
+
+/// Reference distribution exported from stored stats for use without a database (CI).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BaselineFile {
+    pub format: String,
+    pub version: u32,
+    /// Block range [low, high) the distribution was aggregated from.
+    pub low: u32,
+    pub high: u32,
+    pub distribution: ReferenceDistribution,
+}
+
+impl BaselineFile {
+    pub fn new(low: u32, high: u32, distribution: ReferenceDistribution) -> BaselineFile {
+        BaselineFile {
+            format: BASELINE_FORMAT.to_string(),
+            version: BASELINE_VERSION,
+            low,
+            high,
+            distribution,
+        }
+    }
+
+    /// Parse and check the format tag and version.
+    pub fn from_json(data: &str) -> anyhow::Result<BaselineFile> {
+        let file: BaselineFile = serde_json::from_str(data)?;
+        if file.format != BASELINE_FORMAT {
+            anyhow::bail!("not a {} file (format {:?})", BASELINE_FORMAT, file.format);
+        }
+        if file.version != BASELINE_VERSION {
+            anyhow::bail!("unsupported baseline version {}", file.version);
+        }
+        Ok(file)
+    }
+}
+
+/// Score of one transaction shape against a reference distribution.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShapeScore {
+    pub shape: String,
+    /// Reference transactions with the identical shape tuple.
+    pub count: u64,
+    /// count / total.
+    pub share: f64,
+    /// −log2 p with p the smoothed probability of the shape (finite for unseen shapes).
+    pub surprisal_bits: f64,
+    /// 1 = most common shape (ties share a rank); `None` when the shape was never seen.
+    pub rank: Option<usize>,
+    /// Distinct shapes in the reference.
+    pub distinct: usize,
+}
+
+impl ReferenceDistribution {
+    pub fn from_stats(stats: &ShapeStats) -> ReferenceDistribution {
+        ReferenceDistribution {
+            total: stats.shape_hist.values().sum(),
+            shapes: stats
+                .shape_hist
+                .iter()
+                .map(|(k, &v)| (k.clone(), v))
+                .collect(),
+        }
+    }
+
+    /// Score shapes in one pass (ranks are computed once).
+    pub fn score(&self, shapes: &[TxShape]) -> Vec<ShapeScore> {
+        let mut counts: Vec<u64> = self.shapes.values().copied().filter(|&c| c > 0).collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        let distinct = counts.len();
+        // Add-half smoothing over the seen shapes plus one class for all unseen shapes.
+        let denom = self.total as f64 + SMOOTHING * (distinct as f64 + 1.0);
+        shapes
+            .iter()
+            .map(|s| {
+                let shape = s.tuple_key();
+                let count = self.shapes.get(&shape).copied().unwrap_or(0);
+                let rank = (count > 0).then(|| 1 + counts.partition_point(|&c| c > count));
+                ShapeScore {
+                    shape,
+                    count,
+                    share: if self.total == 0 {
+                        0.0
+                    } else {
+                        count as f64 / self.total as f64
+                    },
+                    surprisal_bits: -((count as f64 + SMOOTHING) / denom).log2(),
+                    rank,
+                    distinct,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(n_vin: u32) -> TxShape {
+        TxShape {
+            n_vin,
+            n_vout: 2,
+            n_joinsplit: 0,
+            n_sapling_spend: 0,
+            n_sapling_output: 0,
+            n_orchard_action: 0,
+            size_bucket: 1,
+            version: 4,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_score_ranks_and_surprisal() {
+        let mut reference = vec![shape(1); 6];
+        reference.extend(vec![shape(2); 3]);
+        reference.extend(vec![shape(3); 3]);
+        let dist = ReferenceDistribution::from_stats(&ShapeStats::from_shapes(&reference));
+        assert_eq!(dist.total, 12);
+        let scores = dist.score(&[shape(1), shape(3), shape(9)]);
+        assert_eq!(scores[0].rank, Some(1));
+        assert_eq!(scores[1].rank, Some(2));
+        assert_eq!(scores[2].rank, None);
+        assert_eq!(scores[0].distinct, 3);
+        assert!((scores[0].share - 0.5).abs() < 1e-12);
+        assert_eq!(scores[2].count, 0);
+        // p = 6.5 / (12 + 0.5 * 4) = 6.5 / 14
+        assert!((scores[0].surprisal_bits - (14.0f64 / 6.5).log2()).abs() < 1e-12);
+        assert!(scores[2].surprisal_bits > scores[1].surprisal_bits);
+        assert!(scores[1].surprisal_bits > scores[0].surprisal_bits);
+    }
+
+    #[test]
+    fn test_baseline_file_roundtrip() {
+        let dist =
+            ReferenceDistribution::from_stats(&ShapeStats::from_shapes(&[shape(1), shape(2)]));
+        let file = BaselineFile::new(10, 20, dist);
+        let json = serde_json::to_string(&file).unwrap();
+        assert_eq!(BaselineFile::from_json(&json).unwrap(), file);
+        let other = json.replace(BASELINE_FORMAT, "something-else");
+        assert!(BaselineFile::from_json(&other).is_err());
+    }
+}
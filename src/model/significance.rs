@@ -0,0 +1,413 @@
+//! Significance tests for comparing two ranges: homogeneity tests per histogram,
+//! two-proportion z-tests for component shares, and multiple-comparison correction.
+
+use super::divergence::align;
+use super::ShapeStats;
+use serde::Serialize;
+
+/// Family-wise significance level applied to corrected p-values.
+pub const ALPHA: f64 = 0.05;
+
+/// Test statistic and (uncorrected) p-value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestResult {
+    pub statistic: f64,
+    /// Degrees of freedom (chi-square family only).
+    pub df: Option<u64>,
+    pub p_value: f64,
+}
+
+/// One test in a range comparison, with p-values corrected across the whole family.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignificanceTest {
+    /// Histogram name, or "shielded_share" / "transparent_share" / "grace_action_share".
+    pub subject: String,
+    /// "chi_square" or "two_proportion_z".
+    pub test: String,
+    #[serde(flatten)]
+    pub result: TestResult,
+    /// Holm–Bonferroni adjusted p-value.
+    pub p_holm: f64,
+    /// Benjamini–Hochberg adjusted p-value.
+    pub p_bh: f64,
+    /// Whether `p_holm` is below ALPHA.
+    pub significant: bool,
+    /// G-test of the same histogram (chi-square tests only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub g_test: Option<GTest>,
+}
+
+/// G-test (log-likelihood ratio) result of a histogram, corrected across the family in which
+/// it takes the place of the histogram's chi-square test.
+#[derive(Debug, Clone, Serialize)]
+pub struct GTest {
+    #[serde(flatten)]
+    pub result: TestResult,
+    pub p_holm: f64,
+    pub p_bh: f64,
+    pub significant: bool,
+}
+
+/// Run all tests comparing range A with range B: one chi-square test per histogram and
+/// two-proportion z-tests for the shielded, transparent and ZIP-317 grace-action shares. The
+/// grace-action share only counts transactions with a recorded logical-action count. Each
+/// histogram also gets the G-test of the same hypothesis; counting both in one correction
+/// family would double-count the histogram, so G-tests are corrected in a second family where
+/// they replace the chi-square tests. Tests that cannot be run (empty ranges, single-bucket
+/// histograms) are omitted.
+pub fn range_tests(a: &ShapeStats, b: &ShapeStats) -> Vec<SignificanceTest> {
+    let mut raw: Vec<(String, &str, TestResult)> = Vec::new();
+    let mut g_raw: Vec<Option<TestResult>> = Vec::new();
+    for (ha, hb) in a.histograms().iter().zip(b.histograms().iter()) {
+        let aligned = align(ha, hb);
+        if let Some(r) = chi_square_homogeneity(&aligned.a, &aligned.b) {
+            raw.push((ha.name.to_string(), "chi_square", r));
+            g_raw.push(g_test_homogeneity(&aligned.a, &aligned.b));
+        }
+    }
+    let shares = [
+        (
+            "shielded_share",
+            (a.with_shielded, a.n_txs),
+            (b.with_shielded, b.n_txs),
+        ),
+        (
+            "transparent_share",
+            (a.with_transparent, a.n_txs),
+            (b.with_transparent, b.n_txs),
+        ),
+        (
+            "grace_action_share",
+            (a.grace_action_txs(), a.logical_actions_txs()),
+            (b.grace_action_txs(), b.logical_actions_txs()),
+        ),
+    ];
+    for (subject, (xa, na), (xb, nb)) in shares {
+        if let Some(r) = two_proportion_z(xa, na, xb, nb) {
+            raw.push((subject.to_string(), "two_proportion_z", r));
+            g_raw.push(None);
+        }
+    }
+    let p_values: Vec<f64> = raw.iter().map(|(_, _, r)| r.p_value).collect();
+    let p_holm = holm(&p_values);
+    let p_bh = benjamini_hochberg(&p_values);
+    let g_values: Vec<f64> = g_raw
+        .iter()
+        .zip(&p_values)
+        .map(|(g, &p)| g.as_ref().map_or(p, |g| g.p_value))
+        .collect();
+    let g_holm = holm(&g_values);
+    let g_bh = benjamini_hochberg(&g_values);
+    raw.into_iter()
+        .zip(g_raw)
+        .enumerate()
+        .map(|(i, ((subject, test, result), g))| SignificanceTest {
+            subject,
+            test: test.to_string(),
+            result,
+            p_holm: p_holm[i],
+            p_bh: p_bh[i],
+            significant: p_holm[i] < ALPHA,
+            g_test: g.map(|result| GTest {
+                result,
+                p_holm: g_holm[i],
+                p_bh: g_bh[i],
+                significant: g_holm[i] < ALPHA,
+            }),
+        })
+        .collect()
+}
+
+/// Pearson chi-square test of homogeneity on the 2×K table formed by `a` and `b`.
+/// Buckets empty in both rows are dropped. Returns None if either row is empty or K < 2.
+pub fn chi_square_homogeneity(a: &[u64], b: &[u64]) -> Option<TestResult> {
+    let table = Table2xK::new(a, b)?;
+    let statistic = table
+        .cells()
+        .map(|(o, e)| (o - e) * (o - e) / e)
+        .sum::<f64>();
+    Some(table.chi_square_result(statistic))
+}
+
+/// G-test (log-likelihood ratio) of homogeneity on the same 2×K table.
+pub fn g_test_homogeneity(a: &[u64], b: &[u64]) -> Option<TestResult> {
+    let table = Table2xK::new(a, b)?;
+    let statistic = 2.0
+        * table
+            .cells()
+            .filter(|(o, _)| *o > 0.0)
+            .map(|(o, e)| o * (o / e).ln())
+            .sum::<f64>();
+    Some(table.chi_square_result(statistic.max(0.0)))
+}
+
+/// Two-sided two-proportion z-test of x1/n1 vs x2/n2 (pooled variance). Statistic sign is B − A.
+pub fn two_proportion_z(x1: u64, n1: u64, x2: u64, n2: u64) -> Option<TestResult> {
+    if n1 == 0 || n2 == 0 {
+        return None;
+    }
+    let (n1f, n2f) = (n1 as f64, n2 as f64);
+    let pooled = (x1 + x2) as f64 / (n1f + n2f);
+    let se = (pooled * (1.0 - pooled) * (1.0 / n1f + 1.0 / n2f)).sqrt();
+    if se == 0.0 {
+        return Some(TestResult {
+            statistic: 0.0,
+            df: None,
+            p_value: 1.0,
+        });
+    }
+    let z = (x2 as f64 / n2f - x1 as f64 / n1f) / se;
+    Some(TestResult {
+        statistic: z,
+        df: None,
+        p_value: normal_two_sided_p(z),
+    })
+}
+
+/// Holm–Bonferroni adjusted p-values (controls family-wise error rate), in input order.
+pub fn holm(p_values: &[f64]) -> Vec<f64> {
+    let m = p_values.len();
+    let order = sorted_indices(p_values);
+    let mut adjusted = vec![0.0; m];
+    let mut running_max: f64 = 0.0;
+    for (rank, &i) in order.iter().enumerate() {
+        running_max = running_max.max(((m - rank) as f64 * p_values[i]).min(1.0));
+        adjusted[i] = running_max;
+    }
+    adjusted
+}
+
+/// Benjamini–Hochberg adjusted p-values (controls false discovery rate), in input order.
+pub fn benjamini_hochberg(p_values: &[f64]) -> Vec<f64> {
+    let m = p_values.len();
+    let order = sorted_indices(p_values);
+    let mut adjusted = vec![0.0; m];
+    let mut running_min: f64 = 1.0;
+    for (rank, &i) in order.iter().enumerate().rev() {
+        running_min = running_min.min(p_values[i] * m as f64 / (rank + 1) as f64);
+        adjusted[i] = running_min;
+    }
+    adjusted
+}
+
+/// Survival function of the chi-square distribution: P(X > x) with `df` degrees of freedom.
+pub fn chi_square_sf(x: f64, df: u64) -> f64 {
+    if x <= 0.0 || df == 0 {
+        return 1.0;
+    }
+    upper_regularized_gamma(df as f64 / 2.0, x / 2.0)
+}
+
+/// Two-sided standard normal p-value: P(|Z| > |z|).
+pub fn normal_two_sided_p(z: f64) -> f64 {
+    // erfc(|z|/√2) = Q(1/2, z²/2).
+    upper_regularized_gamma(0.5, z * z / 2.0)
+}
+
+/// Regularized upper incomplete gamma Q(s, x), via series for x < s + 1 and continued fraction otherwise.
+/// Near x ≈ s both need on the order of √s terms, so the iteration cap grows with `s`.
+fn upper_regularized_gamma(s: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let log_prefix = s * x.ln() - x - ln_gamma(s);
+    let max_iter = 100 + (10.0 * s.sqrt()) as usize;
+    if x < s + 1.0 {
+        let mut term = 1.0 / s;
+        let mut sum = term;
+        let mut k = s;
+        for _ in 0..max_iter {
+            k += 1.0;
+            term *= x / k;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (1.0 - sum * log_prefix.exp()).clamp(0.0, 1.0)
+    } else {
+        // Lentz's method for the continued fraction of Q.
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - s;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..max_iter {
+            let an = -(i as f64) * (i as f64 - s);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        (h * log_prefix.exp()).clamp(0.0, 1.0)
+    }
+}
+
+/// ln Γ(x) for x > 0 (Lanczos approximation, g = 7).
+pub(crate) fn ln_gamma(x: f64) -> f64 {
+    const COEF: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEF[1..]
+        .iter()
+        .enumerate()
+        .fold(COEF[0], |acc, (i, c)| acc + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+fn sorted_indices(values: &[f64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&i, &j| values[i].total_cmp(&values[j]));
+    order
+}
+
+/// 2×K contingency table with columns that are empty in both rows removed.
+struct Table2xK {
+    rows: [Vec<f64>; 2],
+    row_totals: [f64; 2],
+    col_totals: Vec<f64>,
+    total: f64,
+}
+
+impl Table2xK {
+    fn new(a: &[u64], b: &[u64]) -> Option<Self> {
+        let (ra, rb): (Vec<f64>, Vec<f64>) = a
+            .iter()
+            .zip(b)
+            .filter(|(x, y)| **x + **y > 0)
+            .map(|(&x, &y)| (x as f64, y as f64))
+            .unzip();
+        let row_totals = [ra.iter().sum::<f64>(), rb.iter().sum::<f64>()];
+        if ra.len() < 2 || row_totals[0] == 0.0 || row_totals[1] == 0.0 {
+            return None;
+        }
+        let col_totals = ra.iter().zip(&rb).map(|(x, y)| x + y).collect();
+        Some(Table2xK {
+            rows: [ra, rb],
+            row_totals,
+            col_totals,
+            total: row_totals[0] + row_totals[1],
+        })
+    }
+
+    /// (observed, expected) for every cell.
+    fn cells(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        (0..2).flat_map(move |r| {
+            self.rows[r]
+                .iter()
+                .zip(&self.col_totals)
+                .map(move |(o, c)| (*o, self.row_totals[r] * c / self.total))
+        })
+    }
+
+    fn chi_square_result(&self, statistic: f64) -> TestResult {
+        let df = (self.col_totals.len() - 1) as u64;
+        TestResult {
+            statistic,
+            df: Some(df),
+            p_value: chi_square_sf(statistic, df),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution_functions() {
+        // Reference values: chi2(df=1) at 3.841 ≈ 0.05, chi2(df=4) at 9.488 ≈ 0.05.
+        assert!((chi_square_sf(3.841_459, 1) - 0.05).abs() < 1e-5);
+        assert!((chi_square_sf(9.487_729, 4) - 0.05).abs() < 1e-5);
+        assert!((normal_two_sided_p(1.959_964) - 0.05).abs() < 1e-5);
+        assert!((normal_two_sided_p(0.0) - 1.0).abs() < 1e-12);
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-10);
+        // Large df, as with thousands of shape-tuple buckets: both the series (x < df) and the
+        // continued fraction (x > df) must run to convergence.
+        assert!((chi_square_sf(4_800.0, 5_000) - 0.978_340_312).abs() < 1e-6);
+        assert!((chi_square_sf(5_000.0, 5_000) - 0.497_340_379).abs() < 1e-6);
+        assert!((chi_square_sf(5_200.0, 5_000) - 0.023_818_998).abs() < 1e-6);
+        assert!((chi_square_sf(19_600.0, 20_000) - 0.977_792_456).abs() < 1e-6);
+        assert!((chi_square_sf(20_000.0, 20_000) - 0.498_670_192).abs() < 1e-6);
+        assert!((chi_square_sf(20_400.0, 20_000) - 0.023_287_322).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_homogeneity_tests() {
+        let same = chi_square_homogeneity(&[50, 30, 20], &[100, 60, 40]).unwrap();
+        assert!(same.statistic.abs() < 1e-10 && (same.p_value - 1.0).abs() < 1e-10);
+        assert_eq!(same.df, Some(2));
+        let diff = g_test_homogeneity(&[90, 10, 0], &[10, 90, 0]).unwrap();
+        assert_eq!(diff.df, Some(1));
+        assert!(diff.p_value < 1e-10);
+        assert!(chi_square_homogeneity(&[5, 0], &[0, 0]).is_none());
+    }
+
+    #[test]
+    fn test_two_proportion_and_corrections() {
+        let z = two_proportion_z(50, 100, 70, 100).unwrap();
+        assert!(z.statistic > 2.0 && z.p_value < 0.01);
+        assert!(two_proportion_z(1, 0, 1, 1).is_none());
+        let p = [0.01, 0.04, 0.03, 0.5];
+        let close = |x: &[f64], y: &[f64]| x.iter().zip(y).all(|(a, b)| (a - b).abs() < 1e-9);
+        assert!(close(&holm(&p), &[0.04, 0.09, 0.09, 0.5]));
+        let third = 0.04 * 4.0 / 3.0;
+        assert!(close(&benjamini_hochberg(&p), &[0.04, third, third, 0.5]));
+    }
+
+    #[test]
+    fn test_range_tests() {
+        let mut a = ShapeStats {
+            n_txs: 100,
+            with_shielded: 20,
+            with_transparent: 90,
+            size_bucket_hist: [50, 50, 0, 0, 0, 0],
+            ..Default::default()
+        };
+        a.version_hist.insert(4, 100);
+        let mut b = a.clone();
+        b.with_shielded = 60;
+        b.size_bucket_hist = [90, 10, 0, 0, 0, 0];
+        let tests = range_tests(&a, &b);
+        // vin_vout and logical_actions are empty and version has a single bucket, so only
+        // size_bucket is tested, and there is no grace-action share without logical actions.
+        let subjects: Vec<&str> = tests.iter().map(|t| t.subject.as_str()).collect();
+        assert_eq!(
+            subjects,
+            ["size_bucket", "shielded_share", "transparent_share"]
+        );
+        assert_eq!(tests[0].test, "chi_square");
+        assert!(tests[0].significant && tests[1].significant);
+        assert!(!tests[2].significant);
+        assert!(tests.iter().all(|t| t.p_holm >= t.result.p_value));
+        let g = tests[0].g_test.as_ref().unwrap();
+        assert_eq!(g.result.df, Some(1));
+        assert!(g.significant && g.p_holm >= g.result.p_value);
+        assert!(tests[1..].iter().all(|t| t.g_test.is_none()));
+    }
+}
@@ -0,0 +1,146 @@
+//! Anomaly alerts: score the latest window against a trailing baseline of windows and the
+//! configured threshold rules.
+
+use super::{aggregate, ReportOptions};
+use crate::alert::{evaluate_rules, WindowAlerts};
+use crate::config::AlertsConfig;
+use crate::model::baseline::{Alert, Baseline, MetricBaseline, Thresholds, MIN_BASELINE_VALUES};
+use crate::storage;
+use rusqlite::Connection;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct AlertReport {
+    window_low: u32,
+    window_high: u32,
+    baseline_low: u32,
+    /// Non-empty windows in the baseline.
+    baseline_windows: usize,
+    metrics: Vec<MetricBaseline>,
+    /// Metrics and histograms not scored for lack of baseline values.
+    insufficient_baseline: Vec<String>,
+    alerts: Vec<Alert>,
+}
+
+/// Score the latest `cfg.window_blocks` blocks against the `cfg.baseline_windows` windows
+/// before them and against `cfg.rules`, and print the alerts. Each baseline metric and histogram
+/// is only scored when at least two baseline windows have data for it, so short histories and
+/// windows stored before a metric existed do not raise alerts.
+pub fn anomaly_alerts(
+    conn: &Connection,
+    cfg: &AlertsConfig,
+    opts: ReportOptions,
+) -> anyhow::Result<WindowAlerts> {
+    let heights = storage::block_heights_in_range(conn, 0, u32::MAX)?;
+    let Some(&max_h) = heights.last() else {
+        if opts.json {
+            println!(
+                "{}",
+                serde_json::json!({"error": "no block data in database"})
+            );
+        } else {
+            println!("No block data in database.");
+        }
+        return Ok(WindowAlerts {
+            window_low: 0,
+            window_high: 0,
+            alerts: Vec::new(),
+        });
+    };
+    let window_high = max_h.saturating_add(1);
+    let window_low = window_high.saturating_sub(cfg.window_blocks);
+    let latest = aggregate(conn, window_low, window_high, opts)?;
+    let mut windows = Vec::new();
+    let mut w_hi = window_low;
+    for _ in 0..cfg.baseline_windows {
+        if w_hi == 0 {
+            break;
+        }
+        let w_lo = w_hi.saturating_sub(cfg.window_blocks);
+        windows.push(aggregate(conn, w_lo, w_hi, opts)?);
+        w_hi = w_lo;
+    }
+    let baseline = Baseline::from_windows(&windows);
+    let thresholds = Thresholds {
+        z: cfg.z_threshold,
+        divergence: cfg.divergence_threshold,
+        bucket_min_share: cfg.bucket_min_share,
+    };
+    let mut alerts = Vec::new();
+    if latest.n_txs > 0 {
+        alerts.extend(evaluate_rules(&latest, &cfg.rules));
+        alerts.extend(baseline.score(&latest, thresholds));
+    }
+    let insufficient: Vec<String> = baseline
+        .insufficient()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let result = WindowAlerts {
+        window_low,
+        window_high,
+        alerts,
+    };
+
+    if opts.json {
+        let report = AlertReport {
+            window_low,
+            window_high,
+            baseline_low: w_hi,
+            baseline_windows: baseline.windows,
+            metrics: baseline.metrics,
+            insufficient_baseline: insufficient,
+            alerts: result.alerts.clone(),
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(result);
+    }
+    println!(
+        "--- Alerts for [{}, {}) vs baseline [{}, {}) ({} windows){} ---",
+        window_low,
+        window_high,
+        w_hi,
+        window_low,
+        baseline.windows,
+        super::floods_note(opts)
+    );
+    if baseline.windows < MIN_BASELINE_VALUES {
+        println!(
+            "Not enough history for a baseline (need at least {} non-empty windows).",
+            MIN_BASELINE_VALUES
+        );
+    } else if !insufficient.is_empty() {
+        println!(
+            "insufficient baseline (fewer than {} windows with data), not scored: {}",
+            MIN_BASELINE_VALUES,
+            insufficient.join(", ")
+        );
+    }
+    if latest.n_txs == 0 {
+        println!("Latest window has no transactions.");
+    }
+    if latest.n_txs > 0 && result.alerts.is_empty() {
+        println!("No alerts.");
+    }
+    for a in &result.alerts {
+        println!("{}", alert_line(a));
+    }
+    Ok(result)
+}
+
+fn alert_line(a: &Alert) -> String {
+    match a.kind.as_str() {
+        "divergence" => format!(
+            "ALERT divergence {}: JS={:.4} (threshold {:.4})",
+            a.subject, a.value, a.threshold
+        ),
+        "rule" => format!(
+            "ALERT rule {}: {:.4} beyond bound {:.4}",
+            a.subject, a.value, a.threshold
+        ),
+        _ => format!(
+            "ALERT {} {}: {:.4} vs baseline {:.4} (z={:+.2}, threshold {:.2})",
+            a.kind, a.subject, a.value, a.baseline, a.score, a.threshold
+        ),
+    }
+}
@@ -0,0 +1,128 @@
+//! Block composition report: block-level distributions (tx count, fullness, shielded share,
+//! interval) and their correlation with per-block shape diversity.
+
+use super::ReportOptions;
+use crate::model::block::{self, DiversityCorrelation, MAX_BLOCK_SIZE};
+use crate::storage;
+use crate::util::entropy;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Serialize)]
+struct BlocksReport {
+    low: u32,
+    high: u32,
+    n_blocks: u64,
+    mean_txs: f64,
+    sized_blocks: u64,
+    mean_fullness: Option<f64>,
+    max_fullness: Option<f64>,
+    mean_shielded_share: f64,
+    mean_interval_secs: Option<f64>,
+    distributions: Vec<Distribution>,
+    /// Correlation of each feature with the block's shape-tuple entropy (bits).
+    shape_entropy_correlation: Vec<DiversityCorrelation>,
+}
+
+#[derive(Serialize)]
+struct Distribution {
+    name: &'static str,
+    buckets: Vec<(String, u64)>,
+}
+
+/// Print block-level distributions of [low, high) and how block features correlate with the
+/// shape entropy of each block.
+pub fn block_composition(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    opts: ReportOptions,
+) -> anyhow::Result<()> {
+    let mut blocks = storage::block_meta_in_range(conn, low, high)?;
+    if opts.exclude_floods {
+        let flooded = storage::flagged_windows_in_range(conn, low, high)?;
+        blocks.retain(|b| {
+            !flooded
+                .iter()
+                .any(|w| w.low <= b.height && b.height < w.high)
+        });
+    }
+    // Rows written before shape tuples were collected have transactions but an empty shape
+    // histogram; their entropy is unknown, not zero.
+    let shape_entropy: HashMap<u32, f64> = storage::block_shape_hists_in_range(conn, low, high)?
+        .into_iter()
+        .filter(|(_, n_txs, hist)| *n_txs == 0 || !hist.is_empty())
+        .map(|(height, _, hist)| (height, entropy(&hist.into_values().collect::<Vec<_>>())))
+        .collect();
+    let composition = block::compose(&blocks);
+    let correlations = block::diversity_correlations(&blocks, &shape_entropy);
+
+    if opts.json {
+        let report = BlocksReport {
+            low,
+            high,
+            n_blocks: composition.n_blocks,
+            mean_txs: composition.mean_txs,
+            sized_blocks: composition.sized_blocks,
+            mean_fullness: composition.mean_fullness,
+            max_fullness: composition.max_fullness,
+            mean_shielded_share: composition.mean_shielded_share,
+            mean_interval_secs: composition.mean_interval_secs,
+            distributions: composition
+                .histograms
+                .into_iter()
+                .map(|h| Distribution {
+                    name: h.name,
+                    buckets: h.buckets,
+                })
+                .collect(),
+            shape_entropy_correlation: correlations,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    println!(
+        "--- Block composition in [{}, {}) ({} blocks){} ---",
+        low,
+        high,
+        composition.n_blocks,
+        super::floods_note(opts)
+    );
+    if blocks.is_empty() {
+        println!("No block-level data stored for this range (collect it first).");
+        return Ok(());
+    }
+    println!("mean_txs: {:.2}", composition.mean_txs);
+    match (composition.mean_fullness, composition.max_fullness) {
+        (Some(mean), Some(max)) => println!(
+            "fullness (of {} bytes, {} sized blocks): mean {:.4}, max {:.4}",
+            MAX_BLOCK_SIZE, composition.sized_blocks, mean, max
+        ),
+        _ => println!("fullness: n/a (no block sizes reported)"),
+    }
+    println!(
+        "mean_shielded_share: {:.4}",
+        composition.mean_shielded_share
+    );
+    match composition.mean_interval_secs {
+        Some(secs) => println!("mean_interval: {:.1}s", secs),
+        None => println!("mean_interval: n/a"),
+    }
+    for h in &composition.histograms {
+        let buckets: Vec<String> = h
+            .buckets
+            .iter()
+            .map(|(label, n)| format!("{}={}", label, n))
+            .collect();
+        println!("{}: {}", h.name, buckets.join(" "));
+    }
+    println!("correlation with per-block shape entropy (Pearson):");
+    for c in &correlations {
+        match c.pearson {
+            Some(r) => println!("  {}: {:+.4} (n={})", c.feature, r, c.n),
+            None => println!("  {}: n/a (n={})", c.feature, c.n),
+        }
+    }
+    Ok(())
+}
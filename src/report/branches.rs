@@ -0,0 +1,145 @@
+//! Consensus branch report: version groups, branch ids, and older version groups still mined
+//! after a network upgrade introduced a newer one.
+
+use super::{aggregate, ReportOptions};
+use crate::model::format::{Network, VersionGroup};
+use crate::storage;
+use rusqlite::Connection;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct BranchReport {
+    low: u32,
+    high: u32,
+    n_txs: u64,
+    version_group: Vec<(String, u64)>,
+    consensus_branch: Vec<(String, u64)>,
+    lagging_txs: u64,
+    /// Share of transactions whose version group lagged behind the newest active one.
+    lagging_share: f64,
+    lagging: Vec<LaggingGroup>,
+}
+
+/// Transactions of one version group mined after a newer group was active, and the blocks
+/// they were mined in.
+#[derive(Debug, Clone, Serialize)]
+pub struct LaggingGroup {
+    pub version_group: String,
+    pub txs: u64,
+    pub blocks: u64,
+    pub first_height: u32,
+    pub last_height: u32,
+}
+
+/// Print version group and consensus branch counts of [low, high), and every version group
+/// mined after a newer one activated on `network` (e.g. v4 after NU5), which points at
+/// outdated wallets still broadcasting. Returns the lagging groups, most transactions first.
+///
+/// Branch ids are shown as counted only: consensus requires v5+ transactions to commit to the
+/// active branch, so an outdated branch id can only be seen in mempool data, never in blocks.
+pub fn version_lag(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    network: Network,
+    opts: ReportOptions,
+) -> anyhow::Result<Vec<LaggingGroup>> {
+    let stats = aggregate(conn, low, high, opts)?;
+    let views = stats.histograms();
+    let view = |name: &str| -> Vec<(String, u64)> {
+        views
+            .iter()
+            .find(|h| h.name == name)
+            .map(|h| h.buckets.iter().filter(|b| b.1 > 0).cloned().collect())
+            .unwrap_or_default()
+    };
+    let version_group = view("version_group");
+    let consensus_branch = view("consensus_branch");
+
+    let flooded = if opts.exclude_floods {
+        storage::flagged_windows_in_range(conn, low, high)?
+    } else {
+        Vec::new()
+    };
+    let mut total = 0u64;
+    let mut lagging: Vec<LaggingGroup> = Vec::new();
+    for (height, hist) in storage::block_version_group_hists_in_range(conn, low, high)? {
+        if flooded.iter().any(|w| w.low <= height && height < w.high) {
+            continue;
+        }
+        total += hist.values().sum::<u64>();
+        for (label, txs) in hist {
+            let lags = VersionGroup::from_label(&label).is_some_and(|g| g.lags_at(height, network));
+            if !lags {
+                continue;
+            }
+            match lagging.iter_mut().find(|l| l.version_group == label) {
+                Some(l) => {
+                    l.txs += txs;
+                    l.blocks += 1;
+                    l.last_height = height;
+                }
+                None => lagging.push(LaggingGroup {
+                    version_group: label,
+                    txs,
+                    blocks: 1,
+                    first_height: height,
+                    last_height: height,
+                }),
+            }
+        }
+    }
+    lagging.sort_by(|a, b| {
+        b.txs
+            .cmp(&a.txs)
+            .then_with(|| a.version_group.cmp(&b.version_group))
+    });
+    let lagging_txs: u64 = lagging.iter().map(|l| l.txs).sum();
+    let lagging_share = if total > 0 {
+        lagging_txs as f64 / total as f64
+    } else {
+        0.0
+    };
+
+    if opts.json {
+        let report = BranchReport {
+            low,
+            high,
+            n_txs: stats.n_txs,
+            version_group,
+            consensus_branch,
+            lagging_txs,
+            lagging_share,
+            lagging,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(report.lagging);
+    }
+    println!(
+        "--- Consensus branches in [{}, {}) ({} txs){} ---",
+        low,
+        high,
+        stats.n_txs,
+        super::floods_note(opts)
+    );
+    let line = |buckets: &[(String, u64)]| {
+        buckets
+            .iter()
+            .map(|(label, n)| format!("{}={}", label, n))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    println!("version_group: {}", line(&version_group));
+    println!("consensus_branch: {}", line(&consensus_branch));
+    println!(
+        "lagging version groups: {} of {} txs ({:.4})",
+        lagging_txs, total, lagging_share
+    );
+    for l in &lagging {
+        println!(
+            "  {}: {} txs in {} blocks (heights {}-{})",
+            l.version_group, l.txs, l.blocks, l.first_height, l.last_height
+        );
+    }
+    Ok(lagging)
+}
@@ -0,0 +1,145 @@
+//! Change-point report: regime shifts in per-window shape metrics.
+
+use super::{for_each_block, ReportOptions};
+use crate::model::changepoint::{bocpd, cusum, ChangePoint};
+use crate::model::metrics::top_k_share;
+use crate::model::ShapeStats;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Named per-window metric; `None` when the window has no data for it.
+type WindowMetric = (&'static str, fn(&ShapeStats) -> Option<f64>);
+
+/// Metrics tracked per window. Windows without transactions are skipped, and so are windows
+/// without data for one metric (e.g. stored before it was recorded) in that metric's series.
+const METRICS: [WindowMetric; 4] = [
+    ("shielded_share", |s| {
+        Some(s.with_shielded as f64 / s.n_txs as f64)
+    }),
+    ("size_entropy", |s| Some(s.size_entropy)),
+    ("grace_action_share", ShapeStats::grace_action_share),
+    ("top_shape_share", |s| {
+        // Full shape tuples; windows stored before they were recorded have none.
+        let counts: Vec<u64> = s.shape_hist.values().copied().collect();
+        (!counts.is_empty()).then(|| top_k_share(&counts, 1))
+    }),
+];
+
+#[derive(Serialize)]
+struct ChangePointReport {
+    low: u32,
+    high: u32,
+    window_blocks: u32,
+    windows: usize,
+    candidates: Vec<ChangeCandidate>,
+}
+
+/// One detected regime shift of one metric.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeCandidate {
+    pub metric: String,
+    /// "cusum" or "bocpd".
+    pub method: String,
+    /// First height of the window where the new regime starts.
+    pub height: u32,
+    pub confidence: f64,
+    /// Metric mean over the segment before the change (up to the previous change).
+    pub mean_before: f64,
+    /// Metric mean over the segment after the change (up to the next change).
+    pub mean_after: f64,
+}
+
+/// Run CUSUM and BOCPD over per-window metrics in [low, high), print candidate heights with
+/// confidence ≥ `min_confidence` and return them, ordered by height.
+pub fn change_points(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    window: u32,
+    min_confidence: f64,
+    opts: ReportOptions,
+) -> anyhow::Result<Vec<ChangeCandidate>> {
+    let window = window.max(1);
+    // Accumulated stats per window, keyed by window index from `low`: coinbase rows arrive
+    // after all user rows, so a window cannot be closed while streaming.
+    let mut acc: BTreeMap<u32, ShapeStats> = BTreeMap::new();
+    for_each_block(conn, low, high, opts, |height, stats| {
+        *acc.entry((height - low) / window).or_default() += stats;
+    })?;
+
+    let mut windows = 0usize;
+    // Per metric: (window start, value) of the windows with data for it.
+    let mut series: Vec<Vec<(u32, f64)>> = vec![Vec::new(); METRICS.len()];
+    for (i, stats) in &acc {
+        if stats.n_txs == 0 {
+            continue;
+        }
+        windows += 1;
+        let w_lo = low + i * window;
+        for ((_, metric), points) in METRICS.iter().zip(series.iter_mut()) {
+            if let Some(value) = metric(stats) {
+                points.push((w_lo, value));
+            }
+        }
+    }
+
+    let mut candidates = Vec::new();
+    for ((name, _), points) in METRICS.iter().zip(&series) {
+        let values: Vec<f64> = points.iter().map(|(_, v)| *v).collect();
+        let detected: [(&str, Vec<ChangePoint>); 2] = [
+            ("cusum", cusum(&values, min_confidence)),
+            ("bocpd", bocpd(&values, min_confidence)),
+        ];
+        for (method, cps) in detected {
+            let bounds: Vec<usize> = std::iter::once(0)
+                .chain(cps.iter().map(|c| c.index))
+                .chain(std::iter::once(values.len()))
+                .collect();
+            for (i, cp) in cps.iter().enumerate() {
+                candidates.push(ChangeCandidate {
+                    metric: name.to_string(),
+                    method: method.to_string(),
+                    height: points[cp.index].0,
+                    confidence: cp.confidence,
+                    mean_before: mean(&values[bounds[i]..bounds[i + 1]]),
+                    mean_after: mean(&values[bounds[i + 1]..bounds[i + 2]]),
+                });
+            }
+        }
+    }
+    candidates.sort_by_key(|c| c.height);
+
+    if opts.json {
+        let report = ChangePointReport {
+            low,
+            high,
+            window_blocks: window,
+            windows,
+            candidates,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(report.candidates);
+    }
+    println!(
+        "--- Change points in [{}, {}) ({} windows of {} blocks) ---",
+        low, high, windows, window
+    );
+    if candidates.is_empty() {
+        println!("No change points with confidence >= {:.2}.", min_confidence);
+    }
+    for c in &candidates {
+        println!(
+            "height {}: {} ({}) confidence={:.3} mean {:.4} -> {:.4}",
+            c.height, c.metric, c.method, c.confidence, c.mean_before, c.mean_after
+        );
+    }
+    Ok(candidates)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
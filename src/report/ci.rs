@@ -0,0 +1,145 @@
+//! CI gating: export a baseline distribution file and check wallet fixture shapes against it.
+
+use super::{aggregate, ReportOptions};
+use crate::model::score::{BaselineFile, ReferenceDistribution, ShapeScore};
+use crate::model::TxShape;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::Path;
+
+/// Commonness thresholds a fixture shape must meet.
+#[derive(Debug, Clone, Copy)]
+pub struct CiThresholds {
+    /// Minimum share of baseline transactions with the identical shape.
+    pub min_share: f64,
+    /// Maximum rank of the shape in the baseline (1 = most common), if set.
+    pub max_rank: Option<usize>,
+}
+
+/// Result of one fixture shape.
+#[derive(Debug, Clone, Serialize)]
+pub struct CiCase {
+    pub name: String,
+    #[serde(flatten)]
+    pub score: ShapeScore,
+    pub passed: bool,
+    pub failure: Option<String>,
+}
+
+/// Result of a CI check run.
+#[derive(Debug, Clone, Serialize)]
+pub struct CiResult {
+    pub baseline_low: u32,
+    pub baseline_high: u32,
+    pub min_share: f64,
+    pub max_rank: Option<usize>,
+    pub tests: usize,
+    pub failures: usize,
+    pub cases: Vec<CiCase>,
+}
+
+/// Write the shape distribution of [low, high) to `path` as a baseline file.
+pub fn export_baseline(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    opts: ReportOptions,
+    path: &Path,
+) -> anyhow::Result<BaselineFile> {
+    let stats = aggregate(conn, low, high, opts)?;
+    if stats.n_txs == 0 {
+        anyhow::bail!("no shape data in [{}, {}) (collect it first)", low, high);
+    }
+    if stats.shape_hist.is_empty() {
+        anyhow::bail!(
+            "no full-shape histogram in [{}, {}) (blocks were collected before it existed; re-collect them)",
+            low,
+            high
+        );
+    }
+    let file = BaselineFile::new(low, high, ReferenceDistribution::from_stats(&stats));
+    std::fs::write(path, serde_json::to_string_pretty(&file)? + "\n")?;
+    Ok(file)
+}
+
+/// Check every fixture shape against the baseline.
+pub fn ci_check(fixtures: &[TxShape], baseline: &BaselineFile, t: CiThresholds) -> CiResult {
+    let cases: Vec<CiCase> = baseline
+        .distribution
+        .score(fixtures)
+        .into_iter()
+        .enumerate()
+        .map(|(i, score)| {
+            let failure = if score.share < t.min_share {
+                Some(format!(
+                    "share {:.6} below min_share {} ({} of {} baseline txs)",
+                    score.share, t.min_share, score.count, baseline.distribution.total
+                ))
+            } else {
+                match (t.max_rank, score.rank) {
+                    (Some(max), Some(rank)) if rank > max => {
+                        Some(format!("rank {} above max_rank {}", rank, max))
+                    }
+                    (Some(max), None) => {
+                        Some(format!("shape unseen in baseline (max_rank {})", max))
+                    }
+                    _ => None,
+                }
+            };
+            CiCase {
+                name: format!("tx[{}] {}", i, score.shape),
+                score,
+                passed: failure.is_none(),
+                failure,
+            }
+        })
+        .collect();
+    CiResult {
+        baseline_low: baseline.low,
+        baseline_high: baseline.high,
+        min_share: t.min_share,
+        max_rank: t.max_rank,
+        tests: cases.len(),
+        failures: cases.iter().filter(|c| !c.passed).count(),
+        cases,
+    }
+}
+
+/// Render a CI result as a JUnit XML test suite.
+pub fn junit_xml(result: &CiResult) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuite name=\"zcash-txshape\" tests=\"{}\" failures=\"{}\">\n",
+        result.tests, result.failures
+    ));
+    for c in &result.cases {
+        out.push_str(&format!(
+            "  <testcase classname=\"zcash-txshape.shape\" name=\"{}\">\n",
+            xml_escape(&c.name)
+        ));
+        out.push_str(&format!(
+            "    <system-out>surprisal={:.2} bits share={:.6} count={} rank={}</system-out>\n",
+            c.score.surprisal_bits,
+            c.score.share,
+            c.score.count,
+            c.score
+                .rank
+                .map(|r| r.to_string())
+                .unwrap_or_else(|| "unseen".to_string())
+        ));
+        if let Some(f) = &c.failure {
+            out.push_str(&format!("    <failure message=\"{}\"/>\n", xml_escape(f)));
+        }
+        out.push_str("  </testcase>\n");
+    }
+    out.push_str("</testsuite>\n");
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,163 @@
+//! N-way comparison of labeled block ranges: side-by-side summary and pairwise divergence matrices.
+
+use super::{aggregate, share_text, ReportOptions};
+use crate::model::divergence::{align, Divergence};
+use crate::model::metrics::DiversityMetrics;
+use crate::model::ShapeStats;
+use crate::storage;
+use rusqlite::Connection;
+use serde::Serialize;
+
+/// A block range [low, high) with a human-readable label (e.g. "post-nu5").
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabeledRange {
+    pub label: String,
+    pub low: u32,
+    pub high: u32,
+}
+
+#[derive(Serialize)]
+struct CompareReport {
+    ranges: Vec<RangeSummary>,
+    matrices: Vec<DivergenceMatrix>,
+}
+
+#[derive(Serialize)]
+struct RangeSummary {
+    label: String,
+    low: u32,
+    high: u32,
+    blocks: usize,
+    n_txs: u64,
+    shielded_share: f64,
+    transparent_share: f64,
+    size_entropy: f64,
+    /// Share of transactions within the ZIP-317 grace actions (of those with a recorded
+    /// logical-action count; null when none has one).
+    grace_action_share: Option<f64>,
+    /// Effective number of (vin, vout) shapes.
+    vin_vout_effective_shapes: f64,
+}
+
+/// Pairwise Jensen–Shannon divergence (bits) of one histogram; `values[i][j]` compares range i with j.
+#[derive(Serialize)]
+struct DivergenceMatrix {
+    histogram: String,
+    labels: Vec<String>,
+    values: Vec<Vec<f64>>,
+}
+
+/// Compare any number of labeled ranges using the per-block stats stored for each.
+pub fn range_compare(
+    conn: &Connection,
+    ranges: &[LabeledRange],
+    opts: ReportOptions,
+) -> anyhow::Result<()> {
+    let mut all_stats: Vec<ShapeStats> = Vec::with_capacity(ranges.len());
+    let mut summaries = Vec::with_capacity(ranges.len());
+    for r in ranges {
+        let blocks = storage::block_heights_in_range(conn, r.low, r.high)?.len();
+        let stats = aggregate(conn, r.low, r.high, opts)?;
+        // Only this metric is needed; `diversity()` would also bootstrap every histogram.
+        let vin_vout: Vec<u64> = stats.vin_vout_hist.values().copied().collect();
+        let vin_vout_effective_shapes = DiversityMetrics::from_counts(&vin_vout).effective_shapes;
+        summaries.push(RangeSummary {
+            label: r.label.clone(),
+            low: r.low,
+            high: r.high,
+            blocks,
+            n_txs: stats.n_txs,
+            shielded_share: share(stats.with_shielded, stats.n_txs),
+            transparent_share: share(stats.with_transparent, stats.n_txs),
+            size_entropy: stats.size_entropy,
+            grace_action_share: stats.grace_action_share(),
+            vin_vout_effective_shapes,
+        });
+        all_stats.push(stats);
+    }
+    let matrices = divergence_matrices(ranges, &all_stats);
+
+    if opts.json {
+        let report = CompareReport {
+            ranges: summaries,
+            matrices,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let width = ranges
+        .iter()
+        .map(|r| r.label.len())
+        .max()
+        .unwrap_or(0)
+        .max(8);
+    println!(
+        "{:<width$}  {:>21}  {:>7}  {:>10}  {:>8}  {:>8}  {:>8}  {:>8}  {:>9}",
+        "label", "range", "blocks", "n_txs", "shielded", "transp", "size_H", "grace", "vv_eff",
+    );
+    for s in &summaries {
+        println!(
+            "{:<width$}  {:>21}  {:>7}  {:>10}  {:>8.4}  {:>8.4}  {:>8.4}  {:>8}  {:>9.2}",
+            s.label,
+            format!("[{}, {})", s.low, s.high),
+            s.blocks,
+            s.n_txs,
+            s.shielded_share,
+            s.transparent_share,
+            s.size_entropy,
+            share_text(s.grace_action_share),
+            s.vin_vout_effective_shapes,
+        );
+    }
+    for m in &matrices {
+        println!();
+        println!("Jensen-Shannon divergence: {}", m.histogram);
+        print!("{:<width$}", "");
+        for label in &m.labels {
+            print!("  {:>width$}", label);
+        }
+        println!();
+        for (label, row) in m.labels.iter().zip(&m.values) {
+            print!("{:<width$}", label);
+            for v in row {
+                print!("  {:>width$.4}", v);
+            }
+            println!();
+        }
+    }
+    Ok(())
+}
+
+fn divergence_matrices(ranges: &[LabeledRange], stats: &[ShapeStats]) -> Vec<DivergenceMatrix> {
+    let histograms: Vec<_> = stats.iter().map(|s| s.histograms()).collect();
+    let labels: Vec<String> = ranges.iter().map(|r| r.label.clone()).collect();
+    let n_hist = histograms.first().map(|h| h.len()).unwrap_or(0);
+    (0..n_hist)
+        .map(|k| {
+            let mut values = vec![vec![0.0; ranges.len()]; ranges.len()];
+            for i in 0..ranges.len() {
+                for j in (i + 1)..ranges.len() {
+                    let (hi, hj) = (&histograms[i][k], &histograms[j][k]);
+                    let aligned = align(hi, hj);
+                    let js = Divergence::between(&aligned.a, &aligned.b, hi.ordered).jensen_shannon;
+                    values[i][j] = js;
+                    values[j][i] = js;
+                }
+            }
+            DivergenceMatrix {
+                histogram: histograms[0][k].name.to_string(),
+                labels: labels.clone(),
+                values,
+            }
+        })
+        .collect()
+}
+
+fn share(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
@@ -0,0 +1,37 @@
+//! Flood windows report: print the stored windows dominated by one shape tuple
+//! (flagged by `storage::flag_floods`).
+
+use super::ReportOptions;
+use crate::config::FloodConfig;
+use crate::storage;
+use rusqlite::Connection;
+
+/// Print the stored flood windows overlapping [low, high); `cfg` holds the thresholds they
+/// were flagged with.
+pub fn flood_windows(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    cfg: &FloodConfig,
+    opts: ReportOptions,
+) -> anyhow::Result<()> {
+    let windows = storage::flagged_windows_in_range(conn, low, high)?;
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&windows)?);
+        return Ok(());
+    }
+    println!(
+        "--- Flood windows in [{}, {}) (window={} blocks, min_share={}, min_count={}) ---",
+        low, high, cfg.window_blocks, cfg.min_share, cfg.min_count
+    );
+    if windows.is_empty() {
+        println!("No flood windows detected.");
+    }
+    for w in &windows {
+        println!(
+            "[{}, {}): shape {} {}/{} txs (share {:.4})",
+            w.low, w.high, w.shape, w.shape_count, w.n_txs, w.share
+        );
+    }
+    Ok(())
+}
@@ -1,10 +1,94 @@
-//! Reporting: daily/weekly summaries and range diffs.
+//! Reporting: daily/weekly summaries, range diffs, N-way range comparisons, change points,
+//! consensus branches, block composition, dimension mutual information, shape flood windows,
+//! anomaly alerts, wallet shape scores, CI gating and padding what-ifs.
 
-use crate::model::ShapeStats;
+mod alerts;
+mod blocks;
+mod branches;
+mod changepoints;
+mod ci;
+mod compare;
+mod floods;
+mod mutual_info;
+mod padding;
+mod score;
+
+pub use alerts::anomaly_alerts;
+pub use blocks::block_composition;
+pub use branches::{version_lag, LaggingGroup};
+pub use changepoints::{change_points, ChangeCandidate};
+pub use ci::{ci_check, export_baseline, junit_xml, CiCase, CiResult, CiThresholds};
+pub use compare::{range_compare, LabeledRange};
+pub use floods::flood_windows;
+pub use mutual_info::mutual_information;
+pub use padding::simulate_padding;
+pub use score::score_shapes;
+
+use crate::model::divergence::{
+    histogram_bucket_deltas, histogram_divergences, BucketDelta, BucketSort, HistogramBucketDeltas,
+    HistogramDivergence,
+};
+use crate::model::entropy::CONFIDENCE_LEVEL;
+use crate::model::metrics::TOP_K;
+use crate::model::significance::{range_tests, SignificanceTest, ALPHA};
+use crate::model::{HistogramDiversity, ShapeStats, ZIP317_GRACE_ACTIONS};
 use crate::storage;
 use rusqlite::Connection;
 use serde::Serialize;
 
+/// Options shared by all reports.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReportOptions {
+    /// Print JSON instead of text.
+    pub json: bool,
+    /// Skip blocks inside flood windows stored by `detect-floods`.
+    pub exclude_floods: bool,
+    /// Count coinbase transactions as user transactions.
+    pub include_coinbase: bool,
+}
+
+impl ReportOptions {
+    fn selection(self) -> storage::BlockSelection {
+        storage::BlockSelection {
+            exclude_floods: self.exclude_floods,
+            include_coinbase: self.include_coinbase,
+        }
+    }
+}
+
+/// Aggregate per-block stats in [low, high), honoring `opts.exclude_floods` and
+/// `opts.include_coinbase`.
+fn aggregate(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    opts: ReportOptions,
+) -> anyhow::Result<ShapeStats> {
+    storage::aggregate_blocks(conn, low, high, opts.selection())
+}
+
+/// Call `f` with the stats of each block in [low, high), honoring `opts.exclude_floods` and
+/// `opts.include_coinbase` (coinbase stats come after all user stats; see
+/// `storage::for_each_block`).
+fn for_each_block(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    opts: ReportOptions,
+    f: impl FnMut(u32, ShapeStats),
+) -> anyhow::Result<()> {
+    storage::for_each_block(conn, low, high, opts.selection(), f)
+}
+
+/// The last `blocks` stored blocks as [low, high), or `None` when the database is empty.
+pub fn recent_range(conn: &Connection, blocks: u32) -> anyhow::Result<Option<(u32, u32)>> {
+    let heights = storage::block_heights_in_range(conn, 0, u32::MAX)?;
+    Ok(heights.last().map(|&max_h| {
+        let high = max_h.saturating_add(1);
+        (high.saturating_sub(blocks), high)
+    }))
+}
+
 #[derive(Serialize)]
 struct SummaryReport {
     title: String,
@@ -14,7 +98,16 @@ struct SummaryReport {
     with_transparent: u64,
     with_shielded: u64,
     size_entropy: f64,
+    /// Share of transactions within the ZIP-317 grace actions, of those with a recorded
+    /// logical-action count (null when none has one).
+    grace_action_share: Option<f64>,
+    /// Coinbase transactions (not in n_txs unless --include-coinbase was given).
+    coinbase_txs: u64,
     version_hist: std::collections::HashMap<u32, u64>,
+    /// Transactions missing from per-transaction histograms, by histogram (blocks stored
+    /// before the histogram existed).
+    uncovered_txs: std::collections::BTreeMap<&'static str, u64>,
+    diversity: Vec<HistogramDiversity>,
 }
 
 #[derive(Serialize)]
@@ -25,6 +118,23 @@ struct DiffReport {
     with_transparent_delta: i64,
     with_shielded_delta: i64,
     size_entropy_delta: f64,
+    diversity_delta: Vec<DiversityDelta>,
+    divergence: Vec<HistogramDivergence>,
+    significance: Vec<SignificanceTest>,
+    buckets: Vec<HistogramBucketDeltas>,
+}
+
+/// Change (B minus A) of the headline diversity metrics of one histogram.
+#[derive(Serialize)]
+struct DiversityDelta {
+    histogram: String,
+    normalized_entropy_delta: f64,
+    gini_simpson_delta: f64,
+    effective_shapes_delta: f64,
+    top_k_share_delta: f64,
+    chao_shen_delta: f64,
+    /// True when the A and B entropy confidence intervals do not overlap.
+    entropy_change_significant: bool,
 }
 
 #[derive(Serialize)]
@@ -35,13 +145,14 @@ struct RangeStats {
     with_transparent: u64,
     with_shielded: u64,
     size_entropy: f64,
+    diversity: Vec<HistogramDiversity>,
 }
 
-pub fn daily_summary(conn: &Connection, days: u32, json: bool) -> anyhow::Result<()> {
+pub fn daily_summary(conn: &Connection, days: u32, opts: ReportOptions) -> anyhow::Result<()> {
     let heights = storage::block_heights_in_range(conn, 0, u32::MAX)?;
     let max_h = heights.last().copied().unwrap_or(0);
     if max_h == 0 {
-        if json {
+        if opts.json {
             println!(
                 "{}",
                 serde_json::json!({"error": "no block data in database"})
@@ -53,9 +164,15 @@ pub fn daily_summary(conn: &Connection, days: u32, json: bool) -> anyhow::Result
     }
     let blocks_per_day = 24 * 6;
     let start = max_h.saturating_sub(days * blocks_per_day);
-    let stats = storage::aggregate_block_stats_in_range(conn, start, max_h)?;
-    let title = format!("Last {} days (heights {}-{})", days, start, max_h);
-    if json {
+    let stats = aggregate(conn, start, max_h, opts)?;
+    let title = format!(
+        "Last {} days (heights {}-{}){}",
+        days,
+        start,
+        max_h,
+        floods_note(opts)
+    );
+    if opts.json {
         let report = SummaryReport {
             title: title.clone(),
             height_start: start,
@@ -64,7 +181,11 @@ pub fn daily_summary(conn: &Connection, days: u32, json: bool) -> anyhow::Result
             with_transparent: stats.with_transparent,
             with_shielded: stats.with_shielded,
             size_entropy: stats.size_entropy,
+            grace_action_share: stats.grace_action_share(),
+            coinbase_txs: stats.coinbase_txs(),
             version_hist: stats.version_hist.clone(),
+            uncovered_txs: stats.uncovered_txs().into_iter().collect(),
+            diversity: stats.diversity(),
         };
         println!("{}", serde_json::to_string_pretty(&report)?);
     } else {
@@ -73,11 +194,11 @@ pub fn daily_summary(conn: &Connection, days: u32, json: bool) -> anyhow::Result
     Ok(())
 }
 
-pub fn weekly_summary(conn: &Connection, json: bool) -> anyhow::Result<()> {
+pub fn weekly_summary(conn: &Connection, opts: ReportOptions) -> anyhow::Result<()> {
     let heights = storage::block_heights_in_range(conn, 0, u32::MAX)?;
     let max_h = heights.last().copied().unwrap_or(0);
     if max_h == 0 {
-        if json {
+        if opts.json {
             println!(
                 "{}",
                 serde_json::json!({"error": "no block data in database"})
@@ -89,9 +210,14 @@ pub fn weekly_summary(conn: &Connection, json: bool) -> anyhow::Result<()> {
     }
     const BLOCKS_PER_WEEK: u32 = 7 * 24 * 6;
     let start = max_h.saturating_sub(BLOCKS_PER_WEEK);
-    let stats = storage::aggregate_block_stats_in_range(conn, start, max_h)?;
-    let title = format!("Last week (heights {}-{})", start, max_h);
-    if json {
+    let stats = aggregate(conn, start, max_h, opts)?;
+    let title = format!(
+        "Last week (heights {}-{}){}",
+        start,
+        max_h,
+        floods_note(opts)
+    );
+    if opts.json {
         let report = SummaryReport {
             title: title.clone(),
             height_start: start,
@@ -100,7 +226,11 @@ pub fn weekly_summary(conn: &Connection, json: bool) -> anyhow::Result<()> {
             with_transparent: stats.with_transparent,
             with_shielded: stats.with_shielded,
             size_entropy: stats.size_entropy,
+            grace_action_share: stats.grace_action_share(),
+            coinbase_txs: stats.coinbase_txs(),
             version_hist: stats.version_hist.clone(),
+            uncovered_txs: stats.uncovered_txs().into_iter().collect(),
+            diversity: stats.diversity(),
         };
         println!("{}", serde_json::to_string_pretty(&report)?);
     } else {
@@ -109,28 +239,46 @@ pub fn weekly_summary(conn: &Connection, json: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Per-bucket deltas shown by `range_diff`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BucketView {
+    /// Buckets listed per histogram in text output (JSON always contains every bucket).
+    pub top: usize,
+    /// Order of the buckets in both outputs.
+    pub sort: BucketSort,
+}
+
+/// Compare range A with range B. Text output lists the first `view.top` per-bucket changes
+/// of each histogram in `view.sort` order ("top movers").
 pub fn range_diff(
     conn: &Connection,
     a_lo: u32,
     a_hi: u32,
     b_lo: u32,
     b_hi: u32,
-    json: bool,
+    view: BucketView,
+    opts: ReportOptions,
 ) -> anyhow::Result<()> {
     let blocks_a = storage::block_heights_in_range(conn, a_lo, a_hi)?;
     let blocks_b = storage::block_heights_in_range(conn, b_lo, b_hi)?;
     let has_data_a = !blocks_a.is_empty();
     let has_data_b = !blocks_b.is_empty();
 
-    let stats_a = storage::aggregate_block_stats_in_range(conn, a_lo, a_hi)?;
-    let stats_b = storage::aggregate_block_stats_in_range(conn, b_lo, b_hi)?;
+    let stats_a = aggregate(conn, a_lo, a_hi, opts)?;
+    let stats_b = aggregate(conn, b_lo, b_hi, opts)?;
 
     let n_txs_delta = stats_b.n_txs as i64 - stats_a.n_txs as i64;
     let with_transparent_delta = stats_b.with_transparent as i64 - stats_a.with_transparent as i64;
     let with_shielded_delta = stats_b.with_shielded as i64 - stats_a.with_shielded as i64;
     let size_entropy_delta = stats_b.size_entropy - stats_a.size_entropy;
+    let diversity_a = stats_a.diversity();
+    let diversity_b = stats_b.diversity();
+    let diversity_delta = diversity_deltas(&diversity_a, &diversity_b);
+    let divergence = histogram_divergences(&stats_a.histograms(), &stats_b.histograms());
+    let significance = range_tests(&stats_a, &stats_b);
+    let buckets = histogram_bucket_deltas(&stats_a.histograms(), &stats_b.histograms(), view.sort);
 
-    if json {
+    if opts.json {
         let report = DiffReport {
             range_a: RangeStats {
                 low: a_lo,
@@ -139,6 +287,7 @@ pub fn range_diff(
                 with_transparent: stats_a.with_transparent,
                 with_shielded: stats_a.with_shielded,
                 size_entropy: stats_a.size_entropy,
+                diversity: diversity_a,
             },
             range_b: RangeStats {
                 low: b_lo,
@@ -147,11 +296,16 @@ pub fn range_diff(
                 with_transparent: stats_b.with_transparent,
                 with_shielded: stats_b.with_shielded,
                 size_entropy: stats_b.size_entropy,
+                diversity: diversity_b,
             },
             n_txs_delta,
             with_transparent_delta,
             with_shielded_delta,
             size_entropy_delta,
+            diversity_delta,
+            divergence,
+            significance,
+            buckets,
         };
         println!("{}", serde_json::to_string_pretty(&report)?);
     } else {
@@ -182,6 +336,40 @@ pub fn range_diff(
                 "Diff: n_txs delta={}, with_transparent delta={}, with_shielded delta={}, size_entropy delta={:.4}",
                 n_txs_delta, with_transparent_delta, with_shielded_delta, size_entropy_delta
             );
+            println!("diversity (A -> B):");
+            for ((a, b), d) in diversity_a.iter().zip(&diversity_b).zip(&diversity_delta) {
+                println!("  {} A: {}", a.histogram, diversity_line(a));
+                println!("  {} B: {}", b.histogram, diversity_line(b));
+                println!(
+                    "  {} delta: H_norm={:+.4} gini_simpson={:+.4} effective={:+.2} top{}={:+.4} H_cs={:+.4} ({})",
+                    d.histogram,
+                    d.normalized_entropy_delta,
+                    d.gini_simpson_delta,
+                    d.effective_shapes_delta,
+                    TOP_K,
+                    d.top_k_share_delta,
+                    d.chao_shen_delta,
+                    if d.entropy_change_significant {
+                        "intervals disjoint"
+                    } else {
+                        "within noise"
+                    }
+                );
+            }
+            println!("divergence (A vs B):");
+            for d in &divergence {
+                println!("  {}: {}", d.histogram, divergence_line(d));
+            }
+            println!("significance (Holm-corrected, alpha={}):", ALPHA);
+            for t in &significance {
+                println!("  {}", significance_line(t));
+            }
+            println!("top movers (by {}):", view.sort.label());
+            for h in &buckets {
+                for d in h.buckets.iter().take(view.top) {
+                    println!("  {} {}", h.histogram, bucket_delta_line(d));
+                }
+            }
         } else {
             println!("Diff: no data to compare (collect block data for both ranges first).");
         }
@@ -189,11 +377,134 @@ pub fn range_diff(
     Ok(())
 }
 
+fn floods_note(opts: ReportOptions) -> &'static str {
+    if opts.exclude_floods {
+        ", flood windows excluded"
+    } else {
+        ""
+    }
+}
+
+/// A share with four decimals, or "n/a" when there was nothing to divide by.
+fn share_text(share: Option<f64>) -> String {
+    share.map_or_else(|| "n/a".to_string(), |v| format!("{:.4}", v))
+}
+
 fn print_stats_summary(title: &str, stats: &ShapeStats) {
     println!("--- {} ---", title);
     println!("n_txs: {}", stats.n_txs);
     println!("with_transparent: {}", stats.with_transparent);
     println!("with_shielded: {}", stats.with_shielded);
+    println!("coinbase_txs: {}", stats.coinbase_txs());
     println!("size_entropy: {:.4}", stats.size_entropy);
+    println!(
+        "grace_action_share (<= {} logical actions): {}",
+        ZIP317_GRACE_ACTIONS,
+        share_text(stats.grace_action_share())
+    );
     println!("version_hist: {:?}", stats.version_hist);
+    for (histogram, missing) in stats.uncovered_txs() {
+        println!(
+            "note: {} of {} txs have no {} data (stored before it was recorded)",
+            missing, stats.n_txs, histogram
+        );
+    }
+    println!("diversity:");
+    for d in stats.diversity() {
+        println!("  {}: {}", d.histogram, diversity_line(&d));
+    }
+}
+
+fn diversity_line(d: &HistogramDiversity) -> String {
+    let m = &d.metrics;
+    let e = &d.entropy;
+    format!(
+        "distinct={} H={:.4} H_mm={:.4} H_cs={:.4} {:.0}%CI=[{:.4}, {:.4}] H_norm={:.4} H2={:.4} H_min={:.4} gini_simpson={:.4} effective={:.2} singletons={:.4} top{}={:.4}",
+        m.distinct,
+        m.shannon,
+        e.miller_madow,
+        e.chao_shen,
+        CONFIDENCE_LEVEL * 100.0,
+        e.ci_low,
+        e.ci_high,
+        m.normalized_entropy,
+        m.renyi2,
+        m.min_entropy,
+        m.gini_simpson,
+        m.effective_shapes,
+        m.singleton_fraction,
+        TOP_K,
+        m.top_k_share
+    )
+}
+
+fn divergence_line(d: &HistogramDivergence) -> String {
+    let v = &d.divergence;
+    let mut line = format!(
+        "JS={:.4} KL(A||B)={:.4} KL(B||A)={:.4} TV={:.4} PSI={:.4}",
+        v.jensen_shannon, v.kl_ab, v.kl_ba, v.total_variation, v.psi
+    );
+    if let Some(w) = v.wasserstein {
+        line.push_str(&format!(" W1={:.4}", w));
+    }
+    line
+}
+
+fn bucket_delta_line(d: &BucketDelta) -> String {
+    let ratio = d
+        .ratio
+        .map(|r| format!("x{:.2}", r))
+        .unwrap_or_else(|| "new".to_string());
+    format!(
+        "{}: share {:.4} -> {:.4} ({:+.4}, {}), count {} -> {}",
+        d.bucket, d.share_a, d.share_b, d.share_delta, ratio, d.count_a, d.count_b
+    )
+}
+
+fn significance_line(t: &SignificanceTest) -> String {
+    let df = t
+        .result
+        .df
+        .map(|df| format!(" df={}", df))
+        .unwrap_or_default();
+    let g = t
+        .g_test
+        .as_ref()
+        .map(|g| {
+            format!(
+                "; g_test: stat={:.4} p={:.3e} p_holm={:.3e}{}",
+                g.result.statistic,
+                g.result.p_value,
+                g.p_holm,
+                if g.significant { " *" } else { "" }
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        "{} {}: stat={:.4}{} p={:.3e} p_holm={:.3e} p_bh={:.3e}{}{}",
+        t.subject,
+        t.test,
+        t.result.statistic,
+        df,
+        t.result.p_value,
+        t.p_holm,
+        t.p_bh,
+        if t.significant { " *" } else { "" },
+        g
+    )
+}
+
+fn diversity_deltas(a: &[HistogramDiversity], b: &[HistogramDiversity]) -> Vec<DiversityDelta> {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| DiversityDelta {
+            histogram: a.histogram.clone(),
+            normalized_entropy_delta: b.metrics.normalized_entropy - a.metrics.normalized_entropy,
+            gini_simpson_delta: b.metrics.gini_simpson - a.metrics.gini_simpson,
+            effective_shapes_delta: b.metrics.effective_shapes - a.metrics.effective_shapes,
+            top_k_share_delta: b.metrics.top_k_share - a.metrics.top_k_share,
+            chao_shen_delta: b.entropy.chao_shen - a.entropy.chao_shen,
+            entropy_change_significant: a.entropy.differs_from(&b.entropy),
+        })
+        .collect()
 }
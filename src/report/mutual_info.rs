@@ -0,0 +1,90 @@
+//! Mutual information report: pairwise information between the shape-tuple dimensions of a
+//! range.
+
+use super::{aggregate, ReportOptions};
+use crate::model::mutual_info::{
+    dimension_matrix, DimensionMatrix, Redundancy, REDUNDANT_EXPLAINED,
+};
+use rusqlite::Connection;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct MutualInfoReport {
+    low: u32,
+    high: u32,
+    #[serde(flatten)]
+    matrix: DimensionMatrix,
+    redundancies: Vec<Redundancy>,
+}
+
+/// Print the mutual information and conditional entropy matrices of the shape-tuple
+/// dimensions in [low, high), and the dimension pairs where one predicts the other.
+pub fn mutual_information(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    opts: ReportOptions,
+) -> anyhow::Result<()> {
+    let stats = aggregate(conn, low, high, opts)?;
+    let matrix = dimension_matrix(&stats.shape_hist);
+    let redundancies = matrix.redundancies(REDUNDANT_EXPLAINED);
+    if opts.json {
+        let report = MutualInfoReport {
+            low,
+            high,
+            matrix,
+            redundancies,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    println!(
+        "--- Mutual information between shape dimensions in [{}, {}) ({} txs){} ---",
+        low,
+        high,
+        matrix.n_txs,
+        super::floods_note(opts)
+    );
+    if matrix.n_txs == 0 {
+        println!("No shape tuples stored for this range (collect it first).");
+        return Ok(());
+    }
+    print_matrix(
+        "I(row; col), bits (diagonal: H(row))",
+        &matrix,
+        &matrix.mutual_information,
+    );
+    print_matrix("H(row | col), bits", &matrix, &matrix.conditional_entropy);
+    println!(
+        "redundant pairs (predictor explains >= {:.0}% of the predicted entropy):",
+        REDUNDANT_EXPLAINED * 100.0
+    );
+    if redundancies.is_empty() {
+        println!("  none");
+    }
+    for r in &redundancies {
+        println!(
+            "  {} predicts {} ({:.1}%)",
+            r.predictor,
+            r.predicted,
+            r.explained * 100.0
+        );
+    }
+    Ok(())
+}
+
+fn print_matrix(title: &str, matrix: &DimensionMatrix, values: &[Vec<f64>]) {
+    // Full dimension names as headers: prefixes such as "sapling_" are shared.
+    let width = matrix.dimensions.iter().map(|d| d.len()).max().unwrap_or(0);
+    println!("{}:", title);
+    let header: Vec<String> = matrix
+        .dimensions
+        .iter()
+        .map(|d| format!("{:>width$}", d))
+        .collect();
+    println!("{:<width$}  {}", "", header.join(" "));
+    for (name, row) in matrix.dimensions.iter().zip(values) {
+        let cells: Vec<String> = row.iter().map(|v| format!("{:>width$.4}", v)).collect();
+        println!("{:<width$}  {}", name, cells.join(" "));
+    }
+}
@@ -0,0 +1,77 @@
+//! Padding what-if report: compare padding policies on the stored shape tuples of a range.
+
+use super::{aggregate, ReportOptions};
+use crate::model::padding::{simulate, PaddingOutcome, PaddingPolicy};
+use rusqlite::Connection;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct PaddingReport {
+    low: u32,
+    high: u32,
+    outcomes: Vec<PaddingOutcome>,
+}
+
+/// Simulate each policy on the shape tuples of [low, high), preceded by the unpadded row.
+pub fn simulate_padding(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    policies: &[PaddingPolicy],
+    opts: ReportOptions,
+) -> anyhow::Result<Vec<PaddingOutcome>> {
+    let stats = aggregate(conn, low, high, opts)?;
+    let outcomes: Vec<PaddingOutcome> = std::iter::once(PaddingPolicy::none())
+        .chain(policies.iter().cloned())
+        .map(|p| simulate(&stats.shape_hist, &p))
+        .collect();
+    if opts.json {
+        let report = PaddingReport {
+            low,
+            high,
+            outcomes: outcomes.clone(),
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(outcomes);
+    }
+    println!(
+        "--- Padding simulation for [{}, {}) ({} txs){} ---",
+        low,
+        high,
+        outcomes[0].n_txs,
+        super::floods_note(opts)
+    );
+    if outcomes[0].n_txs == 0 {
+        println!("No shape tuples stored for this range (collect it first).");
+        return Ok(outcomes);
+    }
+    let width = outcomes
+        .iter()
+        .map(|o| o.policy.len())
+        .max()
+        .unwrap_or(0)
+        .max(6);
+    println!(
+        "{:<width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}  {:>9}",
+        "policy", "distinct", "shape_H", "eff", "size_H", "padded", "overhead",
+    );
+    for o in &outcomes {
+        println!(
+            "{:<width$}  {:>8}  {:>8.4}  {:>8.2}  {:>8.4}  {:>7.2}%  {:>8.2}%",
+            o.policy,
+            o.distinct_shapes,
+            o.shape_entropy,
+            o.effective_shapes,
+            o.size_entropy,
+            100.0 * o.padded_txs as f64 / o.n_txs as f64,
+            100.0 * o.overhead_ratio,
+        );
+    }
+    if outcomes[0].skipped_txs > 0 {
+        println!(
+            "({} txs with unparsable shape tuples left out)",
+            outcomes[0].skipped_txs
+        );
+    }
+    Ok(outcomes)
+}
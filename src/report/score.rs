@@ -0,0 +1,69 @@
+//! Wallet shape conformance: score transaction shapes against a stored reference range.
+
+use super::{aggregate, ReportOptions};
+use crate::model::score::{ReferenceDistribution, ShapeScore};
+use crate::model::TxShape;
+use rusqlite::Connection;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct ScoreReport {
+    reference_low: u32,
+    reference_high: u32,
+    reference_txs: u64,
+    distinct_shapes: usize,
+    scores: Vec<ShapeScore>,
+}
+
+/// Score `shapes` against the full shape-tuple distribution of [low, high) and print
+/// per-transaction surprisal, share of identical shapes and rank.
+pub fn score_shapes(
+    conn: &Connection,
+    shapes: &[TxShape],
+    low: u32,
+    high: u32,
+    opts: ReportOptions,
+) -> anyhow::Result<Vec<ShapeScore>> {
+    let stats = aggregate(conn, low, high, opts)?;
+    let reference = ReferenceDistribution::from_stats(&stats);
+    let scores = reference.score(shapes);
+    let distinct_shapes = reference.shapes.values().filter(|&&c| c > 0).count();
+    if opts.json {
+        let report = ScoreReport {
+            reference_low: low,
+            reference_high: high,
+            reference_txs: reference.total,
+            distinct_shapes,
+            scores: scores.clone(),
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(scores);
+    }
+    println!(
+        "--- Shape scores vs [{}, {}) ({} txs, {} distinct shapes){} ---",
+        low,
+        high,
+        reference.total,
+        distinct_shapes,
+        super::floods_note(opts)
+    );
+    if reference.total == 0 {
+        println!("No shape data in the reference range (collect it first).");
+    }
+    for (i, s) in scores.iter().enumerate() {
+        let rank = s
+            .rank
+            .map(|r| format!("{}/{}", r, s.distinct))
+            .unwrap_or_else(|| "unseen".to_string());
+        println!(
+            "#{} {}: surprisal={:.2} bits, share={:.6} ({} txs), rank={}",
+            i + 1,
+            s.shape,
+            s.surprisal_bits,
+            s.share,
+            s.count,
+            rank
+        );
+    }
+    Ok(scores)
+}
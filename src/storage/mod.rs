@@ -1,7 +1,13 @@
 //! SQLite storage for aggregate shape statistics (no tx hashes or addresses).
 
+use crate::config::FloodConfig;
+use crate::model::block::BlockMeta;
+use crate::model::entropy::miller_madow;
+use crate::model::flood::{self, BlockShapes, FloodWindow};
 use crate::model::ShapeStats;
+use anyhow::Context;
 use rusqlite::Connection;
+use std::collections::HashMap;
 use std::path::Path;
 
 const SCHEMA: &str = "
@@ -14,6 +20,48 @@ CREATE TABLE IF NOT EXISTS block_shapes (
     with_transparent INTEGER NOT NULL,
     with_shielded INTEGER NOT NULL,
     size_entropy REAL NOT NULL,
+    shape_hist TEXT NOT NULL DEFAULT '{}',
+    logical_actions_hist TEXT NOT NULL DEFAULT '{}',
+    coinbase_hist TEXT NOT NULL DEFAULT '{}',
+    script_out_hist TEXT NOT NULL DEFAULT '{}',
+    script_in_hist TEXT NOT NULL DEFAULT '{}',
+    locktime_hist TEXT NOT NULL DEFAULT '{}',
+    expiry_hist TEXT NOT NULL DEFAULT '{}',
+    orchard_flags_hist TEXT NOT NULL DEFAULT '{}',
+    sapling_bundle_hist TEXT NOT NULL DEFAULT '{}',
+    bundle_padding_hist TEXT NOT NULL DEFAULT '{}',
+    version_group_hist TEXT NOT NULL DEFAULT '{}',
+    branch_hist TEXT NOT NULL DEFAULT '{}',
+    issuance_hist TEXT NOT NULL DEFAULT '{}',
+    burn_hist TEXT NOT NULL DEFAULT '{}',
+    asset_types_hist TEXT NOT NULL DEFAULT '{}',
+    PRIMARY KEY (height)
+);
+
+CREATE TABLE IF NOT EXISTS coinbase_shapes (
+    height INTEGER NOT NULL,
+    n_txs INTEGER NOT NULL,
+    vin_vout_hist TEXT NOT NULL,
+    size_bucket_hist TEXT NOT NULL,
+    version_hist TEXT NOT NULL,
+    with_transparent INTEGER NOT NULL,
+    with_shielded INTEGER NOT NULL,
+    size_entropy REAL NOT NULL,
+    shape_hist TEXT NOT NULL DEFAULT '{}',
+    logical_actions_hist TEXT NOT NULL DEFAULT '{}',
+    coinbase_hist TEXT NOT NULL DEFAULT '{}',
+    script_out_hist TEXT NOT NULL DEFAULT '{}',
+    script_in_hist TEXT NOT NULL DEFAULT '{}',
+    locktime_hist TEXT NOT NULL DEFAULT '{}',
+    expiry_hist TEXT NOT NULL DEFAULT '{}',
+    orchard_flags_hist TEXT NOT NULL DEFAULT '{}',
+    sapling_bundle_hist TEXT NOT NULL DEFAULT '{}',
+    bundle_padding_hist TEXT NOT NULL DEFAULT '{}',
+    version_group_hist TEXT NOT NULL DEFAULT '{}',
+    branch_hist TEXT NOT NULL DEFAULT '{}',
+    issuance_hist TEXT NOT NULL DEFAULT '{}',
+    burn_hist TEXT NOT NULL DEFAULT '{}',
+    asset_types_hist TEXT NOT NULL DEFAULT '{}',
     PRIMARY KEY (height)
 );
 
@@ -27,30 +75,200 @@ CREATE TABLE IF NOT EXISTS range_stats (
     with_transparent INTEGER NOT NULL,
     with_shielded INTEGER NOT NULL,
     size_entropy REAL NOT NULL,
+    shape_hist TEXT NOT NULL DEFAULT '{}',
+    logical_actions_hist TEXT NOT NULL DEFAULT '{}',
+    coinbase_hist TEXT NOT NULL DEFAULT '{}',
+    script_out_hist TEXT NOT NULL DEFAULT '{}',
+    script_in_hist TEXT NOT NULL DEFAULT '{}',
+    locktime_hist TEXT NOT NULL DEFAULT '{}',
+    expiry_hist TEXT NOT NULL DEFAULT '{}',
+    orchard_flags_hist TEXT NOT NULL DEFAULT '{}',
+    sapling_bundle_hist TEXT NOT NULL DEFAULT '{}',
+    bundle_padding_hist TEXT NOT NULL DEFAULT '{}',
+    version_group_hist TEXT NOT NULL DEFAULT '{}',
+    branch_hist TEXT NOT NULL DEFAULT '{}',
+    issuance_hist TEXT NOT NULL DEFAULT '{}',
+    burn_hist TEXT NOT NULL DEFAULT '{}',
+    asset_types_hist TEXT NOT NULL DEFAULT '{}',
     PRIMARY KEY (range_low, range_high)
 );
+
+CREATE TABLE IF NOT EXISTS flagged_windows (
+    range_low INTEGER NOT NULL,
+    range_high INTEGER NOT NULL,
+    shape TEXT NOT NULL,
+    shape_count INTEGER NOT NULL,
+    n_txs INTEGER NOT NULL,
+    share REAL NOT NULL,
+    PRIMARY KEY (range_low, range_high)
+);
+
+CREATE TABLE IF NOT EXISTS block_meta (
+    height INTEGER NOT NULL PRIMARY KEY,
+    n_txs INTEGER NOT NULL,
+    shielded_txs INTEGER NOT NULL,
+    size INTEGER,
+    time INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS alert_deliveries (
+    alert_key TEXT NOT NULL,
+    sink TEXT NOT NULL,
+    sent_at INTEGER NOT NULL,
+    window_high INTEGER NOT NULL,
+    PRIMARY KEY (alert_key, sink)
+);
 ";
 
+/// Declaration of a JSON histogram column.
+const JSON_MAP: &str = "TEXT NOT NULL DEFAULT '{}'";
+
+/// Columns added after the initial schema: (table, column, declaration).
+/// Databases created by older versions get them via ALTER TABLE on open.
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("block_shapes", "shape_hist", JSON_MAP),
+    ("range_stats", "shape_hist", JSON_MAP),
+    ("block_shapes", "logical_actions_hist", JSON_MAP),
+    ("range_stats", "logical_actions_hist", JSON_MAP),
+    ("block_shapes", "coinbase_hist", JSON_MAP),
+    ("range_stats", "coinbase_hist", JSON_MAP),
+    ("block_shapes", "script_out_hist", JSON_MAP),
+    ("range_stats", "script_out_hist", JSON_MAP),
+    ("block_shapes", "script_in_hist", JSON_MAP),
+    ("range_stats", "script_in_hist", JSON_MAP),
+    ("block_shapes", "locktime_hist", JSON_MAP),
+    ("range_stats", "locktime_hist", JSON_MAP),
+    ("block_shapes", "expiry_hist", JSON_MAP),
+    ("range_stats", "expiry_hist", JSON_MAP),
+    ("block_shapes", "orchard_flags_hist", JSON_MAP),
+    ("range_stats", "orchard_flags_hist", JSON_MAP),
+    ("block_shapes", "sapling_bundle_hist", JSON_MAP),
+    ("range_stats", "sapling_bundle_hist", JSON_MAP),
+    ("block_shapes", "bundle_padding_hist", JSON_MAP),
+    ("range_stats", "bundle_padding_hist", JSON_MAP),
+    ("block_shapes", "version_group_hist", JSON_MAP),
+    ("range_stats", "version_group_hist", JSON_MAP),
+    ("block_shapes", "branch_hist", JSON_MAP),
+    ("range_stats", "branch_hist", JSON_MAP),
+    ("block_shapes", "issuance_hist", JSON_MAP),
+    ("range_stats", "issuance_hist", JSON_MAP),
+    ("block_shapes", "burn_hist", JSON_MAP),
+    ("range_stats", "burn_hist", JSON_MAP),
+    ("block_shapes", "asset_types_hist", JSON_MAP),
+    ("range_stats", "asset_types_hist", JSON_MAP),
+];
+
 pub fn open_db(path: &Path) -> anyhow::Result<Connection> {
     let conn = Connection::open(path)?;
     conn.execute_batch(SCHEMA)?;
+    migrate(&conn)?;
     Ok(conn)
 }
 
+fn migrate(conn: &Connection) -> anyhow::Result<()> {
+    for (table, column, decl) in ADDED_COLUMNS {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let exists = stmt
+            .query_map([], |r| r.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .any(|c| c == column);
+        if !exists {
+            conn.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, decl
+            ))?;
+        }
+    }
+    relax_block_meta_size(conn)
+}
+
+/// Older databases declared `block_meta.size` NOT NULL and stored 0 for blocks whose size the
+/// node did not report. Rebuild the table with a nullable size, turning those zeros into NULL
+/// (no real block is empty).
+fn relax_block_meta_size(conn: &Connection) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare("PRAGMA table_info(block_meta)")?;
+    let not_null = stmt
+        .query_map([], |r| Ok((r.get::<_, String>(1)?, r.get::<_, bool>(3)?)))?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .any(|(name, not_null)| name == "size" && not_null);
+    if not_null {
+        conn.execute_batch(
+            "BEGIN;
+             ALTER TABLE block_meta RENAME TO block_meta_old;
+             CREATE TABLE block_meta (
+                 height INTEGER NOT NULL PRIMARY KEY,
+                 n_txs INTEGER NOT NULL,
+                 shielded_txs INTEGER NOT NULL,
+                 size INTEGER,
+                 time INTEGER
+             );
+             INSERT INTO block_meta
+                 SELECT height, n_txs, shielded_txs, NULLIF(size, 0), time FROM block_meta_old;
+             DROP TABLE block_meta_old;
+             COMMIT;",
+        )?;
+    }
+    Ok(())
+}
+
 pub fn upsert_block_stats(
     conn: &Connection,
     height: u32,
     stats: &ShapeStats,
+) -> anyhow::Result<()> {
+    upsert_stats(conn, "block_shapes", height, stats)
+}
+
+/// Store the stats of a block's coinbase transactions (see `ShapeStats::from_coinbase_shapes`),
+/// which reports merge into the user stats on request.
+pub fn upsert_coinbase_stats(
+    conn: &Connection,
+    height: u32,
+    stats: &ShapeStats,
+) -> anyhow::Result<()> {
+    upsert_stats(conn, "coinbase_shapes", height, stats)
+}
+
+/// Insert or replace the stats row of `height` in a per-block stats table.
+fn upsert_stats(
+    conn: &Connection,
+    table: &str,
+    height: u32,
+    stats: &ShapeStats,
 ) -> anyhow::Result<()> {
     let vin_vout = serde_json::to_string(&stats.vin_vout_hist)?;
     let size_hist = serde_json::to_string(&stats.size_bucket_hist)?;
     let version_hist = serde_json::to_string(&stats.version_hist)?;
+    let shape_hist = serde_json::to_string(&stats.shape_hist)?;
+    let logical_actions_hist = serde_json::to_string(&stats.logical_actions_hist)?;
+    let coinbase_hist = serde_json::to_string(&stats.coinbase_hist)?;
+    let script_out_hist = serde_json::to_string(&stats.script_out_hist)?;
+    let script_in_hist = serde_json::to_string(&stats.script_in_hist)?;
+    let locktime_hist = serde_json::to_string(&stats.locktime_hist)?;
+    let expiry_hist = serde_json::to_string(&stats.expiry_hist)?;
+    let orchard_flags_hist = serde_json::to_string(&stats.orchard_flags_hist)?;
+    let sapling_bundle_hist = serde_json::to_string(&stats.sapling_bundle_hist)?;
+    let bundle_padding_hist = serde_json::to_string(&stats.bundle_padding_hist)?;
+    let version_group_hist = serde_json::to_string(&stats.version_group_hist)?;
+    let branch_hist = serde_json::to_string(&stats.branch_hist)?;
+    let issuance_hist = serde_json::to_string(&stats.issuance_hist)?;
+    let burn_hist = serde_json::to_string(&stats.burn_hist)?;
+    let asset_types_hist = serde_json::to_string(&stats.asset_types_hist)?;
     conn.execute(
-        "INSERT INTO block_shapes (height, n_txs, vin_vout_hist, size_bucket_hist, version_hist, with_transparent, with_shielded, size_entropy)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        &format!("INSERT INTO {} (height, n_txs, vin_vout_hist, size_bucket_hist, version_hist, with_transparent, with_shielded, size_entropy, shape_hist, logical_actions_hist, coinbase_hist, script_out_hist, script_in_hist, locktime_hist, expiry_hist, orchard_flags_hist, sapling_bundle_hist, bundle_padding_hist, version_group_hist, branch_hist, issuance_hist, burn_hist, asset_types_hist)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)
          ON CONFLICT(height) DO UPDATE SET
          n_txs=excluded.n_txs, vin_vout_hist=excluded.vin_vout_hist, size_bucket_hist=excluded.size_bucket_hist,
-         version_hist=excluded.version_hist, with_transparent=excluded.with_transparent, with_shielded=excluded.with_shielded, size_entropy=excluded.size_entropy",
+         version_hist=excluded.version_hist, with_transparent=excluded.with_transparent, with_shielded=excluded.with_shielded, size_entropy=excluded.size_entropy,
+         shape_hist=excluded.shape_hist, logical_actions_hist=excluded.logical_actions_hist,
+         coinbase_hist=excluded.coinbase_hist, script_out_hist=excluded.script_out_hist,
+         script_in_hist=excluded.script_in_hist, locktime_hist=excluded.locktime_hist,
+         expiry_hist=excluded.expiry_hist, orchard_flags_hist=excluded.orchard_flags_hist,
+         sapling_bundle_hist=excluded.sapling_bundle_hist, bundle_padding_hist=excluded.bundle_padding_hist,
+         version_group_hist=excluded.version_group_hist, branch_hist=excluded.branch_hist,
+         issuance_hist=excluded.issuance_hist, burn_hist=excluded.burn_hist, asset_types_hist=excluded.asset_types_hist", table),
         rusqlite::params![
             height as i64,
             stats.n_txs as i64,
@@ -60,32 +278,36 @@ pub fn upsert_block_stats(
             stats.with_transparent as i64,
             stats.with_shielded as i64,
             stats.size_entropy,
+            shape_hist,
+            logical_actions_hist,
+            coinbase_hist,
+            script_out_hist,
+            script_in_hist,
+            locktime_hist,
+            expiry_hist,
+            orchard_flags_hist,
+            sapling_bundle_hist,
+            bundle_padding_hist,
+            version_group_hist,
+            branch_hist,
+            issuance_hist,
+            burn_hist,
+            asset_types_hist,
         ],
     )?;
     Ok(())
 }
 
 pub fn get_block_stats(conn: &Connection, height: u32) -> anyhow::Result<Option<ShapeStats>> {
-    let mut stmt = conn.prepare(
-        "SELECT n_txs, vin_vout_hist, size_bucket_hist, version_hist, with_transparent, with_shielded, size_entropy FROM block_shapes WHERE height = ?1",
-    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM block_shapes WHERE height = ?1",
+        STATS_COLUMNS
+    ))?;
     let mut rows = stmt.query([height as i64])?;
-    if let Some(row) = rows.next()? {
-        let vin_vout: String = row.get(1)?;
-        let size_bucket_hist: String = row.get(2)?;
-        let version_hist: String = row.get(3)?;
-        let stats = ShapeStats {
-            n_txs: row.get::<_, i64>(0)? as u64,
-            vin_vout_hist: serde_json::from_str(&vin_vout)?,
-            size_bucket_hist: serde_json::from_str(&size_bucket_hist)?,
-            version_hist: serde_json::from_str(&version_hist)?,
-            with_transparent: row.get::<_, i64>(4)? as u64,
-            with_shielded: row.get::<_, i64>(5)? as u64,
-            size_entropy: row.get(6)?,
-        };
-        return Ok(Some(stats));
+    match rows.next()? {
+        Some(row) => Ok(Some(stats_from_row(row)?)),
+        None => Ok(None),
     }
-    Ok(None)
 }
 
 pub fn get_range_stats(
@@ -93,26 +315,49 @@ pub fn get_range_stats(
     low: u32,
     high: u32,
 ) -> anyhow::Result<Option<ShapeStats>> {
-    let mut stmt = conn.prepare(
-        "SELECT n_txs, vin_vout_hist, size_bucket_hist, version_hist, with_transparent, with_shielded, size_entropy FROM range_stats WHERE range_low = ?1 AND range_high = ?2",
-    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM range_stats WHERE range_low = ?1 AND range_high = ?2",
+        STATS_COLUMNS
+    ))?;
     let mut rows = stmt.query(rusqlite::params![low as i64, high as i64])?;
-    if let Some(row) = rows.next()? {
-        let vin_vout: String = row.get(1)?;
-        let size_bucket_hist: String = row.get(2)?;
-        let version_hist: String = row.get(3)?;
-        let stats = ShapeStats {
-            n_txs: row.get::<_, i64>(0)? as u64,
-            vin_vout_hist: serde_json::from_str(&vin_vout)?,
-            size_bucket_hist: serde_json::from_str(&size_bucket_hist)?,
-            version_hist: serde_json::from_str(&version_hist)?,
-            with_transparent: row.get::<_, i64>(4)? as u64,
-            with_shielded: row.get::<_, i64>(5)? as u64,
-            size_entropy: row.get(6)?,
-        };
-        return Ok(Some(stats));
+    match rows.next()? {
+        Some(row) => Ok(Some(stats_from_row(row)?)),
+        None => Ok(None),
     }
-    Ok(None)
+}
+
+/// Columns of a stored `ShapeStats`, in the order `stats_from_row` reads them.
+const STATS_COLUMNS: &str = "n_txs, vin_vout_hist, size_bucket_hist, version_hist, with_transparent, with_shielded, size_entropy, shape_hist, logical_actions_hist, coinbase_hist, script_out_hist, script_in_hist, locktime_hist, expiry_hist, orchard_flags_hist, sapling_bundle_hist, bundle_padding_hist, version_group_hist, branch_hist, issuance_hist, burn_hist, asset_types_hist";
+
+/// Decode a row selected with `STATS_COLUMNS` first; malformed JSON is an error.
+fn stats_from_row(row: &rusqlite::Row) -> anyhow::Result<ShapeStats> {
+    let json = |i: usize| -> anyhow::Result<String> { Ok(row.get(i)?) };
+    let size_bucket_hist: [u64; 6] = serde_json::from_str(&json(2)?)?;
+    Ok(ShapeStats {
+        n_txs: row.get::<_, i64>(0)? as u64,
+        vin_vout_hist: serde_json::from_str(&json(1)?)?,
+        size_bucket_hist,
+        version_hist: serde_json::from_str(&json(3)?)?,
+        with_transparent: row.get::<_, i64>(4)? as u64,
+        with_shielded: row.get::<_, i64>(5)? as u64,
+        // Recomputed: rows stored before the Miller–Madow correction hold plug-in values.
+        size_entropy: miller_madow(&size_bucket_hist),
+        shape_hist: serde_json::from_str(&json(7)?)?,
+        logical_actions_hist: serde_json::from_str(&json(8)?)?,
+        coinbase_hist: serde_json::from_str(&json(9)?)?,
+        script_out_hist: serde_json::from_str(&json(10)?)?,
+        script_in_hist: serde_json::from_str(&json(11)?)?,
+        locktime_hist: serde_json::from_str(&json(12)?)?,
+        expiry_hist: serde_json::from_str(&json(13)?)?,
+        orchard_flags_hist: serde_json::from_str(&json(14)?)?,
+        sapling_bundle_hist: serde_json::from_str(&json(15)?)?,
+        bundle_padding_hist: serde_json::from_str(&json(16)?)?,
+        version_group_hist: serde_json::from_str(&json(17)?)?,
+        branch_hist: serde_json::from_str(&json(18)?)?,
+        issuance_hist: serde_json::from_str(&json(19)?)?,
+        burn_hist: serde_json::from_str(&json(20)?)?,
+        asset_types_hist: serde_json::from_str(&json(21)?)?,
+    })
 }
 
 pub fn save_range_stats(
@@ -124,12 +369,34 @@ pub fn save_range_stats(
     let vin_vout = serde_json::to_string(&stats.vin_vout_hist)?;
     let size_hist = serde_json::to_string(&stats.size_bucket_hist)?;
     let version_hist = serde_json::to_string(&stats.version_hist)?;
+    let shape_hist = serde_json::to_string(&stats.shape_hist)?;
+    let logical_actions_hist = serde_json::to_string(&stats.logical_actions_hist)?;
+    let coinbase_hist = serde_json::to_string(&stats.coinbase_hist)?;
+    let script_out_hist = serde_json::to_string(&stats.script_out_hist)?;
+    let script_in_hist = serde_json::to_string(&stats.script_in_hist)?;
+    let locktime_hist = serde_json::to_string(&stats.locktime_hist)?;
+    let expiry_hist = serde_json::to_string(&stats.expiry_hist)?;
+    let orchard_flags_hist = serde_json::to_string(&stats.orchard_flags_hist)?;
+    let sapling_bundle_hist = serde_json::to_string(&stats.sapling_bundle_hist)?;
+    let bundle_padding_hist = serde_json::to_string(&stats.bundle_padding_hist)?;
+    let version_group_hist = serde_json::to_string(&stats.version_group_hist)?;
+    let branch_hist = serde_json::to_string(&stats.branch_hist)?;
+    let issuance_hist = serde_json::to_string(&stats.issuance_hist)?;
+    let burn_hist = serde_json::to_string(&stats.burn_hist)?;
+    let asset_types_hist = serde_json::to_string(&stats.asset_types_hist)?;
     conn.execute(
-        "INSERT INTO range_stats (range_low, range_high, n_txs, vin_vout_hist, size_bucket_hist, version_hist, with_transparent, with_shielded, size_entropy)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "INSERT INTO range_stats (range_low, range_high, n_txs, vin_vout_hist, size_bucket_hist, version_hist, with_transparent, with_shielded, size_entropy, shape_hist, logical_actions_hist, coinbase_hist, script_out_hist, script_in_hist, locktime_hist, expiry_hist, orchard_flags_hist, sapling_bundle_hist, bundle_padding_hist, version_group_hist, branch_hist, issuance_hist, burn_hist, asset_types_hist)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)
          ON CONFLICT(range_low, range_high) DO UPDATE SET
          n_txs=excluded.n_txs, vin_vout_hist=excluded.vin_vout_hist, size_bucket_hist=excluded.size_bucket_hist,
-         version_hist=excluded.version_hist, with_transparent=excluded.with_transparent, with_shielded=excluded.with_shielded, size_entropy=excluded.size_entropy",
+         version_hist=excluded.version_hist, with_transparent=excluded.with_transparent, with_shielded=excluded.with_shielded, size_entropy=excluded.size_entropy,
+         shape_hist=excluded.shape_hist, logical_actions_hist=excluded.logical_actions_hist,
+         coinbase_hist=excluded.coinbase_hist, script_out_hist=excluded.script_out_hist,
+         script_in_hist=excluded.script_in_hist, locktime_hist=excluded.locktime_hist,
+         expiry_hist=excluded.expiry_hist, orchard_flags_hist=excluded.orchard_flags_hist,
+         sapling_bundle_hist=excluded.sapling_bundle_hist, bundle_padding_hist=excluded.bundle_padding_hist,
+         version_group_hist=excluded.version_group_hist, branch_hist=excluded.branch_hist,
+         issuance_hist=excluded.issuance_hist, burn_hist=excluded.burn_hist, asset_types_hist=excluded.asset_types_hist",
         rusqlite::params![
             low as i64,
             high as i64,
@@ -140,6 +407,21 @@ pub fn save_range_stats(
             stats.with_transparent as i64,
             stats.with_shielded as i64,
             stats.size_entropy,
+            shape_hist,
+            logical_actions_hist,
+            coinbase_hist,
+            script_out_hist,
+            script_in_hist,
+            locktime_hist,
+            expiry_hist,
+            orchard_flags_hist,
+            sapling_bundle_hist,
+            bundle_padding_hist,
+            version_group_hist,
+            branch_hist,
+            issuance_hist,
+            burn_hist,
+            asset_types_hist,
         ],
     )?;
     Ok(())
@@ -159,60 +441,324 @@ pub fn block_heights_in_range(conn: &Connection, low: u32, high: u32) -> anyhow:
     Ok(out)
 }
 
+/// SQL condition excluding heights inside a stored flagged (flood) window.
+const NOT_FLAGGED: &str = "NOT EXISTS (SELECT 1 FROM flagged_windows f WHERE height >= f.range_low AND height < f.range_high)";
+
+/// Which stored per-block rows a range read covers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockSelection {
+    /// Skip blocks inside flagged flood windows.
+    pub exclude_floods: bool,
+    /// Merge each block's coinbase stats into its user stats.
+    pub include_coinbase: bool,
+}
+
 /// Build aggregate ShapeStats from per-block stats in the DB for a range (no tx hashes used).
 pub fn aggregate_block_stats_in_range(
     conn: &Connection,
     low: u32,
     high: u32,
 ) -> anyhow::Result<ShapeStats> {
+    aggregate_blocks(conn, low, high, BlockSelection::default())
+}
+
+/// Like `aggregate_block_stats_in_range`, but skipping blocks inside flagged flood windows.
+pub fn aggregate_block_stats_excluding_floods(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+) -> anyhow::Result<ShapeStats> {
+    let sel = BlockSelection {
+        exclude_floods: true,
+        ..Default::default()
+    };
+    aggregate_blocks(conn, low, high, sel)
+}
+
+/// Aggregate the per-block stats in [low, high) selected by `sel`.
+pub fn aggregate_blocks(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    sel: BlockSelection,
+) -> anyhow::Result<ShapeStats> {
+    let mut total = ShapeStats::default();
+    for_each_block(conn, low, high, sel, |_, stats| total += stats)?;
+    Ok(total)
+}
+
+/// Per-block stats in [low, high), ordered by height.
+pub fn block_stats_in_range(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+) -> anyhow::Result<Vec<(u32, ShapeStats)>> {
+    block_stats(conn, low, high, BlockSelection::default())
+}
+
+/// Like `block_stats_in_range`, but skipping blocks inside flagged flood windows.
+pub fn block_stats_excluding_floods(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+) -> anyhow::Result<Vec<(u32, ShapeStats)>> {
+    let sel = BlockSelection {
+        exclude_floods: true,
+        ..Default::default()
+    };
+    block_stats(conn, low, high, sel)
+}
+
+/// Per-block stats in [low, high) selected by `sel`, ordered by height.
+pub fn block_stats(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    sel: BlockSelection,
+) -> anyhow::Result<Vec<(u32, ShapeStats)>> {
+    let mut out: Vec<(u32, ShapeStats)> = Vec::new();
+    for_each_row(conn, "block_shapes", low, high, sel, |height, stats| {
+        out.push((height, stats));
+    })?;
+    if sel.include_coinbase {
+        for_each_row(
+            conn,
+            "coinbase_shapes",
+            low,
+            high,
+            sel,
+            |height, stats| match out.binary_search_by_key(&height, |(h, _)| *h) {
+                Ok(i) => out[i].1 += stats,
+                Err(i) => out.insert(i, (height, stats)),
+            },
+        )?;
+    }
+    Ok(out)
+}
+
+/// Call `f` with the stats of every block in [low, high) selected by `sel`, one row at a time
+/// so that a range can be folded without holding it in memory: user stats in height order,
+/// then with `include_coinbase` each block's coinbase stats, again in height order.
+pub fn for_each_block(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    sel: BlockSelection,
+    mut f: impl FnMut(u32, ShapeStats),
+) -> anyhow::Result<()> {
+    for_each_row(conn, "block_shapes", low, high, sel, &mut f)?;
+    if sel.include_coinbase {
+        for_each_row(conn, "coinbase_shapes", low, high, sel, &mut f)?;
+    }
+    Ok(())
+}
+
+/// Call `f` with every stats row of a per-block table in [low, high), ordered by height.
+fn for_each_row(
+    conn: &Connection,
+    table: &str,
+    low: u32,
+    high: u32,
+    sel: BlockSelection,
+    mut f: impl FnMut(u32, ShapeStats),
+) -> anyhow::Result<()> {
+    let filter = if sel.exclude_floods {
+        format!(" AND {}", NOT_FLAGGED)
+    } else {
+        String::new()
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, height FROM {} WHERE height >= ?1 AND height < ?2{} ORDER BY height",
+        STATS_COLUMNS, table, filter
+    ))?;
+    let mut rows = stmt.query(rusqlite::params![low as i64, high as i64])?;
+    while let Some(row) = rows.next()? {
+        let height = row.get::<_, i64>(22)? as u32;
+        let stats = stats_from_row(row)
+            .with_context(|| format!("invalid stored stats in {} for block {}", table, height))?;
+        f(height, stats);
+    }
+    Ok(())
+}
+
+/// Full shape-tuple histogram and tx count per block in [low, high), ordered by height.
+pub fn block_shape_hists_in_range(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+) -> anyhow::Result<Vec<BlockShapes>> {
     let mut stmt = conn.prepare(
-        "SELECT n_txs, vin_vout_hist, size_bucket_hist, version_hist, with_transparent, with_shielded FROM block_shapes WHERE height >= ?1 AND height < ?2",
+        "SELECT height, n_txs, shape_hist FROM block_shapes WHERE height >= ?1 AND height < ?2 ORDER BY height",
     )?;
     let rows = stmt.query_map(rusqlite::params![low as i64, high as i64], |row| {
         Ok((
             row.get::<_, i64>(0)?,
-            row.get::<_, String>(1)?,
+            row.get::<_, i64>(1)?,
             row.get::<_, String>(2)?,
-            row.get::<_, String>(3)?,
-            row.get::<_, i64>(4)?,
-            row.get::<_, i64>(5)?,
         ))
     })?;
-    let mut n_txs = 0u64;
-    let mut vin_vout_hist = std::collections::HashMap::new();
-    let mut size_bucket_hist = [0u64; 6];
-    let mut version_hist = std::collections::HashMap::new();
-    let mut with_transparent = 0u64;
-    let mut with_shielded = 0u64;
+    let mut out = Vec::new();
+    for row in rows {
+        let (height, n_txs, hist) = row?;
+        out.push((height as u32, n_txs as u64, serde_json::from_str(&hist)?));
+    }
+    Ok(out)
+}
 
+/// Version-group histogram of each block in [low, high), ordered by height.
+pub fn block_version_group_hists_in_range(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+) -> anyhow::Result<Vec<(u32, HashMap<String, u64>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT height, version_group_hist FROM block_shapes WHERE height >= ?1 AND height < ?2 ORDER BY height",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![low as i64, high as i64], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+    })?;
+    let mut out = Vec::new();
     for row in rows {
-        let (nt, vv, sh, vh, wt, ws): (i64, String, String, String, i64, i64) = row?;
-        n_txs += nt as u64;
-        with_transparent += wt as u64;
-        with_shielded += ws as u64;
-        let vv_map: std::collections::HashMap<String, u64> =
-            serde_json::from_str(&vv).unwrap_or_default();
-        for (k, v) in vv_map {
-            *vin_vout_hist.entry(k).or_insert(0) += v;
-        }
-        let sb: [u64; 6] = serde_json::from_str(&sh).unwrap_or([0; 6]);
-        for (i, &v) in sb.iter().enumerate() {
-            size_bucket_hist[i] += v;
-        }
-        let vh_map: std::collections::HashMap<u32, u64> =
-            serde_json::from_str(&vh).unwrap_or_default();
-        for (k, v) in vh_map {
-            *version_hist.entry(k).or_insert(0) += v;
-        }
+        let (height, hist) = row?;
+        let hist = serde_json::from_str(&hist)
+            .with_context(|| format!("invalid stored version groups for block {}", height))?;
+        out.push((height as u32, hist));
     }
-    let size_entropy = crate::util::entropy(&size_bucket_hist);
-    Ok(ShapeStats {
-        n_txs,
-        vin_vout_hist,
-        size_bucket_hist,
-        version_hist,
-        with_transparent,
-        with_shielded,
-        size_entropy,
-    })
+    Ok(out)
+}
+
+/// Replace the flagged windows overlapping [low, high) with `windows`.
+pub fn replace_flagged_windows(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    windows: &[FloodWindow],
+) -> anyhow::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM flagged_windows WHERE range_low < ?2 AND range_high > ?1",
+        rusqlite::params![low as i64, high as i64],
+    )?;
+    for w in windows {
+        tx.execute(
+            "INSERT OR REPLACE INTO flagged_windows (range_low, range_high, shape, shape_count, n_txs, share)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                w.low as i64,
+                w.high as i64,
+                w.shape,
+                w.shape_count as i64,
+                w.n_txs as i64,
+                w.share,
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Stored flagged windows overlapping [low, high), ordered by start height.
+pub fn flagged_windows_in_range(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+) -> anyhow::Result<Vec<FloodWindow>> {
+    let mut stmt = conn.prepare(
+        "SELECT range_low, range_high, shape, shape_count, n_txs, share FROM flagged_windows
+         WHERE range_low < ?2 AND range_high > ?1 ORDER BY range_low",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![low as i64, high as i64], |row| {
+        Ok(FloodWindow {
+            low: row.get::<_, i64>(0)? as u32,
+            high: row.get::<_, i64>(1)? as u32,
+            shape: row.get(2)?,
+            shape_count: row.get::<_, i64>(3)? as u64,
+            n_txs: row.get::<_, i64>(4)? as u64,
+            share: row.get(5)?,
+        })
+    })?;
+    Ok(rows.collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Detect flood windows in [low, high) from the stored per-block shape tuples and replace the
+/// stored windows for that range. Returns the detected windows.
+pub fn flag_floods(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+    cfg: &FloodConfig,
+) -> anyhow::Result<Vec<FloodWindow>> {
+    let blocks = block_shape_hists_in_range(conn, low, high)?;
+    let windows = flood::detect_floods(&blocks, cfg.window_blocks, cfg.min_share, cfg.min_count);
+    replace_flagged_windows(conn, low, high, &windows)?;
+    Ok(windows)
+}
+
+/// Insert or replace the block-level facts of one block.
+pub fn upsert_block_meta(conn: &Connection, meta: &BlockMeta) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO block_meta (height, n_txs, shielded_txs, size, time) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![
+            meta.height as i64,
+            meta.n_txs as i64,
+            meta.shielded_txs as i64,
+            meta.size.map(|s| s as i64),
+            meta.time,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Block-level facts of the blocks in [low, high), ordered by height.
+pub fn block_meta_in_range(
+    conn: &Connection,
+    low: u32,
+    high: u32,
+) -> anyhow::Result<Vec<BlockMeta>> {
+    let mut stmt = conn.prepare(
+        "SELECT height, n_txs, shielded_txs, size, time FROM block_meta WHERE height >= ?1 AND height < ?2 ORDER BY height",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![low as i64, high as i64], |row| {
+        Ok(BlockMeta {
+            height: row.get::<_, i64>(0)? as u32,
+            n_txs: row.get::<_, i64>(1)? as u64,
+            shielded_txs: row.get::<_, i64>(2)? as u64,
+            size: row.get::<_, Option<i64>>(3)?.map(|s| s as u32),
+            time: row.get(4)?,
+        })
+    })?;
+    Ok(rows.collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Last delivery of an alert key to one sink: (unix time sent, high end of the scored window).
+pub fn alert_last_sent(
+    conn: &Connection,
+    key: &str,
+    sink: &str,
+) -> anyhow::Result<Option<(i64, u32)>> {
+    let mut stmt = conn.prepare(
+        "SELECT sent_at, window_high FROM alert_deliveries WHERE alert_key = ?1 AND sink = ?2",
+    )?;
+    let mut rows = stmt.query(rusqlite::params![key, sink])?;
+    if let Some(row) = rows.next()? {
+        Ok(Some((row.get(0)?, row.get::<_, i64>(1)? as u32)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Record that an alert key was delivered to `sink` at `sent_at` for the window ending at
+/// `window_high`.
+pub fn record_alert_sent(
+    conn: &Connection,
+    key: &str,
+    sink: &str,
+    sent_at: i64,
+    window_high: u32,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO alert_deliveries (alert_key, sink, sent_at, window_high) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![key, sink, sent_at, window_high as i64],
+    )?;
+    Ok(())
 }
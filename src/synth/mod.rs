@@ -0,0 +1,415 @@
+//! Synthetic chain generator for validating detectors and reports against known ground truth.
+//!
+//! A scenario (TOML) mixes wallet profiles (weighted shape tuples), spam bursts (one shape at a
+//! fixed rate over a height range) and version rollouts (a linear switch from one tx version to
+//! another), plus an optional coinbase shape at the start of every block. Generation is
+//! deterministic for a given seed. Blocks can be written straight into
+//! the database or served through a mock node that `collect` reads like zcashd.
+
+pub mod mock_node;
+
+use crate::model::block::BlockMeta;
+use crate::model::{ShapeStats, TxShape};
+use crate::storage;
+use crate::util::{Rng, BUCKET_TYPICAL_SIZE};
+use anyhow::Context;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Weights are sampled at this resolution (multinomial draws take integer weights).
+const WEIGHT_SCALE: f64 = 1e6;
+/// Synthetic block times start at the mainnet genesis time and advance by the target spacing.
+const GENESIS_TIME: i64 = 1_477_641_360;
+const BLOCK_SPACING_SECS: i64 = 75;
+/// Block header with Equihash solution.
+const BLOCK_HEADER_SIZE: u32 = 1_487;
+
+/// Mixture model of synthetic traffic over blocks [low, high).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub seed: u64,
+    pub low: u32,
+    pub high: u32,
+    /// Mean wallet-profile transactions per block (Poisson).
+    pub txs_per_block: f64,
+    pub profiles: Vec<Profile>,
+    #[serde(default)]
+    pub bursts: Vec<Burst>,
+    #[serde(default)]
+    pub rollouts: Vec<Rollout>,
+    /// Shape tuple of the coinbase transaction leading every block.
+    #[serde(default)]
+    pub coinbase: Option<String>,
+}
+
+/// Wallet profile: share of traffic and the shape tuples it produces.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// Relative weight among profiles.
+    pub weight: f64,
+    /// TxShape::tuple_key → relative weight.
+    pub shapes: BTreeMap<String, f64>,
+}
+
+/// Spam burst: `shape` at a mean of `txs_per_block` in [low, high), on top of profile traffic.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Burst {
+    pub name: String,
+    pub low: u32,
+    pub high: u32,
+    pub shape: String,
+    pub txs_per_block: f64,
+}
+
+/// Version rollout: profile transactions with `from_version` switch to `to_version` with a
+/// probability rising linearly from 0 at `start` to 1 at `end`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rollout {
+    pub from_version: u32,
+    pub to_version: u32,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// One generated block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticBlock {
+    pub height: u32,
+    pub shapes: Vec<TxShape>,
+}
+
+/// What was actually generated, for checking detector output.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GroundTruth {
+    pub low: u32,
+    pub high: u32,
+    /// Non-coinbase transactions.
+    pub n_txs: u64,
+    pub coinbase_txs: u64,
+    pub profiles: Vec<ProfileTruth>,
+    pub bursts: Vec<BurstTruth>,
+    pub rollouts: Vec<RolloutTruth>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileTruth {
+    pub name: String,
+    pub n_txs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BurstTruth {
+    pub name: String,
+    /// Burst range clipped to the scenario range.
+    pub low: u32,
+    pub high: u32,
+    pub shape: String,
+    pub n_txs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RolloutTruth {
+    pub from_version: u32,
+    pub to_version: u32,
+    pub start: u32,
+    pub end: u32,
+    pub upgraded_txs: u64,
+}
+
+impl Scenario {
+    /// Load and validate a scenario TOML file.
+    pub fn load(path: &Path) -> anyhow::Result<Scenario> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read scenario: {}", path.display()))?;
+        let scenario: Scenario = toml::from_str(&data).context("invalid scenario TOML")?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.low >= self.high {
+            anyhow::bail!("scenario low must be less than high");
+        }
+        if self.txs_per_block < 0.0 {
+            anyhow::bail!("scenario txs_per_block must not be negative");
+        }
+        if self.profiles.is_empty() {
+            anyhow::bail!("scenario needs at least one profile");
+        }
+        for p in &self.profiles {
+            if p.weight <= 0.0 || p.shapes.is_empty() || p.shapes.values().any(|&w| w <= 0.0) {
+                anyhow::bail!(
+                    "profile {:?} needs a positive weight and weighted shapes",
+                    p.name
+                );
+            }
+            for key in p.shapes.keys() {
+                parse_shape(key)?;
+            }
+        }
+        if let Some(key) = &self.coinbase {
+            parse_shape(key)?;
+        }
+        for b in &self.bursts {
+            parse_shape(&b.shape)?;
+            if b.low >= b.high || b.txs_per_block <= 0.0 {
+                anyhow::bail!(
+                    "burst {:?} needs low < high and positive txs_per_block",
+                    b.name
+                );
+            }
+        }
+        for r in &self.rollouts {
+            if r.start >= r.end {
+                anyhow::bail!("rollout start must be less than end");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Generate all blocks of the scenario with their ground truth.
+pub fn generate(scenario: &Scenario) -> anyhow::Result<(Vec<SyntheticBlock>, GroundTruth)> {
+    scenario.validate()?;
+    let mut rng = Rng::new(scenario.seed);
+    let profile_weights: Vec<u64> = scenario.profiles.iter().map(|p| scaled(p.weight)).collect();
+    let profiles: Vec<(Vec<TxShape>, Vec<u64>)> = scenario
+        .profiles
+        .iter()
+        .map(|p| {
+            let shapes = p
+                .shapes
+                .keys()
+                .map(|k| parse_shape(k))
+                .collect::<anyhow::Result<_>>()?;
+            Ok((shapes, p.shapes.values().map(|&w| scaled(w)).collect()))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    let bursts: Vec<TxShape> = scenario
+        .bursts
+        .iter()
+        .map(|b| parse_shape(&b.shape))
+        .collect::<anyhow::Result<_>>()?;
+    let coinbase = match &scenario.coinbase {
+        Some(key) => Some(TxShape {
+            coinbase: true,
+            ..parse_shape(key)?
+        }),
+        None => None,
+    };
+
+    let mut truth = GroundTruth {
+        low: scenario.low,
+        high: scenario.high,
+        profiles: scenario
+            .profiles
+            .iter()
+            .map(|p| ProfileTruth {
+                name: p.name.clone(),
+                n_txs: 0,
+            })
+            .collect(),
+        bursts: scenario
+            .bursts
+            .iter()
+            .map(|b| BurstTruth {
+                name: b.name.clone(),
+                low: b.low.max(scenario.low),
+                high: b.high.min(scenario.high),
+                shape: b.shape.clone(),
+                n_txs: 0,
+            })
+            .collect(),
+        rollouts: scenario
+            .rollouts
+            .iter()
+            .map(|r| RolloutTruth {
+                from_version: r.from_version,
+                to_version: r.to_version,
+                start: r.start,
+                end: r.end,
+                upgraded_txs: 0,
+            })
+            .collect(),
+        ..Default::default()
+    };
+
+    let mut blocks = Vec::with_capacity((scenario.high - scenario.low) as usize);
+    for height in scenario.low..scenario.high {
+        let mut shapes: Vec<TxShape> = coinbase.iter().cloned().collect();
+        truth.coinbase_txs += shapes.len() as u64;
+        let n = rng.poisson(scenario.txs_per_block);
+        let per_profile = rng.multinomial(n, &profile_weights);
+        for (i, ((templates, weights), &count)) in profiles.iter().zip(&per_profile).enumerate() {
+            truth.profiles[i].n_txs += count;
+            for (shape, &k) in templates.iter().zip(&rng.multinomial(count, weights)) {
+                for _ in 0..k {
+                    let mut tx = shape.clone();
+                    for (r, rt) in scenario.rollouts.iter().zip(truth.rollouts.iter_mut()) {
+                        if tx.version == r.from_version && rng.next_f64() < r.progress(height) {
+                            tx.version = r.to_version;
+                            rt.upgraded_txs += 1;
+                        }
+                    }
+                    shapes.push(tx);
+                }
+            }
+        }
+        for ((b, shape), bt) in scenario.bursts.iter().zip(&bursts).zip(&mut truth.bursts) {
+            if height >= b.low && height < b.high {
+                let k = rng.poisson(b.txs_per_block);
+                bt.n_txs += k;
+                shapes.extend(std::iter::repeat(shape.clone()).take(k as usize));
+            }
+        }
+        truth.n_txs += shapes.iter().filter(|s| !s.coinbase).count() as u64;
+        blocks.push(SyntheticBlock { height, shapes });
+    }
+    Ok((blocks, truth))
+}
+
+/// Store generated blocks as per-block stats, plus the range stats `collect` would save.
+pub fn write_to_db(conn: &Connection, blocks: &[SyntheticBlock]) -> anyhow::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let mut range_stats = ShapeStats::default();
+    for b in blocks {
+        let stats = ShapeStats::from_shapes(&b.shapes);
+        storage::upsert_block_stats(&tx, b.height, &stats)?;
+        let coinbase = ShapeStats::from_coinbase_shapes(&b.shapes);
+        storage::upsert_coinbase_stats(&tx, b.height, &coinbase)?;
+        storage::upsert_block_meta(&tx, &block_meta(b.height, &b.shapes))?;
+        range_stats += stats;
+    }
+    if let (Some(first), Some(last)) = (blocks.first(), blocks.last()) {
+        storage::save_range_stats(&tx, first.height, last.height + 1, &range_stats)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Block-level facts of a synthetic block: typical transaction sizes plus a header, and
+/// evenly spaced times.
+pub fn block_meta(height: u32, shapes: &[TxShape]) -> BlockMeta {
+    let size = BLOCK_HEADER_SIZE
+        + shapes
+            .iter()
+            .map(|s| BUCKET_TYPICAL_SIZE[(s.size_bucket as usize).min(5)])
+            .sum::<u32>();
+    let time = GENESIS_TIME + height as i64 * BLOCK_SPACING_SECS;
+    BlockMeta::from_shapes(height, shapes, Some(size), Some(time))
+}
+
+impl Rollout {
+    /// Share of `from_version` transactions switched at `height`.
+    fn progress(&self, height: u32) -> f64 {
+        if height <= self.start {
+            0.0
+        } else if height >= self.end {
+            1.0
+        } else {
+            (height - self.start) as f64 / (self.end - self.start) as f64
+        }
+    }
+}
+
+fn parse_shape(key: &str) -> anyhow::Result<TxShape> {
+    TxShape::from_tuple_key(key).ok_or_else(|| {
+        anyhow::anyhow!(
+            "invalid shape tuple {:?} (expected vin_vout_js_ss_so_oa_sizebucket_version)",
+            key
+        )
+    })
+}
+
+fn scaled(weight: f64) -> u64 {
+    (weight * WEIGHT_SCALE).round().max(1.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO: &str = r#"
+seed = 9
+low = 100
+high = 200
+txs_per_block = 20
+
+[[profiles]]
+name = "transparent"
+weight = 3
+shapes = { "1_2_0_0_0_0_1_4" = 2, "2_2_0_0_0_0_1_4" = 1 }
+
+[[profiles]]
+name = "shielded"
+weight = 1
+shapes = { "0_0_0_0_0_2_3_5" = 1 }
+
+[[bursts]]
+name = "spam"
+low = 150
+high = 160
+shape = "0_0_0_1_2_0_3_4"
+txs_per_block = 100
+
+[[rollouts]]
+from_version = 4
+to_version = 5
+start = 120
+end = 180
+"#;
+
+    #[test]
+    fn test_generate_matches_truth() {
+        let scenario: Scenario = toml::from_str(SCENARIO).unwrap();
+        let (blocks, truth) = generate(&scenario).unwrap();
+        assert_eq!(blocks.len(), 100);
+        assert_eq!(blocks[0].height, 100);
+        let total: usize = blocks.iter().map(|b| b.shapes.len()).sum();
+        assert_eq!(truth.n_txs, total as u64);
+        let profile_txs: u64 = truth.profiles.iter().map(|p| p.n_txs).sum();
+        assert_eq!(profile_txs + truth.bursts[0].n_txs, truth.n_txs);
+        assert!(truth.profiles[0].n_txs > 2 * truth.profiles[1].n_txs);
+        assert!((800..1200).contains(&truth.bursts[0].n_txs));
+
+        let spam = |b: &SyntheticBlock| b.shapes.iter().filter(|s| s.n_sapling_spend == 1).count();
+        assert!(blocks
+            .iter()
+            .filter(|b| !(150..160).contains(&b.height))
+            .all(|b| spam(b) == 0));
+        // Before the rollout every transparent tx is v4, after it every one is v5.
+        let v5 = |b: &SyntheticBlock| {
+            b.shapes
+                .iter()
+                .filter(|s| s.n_vin > 0 && s.version == 5)
+                .count()
+        };
+        let v4 = |b: &SyntheticBlock| {
+            b.shapes
+                .iter()
+                .filter(|s| s.n_vin > 0 && s.version == 4)
+                .count()
+        };
+        assert!(blocks[..20].iter().all(|b| v5(b) == 0));
+        assert!(blocks[80..].iter().all(|b| v4(b) == 0));
+        let upgraded: usize = blocks.iter().map(v5).sum();
+        assert_eq!(truth.rollouts[0].upgraded_txs, upgraded as u64);
+
+        let (again, _) = generate(&scenario).unwrap();
+        assert_eq!(again, blocks);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut scenario: Scenario = toml::from_str(SCENARIO).unwrap();
+        scenario.bursts[0].shape = "1_2".to_string();
+        assert!(scenario.validate().is_err());
+        let mut scenario: Scenario = toml::from_str(SCENARIO).unwrap();
+        scenario.profiles.clear();
+        assert!(scenario.validate().is_err());
+    }
+}
@@ -12,6 +12,9 @@ pub fn size_bucket(size_bytes: u32) -> u8 {
     }
 }
 
+/// Typical size (bytes) of a transaction in each size bucket, for estimates and synthetic data.
+pub const BUCKET_TYPICAL_SIZE: [u32; 6] = [200, 384, 768, 1536, 3072, 6144];
+
 /// Shannon entropy of a distribution (counts per bucket). Returns 0 if total is 0.
 pub fn entropy(counts: &[u64]) -> f64 {
     let total: u64 = counts.iter().sum();
@@ -29,12 +32,135 @@ pub fn entropy(counts: &[u64]) -> f64 {
         .sum::<f64>()
 }
 
+/// Decode a hex string (case-insensitive, surrounding whitespace ignored).
+pub fn hex_decode(s: &str) -> anyhow::Result<Vec<u8>> {
+    let s = s.trim();
+    if s.len() % 2 != 0 {
+        anyhow::bail!("hex string has odd length");
+    }
+    let digit = |c: u8| -> anyhow::Result<u8> {
+        match c {
+            b'0'..=b'9' => Ok(c - b'0'),
+            b'a'..=b'f' => Ok(c - b'a' + 10),
+            b'A'..=b'F' => Ok(c - b'A' + 10),
+            _ => anyhow::bail!("invalid hex digit {:?}", c as char),
+        }
+    };
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+/// Small deterministic PRNG (SplitMix64) for reproducible resampling. Not for cryptographic use.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal (Box–Muller).
+    pub fn next_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+
+    /// Binomial(n, p) draw: waiting-time inversion for small means, normal approximation otherwise.
+    pub fn binomial(&mut self, n: u64, p: f64) -> u64 {
+        if n == 0 || p <= 0.0 {
+            return 0;
+        }
+        if p >= 1.0 {
+            return n;
+        }
+        if p > 0.5 {
+            return n - self.binomial(n, 1.0 - p);
+        }
+        let mean = n as f64 * p;
+        if mean < 30.0 {
+            let log_q = (1.0 - p).ln();
+            let mut successes = 0u64;
+            let mut trials = 0f64;
+            loop {
+                trials += ((1.0 - self.next_f64()).ln() / log_q).ceil().max(1.0);
+                if trials > n as f64 {
+                    return successes;
+                }
+                successes += 1;
+            }
+        }
+        let sd = (mean * (1.0 - p)).sqrt();
+        (mean + sd * self.next_normal())
+            .round()
+            .clamp(0.0, n as f64) as u64
+    }
+
+    /// Poisson(lambda) draw: Knuth's product method for small means, normal approximation otherwise.
+    pub fn poisson(&mut self, lambda: f64) -> u64 {
+        if lambda <= 0.0 {
+            return 0;
+        }
+        if lambda < 30.0 {
+            let limit = (-lambda).exp();
+            let mut k = 0u64;
+            let mut prod = self.next_f64();
+            while prod > limit {
+                k += 1;
+                prod *= self.next_f64();
+            }
+            return k;
+        }
+        (lambda + lambda.sqrt() * self.next_normal())
+            .round()
+            .max(0.0) as u64
+    }
+
+    /// Multinomial resample of `n` draws with probabilities proportional to `weights`.
+    pub fn multinomial(&mut self, n: u64, weights: &[u64]) -> Vec<u64> {
+        let mut remaining_mass: u64 = weights.iter().sum();
+        let mut remaining_n = n;
+        let mut out = Vec::with_capacity(weights.len());
+        for &w in weights {
+            let draw = if remaining_mass == 0 {
+                0
+            } else {
+                self.binomial(remaining_n, w as f64 / remaining_mass as f64)
+            };
+            out.push(draw);
+            remaining_n -= draw;
+            remaining_mass -= w;
+        }
+        out
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_size_bucket() {
+        for (bucket, &size) in BUCKET_TYPICAL_SIZE.iter().enumerate() {
+            assert_eq!(size_bucket(size) as usize, bucket);
+        }
         assert_eq!(size_bucket(0), 0);
         assert_eq!(size_bucket(256), 0);
         assert_eq!(size_bucket(257), 1);
@@ -44,6 +170,28 @@ mod tests {
         assert_eq!(size_bucket(5000), 5);
     }
 
+    #[test]
+    fn test_rng_poisson() {
+        let mut rng = Rng::new(7);
+        for lambda in [3.0, 200.0] {
+            let mean = (0..2000).map(|_| rng.poisson(lambda)).sum::<u64>() as f64 / 2000.0;
+            assert!(
+                (mean - lambda).abs() < 0.05 * lambda,
+                "{} vs {}",
+                mean,
+                lambda
+            );
+        }
+        assert_eq!(rng.poisson(0.0), 0);
+    }
+
+    #[test]
+    fn test_hex_decode() {
+        assert_eq!(hex_decode(" 00ff7A \n").unwrap(), vec![0x00, 0xff, 0x7a]);
+        assert!(hex_decode("abc").is_err());
+        assert!(hex_decode("zz").is_err());
+    }
+
     #[test]
     fn test_entropy() {
         assert_eq!(entropy(&[]), 0.0);
@@ -51,4 +199,16 @@ mod tests {
         assert!((entropy(&[1, 1]) - 1.0).abs() < 1e-10);
         assert!((entropy(&[1, 1, 1, 1]) - 2.0).abs() < 1e-10);
     }
+
+    #[test]
+    fn test_rng_multinomial() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+        assert_eq!(a.next_u64(), b.next_u64());
+        let draw = a.multinomial(10_000, &[1, 0, 3]);
+        assert_eq!(draw.iter().sum::<u64>(), 10_000);
+        assert_eq!(draw[1], 0);
+        assert!(draw[2] > draw[0] * 2);
+        assert_eq!(a.multinomial(5, &[0, 0]), vec![0, 0]);
+    }
 }
@@ -19,6 +19,13 @@ fn cli_help() {
     assert!(stdout.contains("Transaction Shape Analyzer"));
     assert!(stdout.contains("collect"));
     assert!(stdout.contains("report"));
+    assert!(stdout.contains("detect-floods"));
+    assert!(stdout.contains("alerts"));
+    assert!(stdout.contains("score"));
+    assert!(stdout.contains("export-baseline"));
+    assert!(stdout.contains("simulate-padding"));
+    assert!(stdout.contains("synth"));
+    assert!(stdout.contains(" ci "));
 }
 
 #[test]
@@ -33,6 +40,10 @@ fn cli_report_help() {
     assert!(stdout.contains("daily"));
     assert!(stdout.contains("weekly"));
     assert!(stdout.contains("diff"));
+    assert!(stdout.contains("compare"));
+    assert!(stdout.contains("branches"));
+    assert!(stdout.contains("blocks"));
+    assert!(stdout.contains("mutual-info"));
 }
 
 #[test]
@@ -78,3 +89,54 @@ batch_delay_ms = 100
     let stdout = String::from_utf8_lossy(&out.stdout);
     assert!(stdout.contains("No block data") || stdout.contains("n_txs"));
 }
+
+#[test]
+fn cli_ci_without_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let baseline = dir.path().join("baseline.json");
+    let fixtures = dir.path().join("fixtures.json");
+    std::fs::write(
+        &baseline,
+        r#"{"format": "zcash-txshape-baseline", "version": 1, "low": 0, "high": 10,
+            "distribution": {"total": 100, "shapes": {"1_2_0_0_0_0_1_4": 99, "0_0_0_1_2_0_3_5": 1}}}"#,
+    )
+    .unwrap();
+    let shape = |n_vin: u32| {
+        format!(
+            r#"{{"n_vin": {}, "n_vout": 2, "n_joinsplit": 0, "n_sapling_spend": 0,
+                "n_sapling_output": 0, "n_orchard_action": 0, "size_bucket": 1, "version": 4}}"#,
+            n_vin
+        )
+    };
+    let run = |fixture: String, format: &str| {
+        std::fs::write(&fixtures, fixture).unwrap();
+        bin()
+            .env("ZCASH_TXSHAPE_CONFIG", dir.path().join("missing.toml"))
+            .args([
+                "ci",
+                "--fixtures",
+                fixtures.to_str().unwrap(),
+                "--baseline",
+                baseline.to_str().unwrap(),
+                "--format",
+                format,
+            ])
+            .output()
+            .unwrap()
+    };
+
+    let out = run(format!("[{}]", shape(1)), "json");
+    assert!(
+        out.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let result: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(result["failures"], 0);
+
+    let out = run(format!("[{}, {}]", shape(1), shape(7)), "junit");
+    assert_eq!(out.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("<testsuite name=\"zcash-txshape\" tests=\"2\" failures=\"1\">"));
+    assert!(stdout.contains("<failure message=\"share 0.000000 below min_share 0.001"));
+}
@@ -1,10 +1,36 @@
 //! Integration tests: storage and report with in-memory DB.
 
-use zcash_txshape::model::{ShapeStats, TxShape};
-use zcash_txshape::report;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use zcash_txshape::alert::{self, WindowAlerts};
+use zcash_txshape::config::{AlertRule, AlertsConfig, Config, FloodConfig, SinkConfig};
+use zcash_txshape::model::baseline::Alert;
+use zcash_txshape::model::divergence::BucketSort;
+use zcash_txshape::model::format::{ConsensusBranch, Network, VersionGroup};
+use zcash_txshape::model::mutual_info::dimension_matrix;
+use zcash_txshape::model::padding::PaddingPolicy;
+use zcash_txshape::model::score::BaselineFile;
+use zcash_txshape::model::significance::range_tests;
+use zcash_txshape::model::{ScriptCounts, ShapeStats, TxShape};
+use zcash_txshape::report::{self, ReportOptions};
 use zcash_txshape::storage;
+use zcash_txshape::synth;
 use zcash_txshape::util::size_bucket;
 
+fn sample_shape(n_vin: u32, n_sapling_output: u32) -> TxShape {
+    TxShape {
+        n_vin,
+        n_vout: 1,
+        n_joinsplit: 0,
+        n_sapling_spend: 0,
+        n_sapling_output,
+        n_orchard_action: 0,
+        size_bucket: size_bucket(300),
+        version: 4,
+        ..Default::default()
+    }
+}
+
 fn sample_stats() -> ShapeStats {
     let shapes = vec![
         TxShape {
@@ -16,6 +42,13 @@ fn sample_stats() -> ShapeStats {
             n_orchard_action: 0,
             size_bucket: size_bucket(300),
             version: 4,
+            scripts: ScriptCounts {
+                out_p2pkh: 1,
+                out_p2sh: 1,
+                in_p2sh_multisig: 1,
+                ..Default::default()
+            },
+            ..Default::default()
         },
         TxShape {
             n_vin: 0,
@@ -26,6 +59,7 @@ fn sample_stats() -> ShapeStats {
             n_orchard_action: 0,
             size_bucket: size_bucket(500),
             version: 4,
+            ..Default::default()
         },
     ];
     ShapeStats::from_shapes(&shapes)
@@ -42,6 +76,7 @@ fn storage_upsert_and_get_block_stats() {
     assert_eq!(loaded.n_txs, stats.n_txs);
     assert_eq!(loaded.with_transparent, 1);
     assert_eq!(loaded.with_shielded, 1);
+    assert_eq!(loaded.logical_actions_hist, stats.logical_actions_hist);
 }
 
 #[test]
@@ -55,6 +90,106 @@ fn storage_aggregate_range() {
     storage::upsert_block_stats(&conn, 11, &stats2).unwrap();
     let agg = storage::aggregate_block_stats_in_range(&conn, 10, 12).unwrap();
     assert_eq!(agg.n_txs, 2 + 2);
+    // 1-in/2-out transparent and 1-spend/1-output Sapling: 2 and 1 logical actions.
+    assert_eq!(agg.logical_actions_hist.get(&2), Some(&2));
+    assert_eq!(agg.logical_actions_hist.get(&1), Some(&2));
+    assert_eq!(agg.grace_action_txs(), 4);
+    assert_eq!(agg.script_out_hist.get("p2sh"), Some(&2));
+    assert_eq!(agg.script_in_hist.get("p2sh_multisig"), Some(&2));
+    assert_eq!(agg.script_in_hist.get("p2pkh"), None);
+    assert_eq!(agg.locktime_hist.get("zero"), Some(&4));
+    assert_eq!(agg.expiry_hist.get("none"), Some(&4));
+    // The Sapling bundle has one output, below the padding convention.
+    assert_eq!(agg.sapling_bundle_hist.get("both"), Some(&2));
+    assert_eq!(agg.bundle_padding_hist.get("unpadded"), Some(&2));
+    assert_eq!(agg.orchard_flags_hist.get("none"), Some(&4));
+}
+
+#[test]
+fn storage_aggregate_merges_and_rejects_corrupt_rows() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    storage::upsert_block_stats(&conn, 10, &sample_stats()).unwrap();
+    storage::upsert_block_stats(&conn, 11, &sample_stats()).unwrap();
+    let blocks = storage::block_stats_in_range(&conn, 10, 12).unwrap();
+    assert_eq!(blocks.iter().map(|b| b.0).collect::<Vec<_>>(), vec![10, 11]);
+    let merged: ShapeStats = blocks.iter().map(|(_, s)| s).sum();
+    let agg = storage::aggregate_block_stats_in_range(&conn, 10, 12).unwrap();
+    assert_eq!(agg.n_txs, merged.n_txs);
+    assert_eq!(agg.shape_hist, merged.shape_hist);
+    assert_eq!(agg.size_entropy, merged.size_entropy);
+
+    conn.execute(
+        "UPDATE block_shapes SET shape_hist = 'not json' WHERE height = 11",
+        [],
+    )
+    .unwrap();
+    let err = storage::aggregate_block_stats_in_range(&conn, 10, 12).unwrap_err();
+    assert!(format!("{:#}", err).contains("block 11"));
+    assert!(storage::aggregate_block_stats_in_range(&conn, 10, 11).is_ok());
+}
+
+#[test]
+fn parse_v6_regtest_fixtures() {
+    let text = std::fs::read_to_string("tests/fixtures/v6_regtest.hex").unwrap();
+    let shapes = zcash_txshape::collector::rawtx::shapes_from_input(&text).unwrap();
+    assert_eq!(shapes.len(), 3);
+    assert!(shapes.iter().all(|s| s.version == 6));
+    assert_eq!((shapes[0].n_vin, shapes[0].n_orchard_action), (1, 2));
+    assert_eq!(shapes[0].scripts.in_p2pkh, 1);
+    assert_eq!((shapes[1].n_sapling_output, shapes[1].n_burn), (2, 2));
+    assert_eq!(shapes[2].n_issue_action, 2);
+    let stats = ShapeStats::from_shapes(&shapes);
+    assert_eq!(stats.issuance_hist.get("2"), Some(&1));
+    assert_eq!(stats.burn_hist.get("0"), Some(&2));
+    assert_eq!(stats.asset_types_hist.get("2"), Some(&2));
+    assert_eq!(stats.version_group_hist.get("nu7"), Some(&3));
+}
+
+#[test]
+fn version_group_lag_after_upgrade() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    let v4 = TxShape {
+        n_sapling_output: 2,
+        version: 4,
+        version_group: VersionGroup::Sapling,
+        ..Default::default()
+    };
+    let v5 = TxShape {
+        n_orchard_action: 2,
+        version: 5,
+        version_group: VersionGroup::Nu5,
+        branch: ConsensusBranch::Nu5,
+        ..Default::default()
+    };
+    // Mainnet NU5 activates at 1687104: v4 is only lagging from there on.
+    let nu5 = 1_687_104;
+    let blocks = [
+        (nu5 - 1, vec![v4.clone(), v4.clone()]),
+        (nu5, vec![v4.clone(), v5.clone()]),
+        (nu5 + 1, vec![v4.clone(), v4.clone(), v4.clone()]),
+    ];
+    for (height, shapes) in &blocks {
+        storage::upsert_block_stats(&conn, *height, &ShapeStats::from_shapes(shapes)).unwrap();
+    }
+    let agg = storage::aggregate_block_stats_in_range(&conn, nu5 - 1, nu5 + 2).unwrap();
+    assert_eq!(agg.version_group_hist.get("sapling"), Some(&6));
+    assert_eq!(agg.branch_hist.get("nu5"), Some(&1));
+
+    let opts = ReportOptions {
+        json: true,
+        ..Default::default()
+    };
+    let lagging = report::version_lag(&conn, nu5 - 1, nu5 + 2, Network::Main, opts).unwrap();
+    assert_eq!(lagging.len(), 1);
+    let l = &lagging[0];
+    assert_eq!(l.version_group, "sapling");
+    assert_eq!((l.txs, l.blocks), (4, 2));
+    assert_eq!((l.first_height, l.last_height), (nu5, nu5 + 1));
+    // Testnet activated NU5 later, so none of these blocks lag there.
+    let lagging = report::version_lag(&conn, nu5 - 1, nu5 + 2, Network::Test, opts).unwrap();
+    assert!(lagging.is_empty());
 }
 
 #[test]
@@ -62,7 +197,7 @@ fn report_daily_empty_db() {
     let dir = tempfile::tempdir().unwrap();
     let db_path = dir.path().join("test.db");
     let conn = storage::open_db(&db_path).unwrap();
-    let result = report::daily_summary(&conn, 7, false);
+    let result = report::daily_summary(&conn, 7, ReportOptions::default());
     result.unwrap();
 }
 
@@ -74,6 +209,667 @@ fn report_range_diff() {
     let stats = sample_stats();
     storage::upsert_block_stats(&conn, 0, &stats).unwrap();
     storage::upsert_block_stats(&conn, 1, &stats).unwrap();
-    let result = report::range_diff(&conn, 0, 1, 1, 2, false);
+    let view = report::BucketView {
+        top: 5,
+        sort: BucketSort::Ratio,
+    };
+    let result = report::range_diff(&conn, 0, 1, 1, 2, view, ReportOptions::default());
     result.unwrap();
 }
+
+#[test]
+fn report_range_compare() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let conn = storage::open_db(&db_path).unwrap();
+    let stats = sample_stats();
+    for h in 0..3 {
+        storage::upsert_block_stats(&conn, h, &stats).unwrap();
+    }
+    let ranges: Vec<report::LabeledRange> = (0..3)
+        .map(|h| report::LabeledRange {
+            label: format!("era{}", h),
+            low: h,
+            high: h + 1,
+        })
+        .collect();
+    report::range_compare(&conn, &ranges, ReportOptions::default()).unwrap();
+    let json = ReportOptions {
+        json: true,
+        ..Default::default()
+    };
+    report::range_compare(&conn, &ranges, json).unwrap();
+}
+
+#[test]
+fn report_change_points() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let conn = storage::open_db(&db_path).unwrap();
+    let transparent = ShapeStats::from_shapes(&[sample_shape(1, 0), sample_shape(1, 0)]);
+    let shielded = ShapeStats::from_shapes(&[sample_shape(0, 1), sample_shape(0, 1)]);
+    for h in 0..60 {
+        let stats = if h < 30 { &transparent } else { &shielded };
+        storage::upsert_block_stats(&conn, h, stats).unwrap();
+    }
+    // A single regime: nothing to report.
+    assert!(
+        report::change_points(&conn, 0, 30, 2, 0.9, ReportOptions::default())
+            .unwrap()
+            .is_empty()
+    );
+
+    let found = report::change_points(&conn, 0, 60, 2, 0.9, ReportOptions::default()).unwrap();
+    let shielded_share: Vec<_> = found
+        .iter()
+        .filter(|c| c.metric == "shielded_share")
+        .collect();
+    assert!(!shielded_share.is_empty());
+    for c in &shielded_share {
+        // The step is at height 30; windows are 2 blocks wide.
+        assert!((28..=32).contains(&c.height), "{:?}", c);
+        assert!(c.confidence >= 0.9);
+        assert!(c.mean_before < 0.01 && c.mean_after > 0.99);
+    }
+    assert!(shielded_share.iter().any(|c| c.method == "cusum"));
+    assert!(shielded_share.iter().any(|c| c.method == "bocpd"));
+
+    let json = ReportOptions {
+        json: true,
+        ..Default::default()
+    };
+    let again = report::change_points(&conn, 0, 60, 2, 0.9, json).unwrap();
+    assert_eq!(again.len(), found.len());
+
+    // Same (vin, vout) pair throughout, but the block's two txs split into two shapes (size
+    // buckets) from height 130: only the full-tuple top share moves.
+    let larger = TxShape {
+        size_bucket: size_bucket(3_000),
+        ..sample_shape(1, 0)
+    };
+    let split = ShapeStats::from_shapes(&[sample_shape(1, 0), larger]);
+    for h in 100..160 {
+        let stats = if h < 130 { &transparent } else { &split };
+        storage::upsert_block_stats(&conn, h, stats).unwrap();
+    }
+    let found = report::change_points(&conn, 100, 160, 2, 0.9, ReportOptions::default()).unwrap();
+    let top: Vec<_> = found
+        .iter()
+        .filter(|c| c.metric == "top_shape_share")
+        .collect();
+    assert!(!top.is_empty());
+    for c in &top {
+        assert!((128..=132).contains(&c.height), "{:?}", c);
+        assert!(c.mean_before > 0.99 && (c.mean_after - 0.5).abs() < 0.01);
+    }
+}
+
+#[test]
+fn detect_floods_and_exclude_from_reports() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let conn = storage::open_db(&db_path).unwrap();
+    let normal = sample_stats();
+    let spam = ShapeStats::from_shapes(&vec![sample_shape(0, 2); 300]);
+    for h in 0..40 {
+        let stats = if (20..24).contains(&h) {
+            &spam
+        } else {
+            &normal
+        };
+        storage::upsert_block_stats(&conn, h, stats).unwrap();
+    }
+    let cfg = FloodConfig::default();
+    let windows = storage::flag_floods(&conn, 0, 40, &cfg).unwrap();
+    assert_eq!(windows.len(), 1);
+    assert_eq!((windows[0].low, windows[0].high), (20, 24));
+    assert_eq!(
+        storage::flagged_windows_in_range(&conn, 0, 40).unwrap(),
+        windows
+    );
+    report::flood_windows(&conn, 0, 40, &cfg, ReportOptions::default()).unwrap();
+
+    let all = storage::aggregate_block_stats_in_range(&conn, 0, 40).unwrap();
+    let clean = storage::aggregate_block_stats_excluding_floods(&conn, 0, 40).unwrap();
+    assert_eq!(all.n_txs, 36 * 2 + 4 * 300);
+    assert_eq!(clean.n_txs, 36 * 2);
+}
+
+#[test]
+fn anomaly_alerts_against_trailing_baseline() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let conn = storage::open_db(&db_path).unwrap();
+    let cfg = AlertsConfig {
+        window_blocks: 10,
+        ..Default::default()
+    };
+    let mixed = |h: u32| {
+        let mut shapes = vec![sample_shape(1, 0); 8 + (h % 3) as usize];
+        shapes.extend(vec![sample_shape(0, 2); 2]);
+        ShapeStats::from_shapes(&shapes)
+    };
+    for h in 0..80 {
+        storage::upsert_block_stats(&conn, h, &mixed(h)).unwrap();
+    }
+    let opts = ReportOptions::default();
+    assert!(report::anomaly_alerts(&conn, &cfg, opts)
+        .unwrap()
+        .alerts
+        .is_empty());
+
+    let shielded = ShapeStats::from_shapes(&vec![sample_shape(0, 2); 10]);
+    for h in 80..90 {
+        storage::upsert_block_stats(&conn, h, &shielded).unwrap();
+    }
+    let result = report::anomaly_alerts(&conn, &cfg, opts).unwrap();
+    assert_eq!((result.window_low, result.window_high), (80, 90));
+    let alerts = result.alerts;
+    let shielded = alerts
+        .iter()
+        .find(|a| a.kind == "metric" && a.subject == "shielded_share")
+        .unwrap();
+    // Baseline windows are 2 of 10-12 txs shielded; the latest window is all shielded.
+    assert_eq!(shielded.value, 1.0);
+    assert!(shielded.baseline > 0.15 && shielded.baseline < 0.21);
+    assert!(shielded.score >= cfg.z_threshold);
+    let vin_vout = alerts
+        .iter()
+        .find(|a| a.kind == "divergence" && a.subject == "vin_vout")
+        .unwrap();
+    assert!(vin_vout.value >= cfg.divergence_threshold);
+    // The transparent bucket collapses and the shielded bucket takes over.
+    let bucket = |subject: &str| {
+        alerts
+            .iter()
+            .find(|a| a.kind == "bucket_share" && a.subject == subject)
+            .map(|a| a.score)
+    };
+    assert!(bucket("vin_vout:1_1").unwrap() <= -cfg.z_threshold);
+    assert!(bucket("vin_vout:0_1").unwrap() >= cfg.z_threshold);
+    // Size buckets and versions did not change.
+    assert!(!alerts
+        .iter()
+        .any(|a| a.subject == "version" || a.subject == "size_bucket"));
+}
+
+#[test]
+fn anomaly_alerts_skip_metrics_without_baseline() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    let cfg = AlertsConfig {
+        window_blocks: 10,
+        ..Default::default()
+    };
+    let mixed = |h: u32| {
+        let mut shapes = vec![sample_shape(1, 0); 8 + (h % 3) as usize];
+        shapes.extend(vec![sample_shape(0, 2); 2]);
+        ShapeStats::from_shapes(&shapes)
+    };
+    // Only the last baseline window [70, 80) has logical actions and shape tuples; the latest
+    // window [80, 90) has a few more shielded transactions.
+    let latest = ShapeStats::from_shapes(
+        &[vec![sample_shape(1, 0); 8], vec![sample_shape(0, 2); 3]].concat(),
+    );
+    for h in 0..90 {
+        let mut stats = if h < 80 { mixed(h) } else { latest.clone() };
+        if h < 70 {
+            stats.logical_actions_hist.clear();
+            stats.shape_hist.clear();
+        }
+        storage::upsert_block_stats(&conn, h, &stats).unwrap();
+    }
+    let result = report::anomaly_alerts(&conn, &cfg, ReportOptions::default()).unwrap();
+    // With a single baseline value, std would be 0 and any change a huge z-score.
+    assert!(
+        result
+            .alerts
+            .iter()
+            .all(|a| a.subject != "grace_action_share"
+                && a.subject != "shape"
+                && !a.subject.starts_with("shape:")
+                && a.subject != "logical_actions"
+                && !a.subject.starts_with("logical_actions:")),
+        "{:?}",
+        result.alerts
+    );
+}
+
+/// Accept `n` HTTP requests on a local port and return their JSON bodies.
+fn spawn_webhook(n: usize) -> (String, std::thread::JoinHandle<Vec<serde_json::Value>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut bodies = Vec::new();
+        for stream in listener.incoming().take(n) {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut len = 0usize;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(v) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    len = v.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0u8; len];
+            reader.read_exact(&mut body).unwrap();
+            bodies.push(serde_json::from_slice(&body).unwrap());
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+        }
+        bodies
+    });
+    (url, handle)
+}
+
+#[tokio::test]
+async fn alert_delivery_with_dedup_and_cooldown() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    let ndjson = dir.path().join("alerts.ndjson");
+    let (url, webhook) = spawn_webhook(2);
+    let cfg = AlertsConfig {
+        rules: vec![AlertRule {
+            name: "shielded-band".to_string(),
+            metric: "shielded_share".to_string(),
+            min: Some(0.6),
+            max: None,
+        }],
+        sinks: vec![
+            SinkConfig::Webhook {
+                url,
+                timeout_secs: 5,
+            },
+            SinkConfig::File {
+                path: ndjson.clone(),
+            },
+        ],
+        cooldown_secs: 600,
+        ..Default::default()
+    };
+    let alerts: Vec<Alert> = alert::evaluate_rules(&sample_stats(), &cfg.rules);
+    assert_eq!(alerts.len(), 1);
+    let window = |high: u32| WindowAlerts {
+        window_low: high - 10,
+        window_high: high,
+        alerts: alerts.clone(),
+    };
+
+    let sent = alert::deliver(&conn, &cfg, &window(10), 1000)
+        .await
+        .unwrap();
+    assert_eq!(sent.len(), 1);
+    // Same window again: deduplicated.
+    assert!(alert::deliver(&conn, &cfg, &window(10), 2000)
+        .await
+        .unwrap()
+        .is_empty());
+    // New window inside the cool-down: suppressed.
+    assert!(alert::deliver(&conn, &cfg, &window(20), 1500)
+        .await
+        .unwrap()
+        .is_empty());
+    // New window after the cool-down: delivered again.
+    let sent = alert::deliver(&conn, &cfg, &window(20), 1600)
+        .await
+        .unwrap();
+    assert_eq!(sent.len(), 1);
+
+    let bodies = webhook.join().unwrap();
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[0]["alerts"][0]["key"], "rule:shielded-band");
+    assert_eq!(bodies[1]["alerts"][0]["window_high"], 20);
+    let lines: Vec<serde_json::Value> = std::fs::read_to_string(&ndjson)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["subject"], "shielded-band");
+    assert_eq!(lines[0]["sent_at"], 1000);
+}
+
+#[tokio::test]
+async fn alert_delivery_retries_only_failed_sinks() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    let ndjson = dir.path().join("alerts.ndjson");
+    // A port nobody listens on: the webhook always fails.
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/alerts", closed.local_addr().unwrap());
+    drop(closed);
+    let cfg = AlertsConfig {
+        rules: vec![AlertRule {
+            name: "shielded-band".to_string(),
+            metric: "shielded_share".to_string(),
+            min: Some(0.6),
+            max: None,
+        }],
+        sinks: vec![
+            SinkConfig::File {
+                path: ndjson.clone(),
+            },
+            SinkConfig::Webhook {
+                url,
+                timeout_secs: 5,
+            },
+        ],
+        ..Default::default()
+    };
+    let window = WindowAlerts {
+        window_low: 0,
+        window_high: 10,
+        alerts: alert::evaluate_rules(&sample_stats(), &cfg.rules),
+    };
+    assert!(alert::deliver(&conn, &cfg, &window, 1000).await.is_err());
+    assert!(alert::deliver(&conn, &cfg, &window, 2000).await.is_err());
+    // The file sink succeeded the first time and is not written again on retry.
+    let lines = std::fs::read_to_string(&ndjson).unwrap();
+    assert_eq!(lines.lines().count(), 1);
+    assert!(
+        storage::alert_last_sent(&conn, "rule:shielded-band", &cfg.sinks[1].id())
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn score_shapes_against_reference_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    let mut shapes = vec![sample_shape(1, 0); 9];
+    shapes.push(sample_shape(0, 2));
+    for h in 0..5 {
+        storage::upsert_block_stats(&conn, h, &ShapeStats::from_shapes(&shapes)).unwrap();
+    }
+    let wallet = [sample_shape(1, 0), sample_shape(0, 2), sample_shape(5, 5)];
+    let scores = report::score_shapes(&conn, &wallet, 0, 5, ReportOptions::default()).unwrap();
+    assert_eq!(scores.len(), 3);
+    assert_eq!(scores[0].count, 45);
+    assert_eq!(scores[0].rank, Some(1));
+    assert_eq!(scores[1].rank, Some(2));
+    assert_eq!(scores[2].rank, None);
+    assert!((scores[1].share - 0.1).abs() < 1e-12);
+    assert!(scores[2].surprisal_bits > scores[1].surprisal_bits);
+    assert_eq!(report::recent_range(&conn, 3).unwrap(), Some((2, 5)));
+}
+
+#[test]
+fn legacy_rows_do_not_dilute_shares() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    let mut shapes = vec![sample_shape(1, 0); 5];
+    shapes.extend(vec![sample_shape(0, 5); 5]);
+    let stats = ShapeStats::from_shapes(&shapes);
+    for h in 0..20 {
+        storage::upsert_block_stats(&conn, h, &stats).unwrap();
+    }
+    // Blocks 10..20 look like rows stored before logical actions were recorded.
+    conn.execute(
+        "UPDATE block_shapes SET logical_actions_hist = '{}' WHERE height >= 10",
+        [],
+    )
+    .unwrap();
+
+    let all = storage::aggregate_block_stats_in_range(&conn, 0, 20).unwrap();
+    assert_eq!(all.n_txs, 200);
+    assert_eq!(all.grace_action_share(), Some(0.5));
+    assert_eq!(all.uncovered_txs(), vec![("logical_actions", 100)]);
+
+    let a = storage::aggregate_block_stats_in_range(&conn, 0, 10).unwrap();
+    let b = storage::aggregate_block_stats_in_range(&conn, 10, 20).unwrap();
+    let tests = range_tests(&a, &b);
+    assert!(tests.iter().all(|t| t.subject != "grace_action_share"));
+    assert!(tests.iter().all(|t| !t.significant));
+
+    let candidates = report::change_points(&conn, 0, 20, 2, 0.5, ReportOptions::default()).unwrap();
+    assert!(candidates.is_empty());
+}
+
+#[test]
+fn export_baseline_and_ci_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    let mut shapes = vec![sample_shape(1, 0); 99];
+    shapes.push(sample_shape(0, 2));
+    storage::upsert_block_stats(&conn, 7, &ShapeStats::from_shapes(&shapes)).unwrap();
+    let path = dir.path().join("baseline.json");
+    let exported = report::export_baseline(&conn, 0, 10, ReportOptions::default(), &path).unwrap();
+    let loaded = BaselineFile::from_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(loaded, exported);
+    assert_eq!(loaded.distribution.total, 100);
+    assert!(report::export_baseline(&conn, 20, 30, ReportOptions::default(), &path).is_err());
+    // Legacy rows have transactions but no full-shape histogram.
+    let mut legacy = ShapeStats::from_shapes(&shapes);
+    legacy.shape_hist.clear();
+    storage::upsert_block_stats(&conn, 25, &legacy).unwrap();
+    assert!(report::export_baseline(&conn, 20, 30, ReportOptions::default(), &path).is_err());
+
+    let thresholds = report::CiThresholds {
+        min_share: 0.05,
+        max_rank: None,
+    };
+    let fixtures = [sample_shape(1, 0), sample_shape(0, 2), sample_shape(3, 3)];
+    let result = report::ci_check(&fixtures, &loaded, thresholds);
+    assert_eq!((result.tests, result.failures), (3, 2));
+    assert!(result.cases[0].passed);
+    assert!(result.cases[2].failure.is_some());
+    let ranked = report::CiThresholds {
+        min_share: 0.0,
+        max_rank: Some(1),
+    };
+    assert_eq!(report::ci_check(&fixtures, &loaded, ranked).failures, 2);
+    let xml = report::junit_xml(&result);
+    assert!(xml.contains("tests=\"3\" failures=\"2\""));
+    assert_eq!(xml.matches("<failure ").count(), 2);
+}
+
+#[test]
+fn simulate_padding_on_stored_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    let shapes: Vec<TxShape> = (1..=4).map(|n| sample_shape(0, n)).collect();
+    storage::upsert_block_stats(&conn, 3, &ShapeStats::from_shapes(&shapes)).unwrap();
+    let policies = [PaddingPolicy {
+        name: "pow2-bucket-3".to_string(),
+        min_outputs: Some(2),
+        round_pow2: true,
+        pad_size_bucket: Some(3),
+    }];
+    let outcomes =
+        report::simulate_padding(&conn, 0, 10, &policies, ReportOptions::default()).unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(outcomes[0].policy, "none");
+    assert_eq!(outcomes[0].distinct_shapes, 4);
+    assert_eq!(outcomes[1].n_txs, 4);
+    assert_eq!(outcomes[1].distinct_shapes, 2);
+    assert!(outcomes[1].overhead_bytes > 0);
+}
+
+fn fixture_scenario() -> synth::Scenario {
+    let path =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.toml");
+    synth::Scenario::load(&path).unwrap()
+}
+
+#[test]
+fn synthetic_burst_is_detected_as_flood() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    let scenario = fixture_scenario();
+    let (blocks, truth) = synth::generate(&scenario).unwrap();
+    synth::write_to_db(&conn, &blocks).unwrap();
+
+    let total =
+        storage::aggregate_block_stats_in_range(&conn, scenario.low, scenario.high).unwrap();
+    assert_eq!(total.n_txs, truth.n_txs);
+    let windows =
+        storage::flag_floods(&conn, scenario.low, scenario.high, &FloodConfig::default()).unwrap();
+    assert_eq!(windows.len(), 1, "{:?}", windows);
+    let burst = &truth.bursts[0];
+    assert_eq!((windows[0].low, windows[0].high), (burst.low, burst.high));
+    assert_eq!(windows[0].shape, burst.shape);
+    assert!(windows[0].shape_count >= burst.n_txs);
+}
+
+#[test]
+fn report_mutual_information() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    storage::upsert_block_stats(&conn, 10, &sample_stats()).unwrap();
+    storage::upsert_block_stats(&conn, 11, &sample_stats()).unwrap();
+    report::mutual_information(&conn, 10, 12, ReportOptions::default()).unwrap();
+    let stats = storage::aggregate_block_stats_in_range(&conn, 10, 12).unwrap();
+    let m = dimension_matrix(&stats.shape_hist);
+    assert_eq!(m.n_txs, 4);
+    let vin = m.dimensions.iter().position(|&d| d == "vin").unwrap();
+    let spend = m
+        .dimensions
+        .iter()
+        .position(|&d| d == "sapling_spend")
+        .unwrap();
+    // Transparent and Sapling transactions: either count determines the other.
+    assert!(m.conditional_entropy[vin][spend] < 1e-9);
+    assert!(m.mutual_information[vin][spend] > 0.9);
+}
+
+#[test]
+fn synthetic_block_composition() {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = storage::open_db(&dir.path().join("test.db")).unwrap();
+    let scenario = fixture_scenario();
+    let (blocks, _) = synth::generate(&scenario).unwrap();
+    synth::write_to_db(&conn, &blocks).unwrap();
+
+    let metas = storage::block_meta_in_range(&conn, scenario.low, scenario.high).unwrap();
+    assert_eq!(metas.len(), blocks.len());
+    let composition = zcash_txshape::model::block::compose(&metas);
+    assert_eq!(composition.mean_interval_secs, Some(75.0));
+    let interval = &composition.histograms[3];
+    assert_eq!(interval.name, "block_interval");
+    assert_eq!(interval.buckets[2].1, blocks.len() as u64 - 1);
+    let opts = ReportOptions {
+        json: true,
+        ..Default::default()
+    };
+    report::block_composition(&conn, scenario.low, scenario.high, opts).unwrap();
+}
+
+#[tokio::test]
+async fn collect_from_synthetic_mock_node() {
+    let mut scenario = fixture_scenario();
+    scenario.high = scenario.low + 12;
+    let (blocks, truth) = synth::generate(&scenario).unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let served = std::sync::Arc::new(synth::mock_node::block_map(blocks.clone()));
+    let server = tokio::spawn(synth::mock_node::serve(listener, served));
+
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        format!(
+            "[node]\nrpc_url = \"http://{}\"\n[storage]\ndb_path = \"{}\"\n[collector]\nbatch_delay_ms = 0\n",
+            addr,
+            dir.path().join("test.db").display()
+        ),
+    )
+    .unwrap();
+    let config = Config::load(&config_path).unwrap();
+    let conn = storage::open_db(&config.storage.db_path).unwrap();
+    // A row from an earlier collection is replaced, not kept next to fresh range stats.
+    storage::upsert_block_stats(&conn, scenario.low, &sample_stats()).unwrap();
+    zcash_txshape::collector::run_collect(&config, &conn, scenario.low, scenario.high + 1)
+        .await
+        .unwrap();
+    server.abort();
+
+    let heights = storage::block_heights_in_range(&conn, 0, u32::MAX).unwrap();
+    assert_eq!(heights, (scenario.low..scenario.high).collect::<Vec<_>>());
+    for b in &blocks {
+        let stored = storage::get_block_stats(&conn, b.height).unwrap().unwrap();
+        let expected = ShapeStats::from_shapes(&b.shapes);
+        assert_eq!(stored.n_txs, expected.n_txs);
+        assert_eq!(stored.shape_hist, expected.shape_hist);
+        assert_eq!(stored.coinbase_txs(), 1);
+    }
+    // Block size and time reach block_meta exactly as the synthetic writer computes them.
+    let metas = storage::block_meta_in_range(&conn, scenario.low, scenario.high).unwrap();
+    let expected: Vec<_> = blocks
+        .iter()
+        .map(|b| synth::block_meta(b.height, &b.shapes))
+        .collect();
+    assert_eq!(metas, expected);
+    let range = storage::get_range_stats(&conn, scenario.low, scenario.high + 1)
+        .unwrap()
+        .unwrap();
+    assert_eq!(range.n_txs, truth.n_txs);
+    assert_eq!(range.coinbase_txs(), truth.coinbase_txs);
+    assert_eq!(
+        range.coinbase_hist.get("3_0_0_0_5"),
+        Some(&truth.coinbase_txs)
+    );
+    // Range stats are merged per block, so they match the stored blocks' aggregate.
+    let agg =
+        storage::aggregate_block_stats_in_range(&conn, scenario.low, scenario.high + 1).unwrap();
+    assert_eq!(range.shape_hist, agg.shape_hist);
+    assert_eq!(range.size_bucket_hist, agg.size_bucket_hist);
+    assert!((range.size_entropy - agg.size_entropy).abs() < 1e-12);
+    // Coinbase txs are stored on their own and only counted as user txs on request.
+    let sel = storage::BlockSelection {
+        include_coinbase: true,
+        ..Default::default()
+    };
+    let with_coinbase =
+        storage::aggregate_blocks(&conn, scenario.low, scenario.high + 1, sel).unwrap();
+    assert_eq!(with_coinbase.n_txs, truth.n_txs + truth.coinbase_txs);
+    assert_eq!(with_coinbase.coinbase_hist, agg.coinbase_hist);
+    assert_eq!(
+        with_coinbase.vin_vout_hist.get("1_3").copied().unwrap_or(0),
+        agg.vin_vout_hist.get("1_3").copied().unwrap_or(0) + truth.coinbase_txs
+    );
+}
+
+#[test]
+fn legacy_block_meta_size_is_unknown() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("test.db");
+    {
+        // Schema of older versions, which stored 0 for an unreported size.
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE block_meta (
+                 height INTEGER NOT NULL PRIMARY KEY,
+                 n_txs INTEGER NOT NULL,
+                 shielded_txs INTEGER NOT NULL,
+                 size INTEGER NOT NULL,
+                 time INTEGER
+             );
+             INSERT INTO block_meta VALUES (10, 3, 1, 0, 1000), (11, 2, 0, 1000000, 1075);",
+        )
+        .unwrap();
+    }
+    let conn = storage::open_db(&path).unwrap();
+    let metas = storage::block_meta_in_range(&conn, 10, 12).unwrap();
+    assert_eq!(metas[0].size, None);
+    assert_eq!(metas[1].size, Some(1_000_000));
+    let composition = zcash_txshape::model::block::compose(&metas);
+    assert_eq!(composition.sized_blocks, 1);
+    assert_eq!(composition.mean_fullness, Some(0.5));
+
+    // New rows may leave the size out.
+    let mut meta = metas[1];
+    meta.height = 12;
+    meta.size = None;
+    storage::upsert_block_meta(&conn, &meta).unwrap();
+    assert_eq!(
+        storage::block_meta_in_range(&conn, 12, 13).unwrap()[0].size,
+        None
+    );
+}